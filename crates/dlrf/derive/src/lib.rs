@@ -1,6 +1,9 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{ItemStruct, LitStr};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::{
+    parse::Parser, punctuated::Punctuated, spanned::Spanned, Expr, Fields, ItemStruct, LitStr,
+    MetaNameValue, Token,
+};
 
 #[proc_macro_attribute]
 pub fn singleton(args: TokenStream, input: TokenStream) -> TokenStream {
@@ -16,3 +19,126 @@ pub fn singleton(args: TokenStream, input: TokenStream) -> TokenStream {
         }
     })
 }
+
+/// Asserts a struct's layout at compile time. `size` checks the size of the struct and
+/// `#[offset(...)]` checks the offset of a field:
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[dlrf::game_layout(size = 0x10)]
+/// pub struct Example {
+///     vftable: usize,
+///     #[offset(0x8)]
+///     pub value: u32,
+///     unkc: u32,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn game_layout(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut input_struct: ItemStruct = syn::parse_macro_input!(input as ItemStruct);
+    match layout_assertions(args, &mut input_struct) {
+        Ok(assertions) => TokenStream::from(quote! {
+            #input_struct
+
+            #(#assertions)*
+        }),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+/// Strips the `#[offset]` field attributes and turns them, along with the struct's size, into
+/// const assertions. Every assertion is its own item so that all mismatches get reported.
+fn layout_assertions(
+    args: TokenStream,
+    input_struct: &mut ItemStruct,
+) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    if !input_struct.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input_struct.generics.span(),
+            "game_layout does not support generic structs",
+        ));
+    }
+
+    if !is_repr_c(input_struct)? {
+        return Err(syn::Error::new(
+            input_struct.ident.span(),
+            "game_layout requires #[repr(C)], the layout of other structs isn't stable",
+        ));
+    }
+
+    let ident = input_struct.ident.clone();
+    let mut assertions = Vec::new();
+
+    let args = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse(args)?;
+    for arg in args {
+        if !arg.path.is_ident("size") {
+            return Err(syn::Error::new(
+                arg.path.span(),
+                "expected `size = <bytes>`",
+            ));
+        }
+
+        let size = arg.value;
+        let message = layout_message(&ident.to_string(), "size", &size);
+        assertions.push(quote_spanned! {size.span()=>
+            const _: () = assert!(::core::mem::size_of::<#ident>() == #size, #message);
+        });
+    }
+
+    let Fields::Named(fields) = &mut input_struct.fields else {
+        return Err(syn::Error::new(
+            input_struct.fields.span(),
+            "game_layout requires named fields",
+        ));
+    };
+
+    for field in fields.named.iter_mut() {
+        let field_ident = field.ident.clone().unwrap();
+        let mut offsets = Vec::new();
+        field.attrs.retain(|attr| {
+            let is_offset = attr.path().is_ident("offset");
+            if is_offset {
+                offsets.push(attr.parse_args::<Expr>());
+            }
+            !is_offset
+        });
+
+        for offset in offsets {
+            let offset = offset?;
+            let message = layout_message(&format!("{ident}::{field_ident}"), "offset", &offset);
+            assertions.push(quote_spanned! {offset.span()=>
+                const _: () =
+                    assert!(::core::mem::offset_of!(#ident, #field_ident) == #offset, #message);
+            });
+        }
+    }
+
+    Ok(assertions)
+}
+
+fn is_repr_c(input_struct: &ItemStruct) -> syn::Result<bool> {
+    let mut repr_c = false;
+    for attr in input_struct.attrs.iter() {
+        if attr.path().is_ident("repr") {
+            attr.parse_nested_meta(|meta| {
+                repr_c |= meta.path.is_ident("C");
+                // Skip the arguments of `align(..)` and `packed(..)`.
+                if meta.input.peek(syn::token::Paren) {
+                    meta.input.parse::<proc_macro2::Group>()?;
+                }
+                Ok(())
+            })?;
+        }
+    }
+    Ok(repr_c)
+}
+
+fn layout_message(subject: &str, property: &str, expected: &Expr) -> LitStr {
+    LitStr::new(
+        &format!(
+            "{subject} {property} differs from the expected {}",
+            expected.to_token_stream()
+        ),
+        expected.span(),
+    )
+}
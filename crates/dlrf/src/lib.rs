@@ -3,4 +3,79 @@ pub trait DLRFSingleton {
     const DLRF_NAME: &'static str;
 }
 
-pub use dlrf_derive::singleton;
+pub use dlrf_derive::{game_layout, singleton};
+
+/// Checks that [`game_layout`] accepts matching layouts and rejects everything else.
+///
+/// ```
+/// #[repr(C)]
+/// #[eldenring_dlrf::game_layout(size = 0x10)]
+/// struct Example {
+///     vftable: usize,
+///     #[offset(0x8)]
+///     value: u32,
+///     unkc: u32,
+/// }
+/// ```
+///
+/// Wrong size:
+///
+/// ```compile_fail
+/// #[repr(C)]
+/// #[eldenring_dlrf::game_layout(size = 0x8)]
+/// struct Example {
+///     vftable: usize,
+///     value: u32,
+/// }
+/// ```
+///
+/// Wrong offset:
+///
+/// ```compile_fail
+/// #[repr(C)]
+/// #[eldenring_dlrf::game_layout]
+/// struct Example {
+///     vftable: usize,
+///     #[offset(0xc)]
+///     value: u32,
+/// }
+/// ```
+///
+/// Generic struct:
+///
+/// ```compile_fail
+/// #[repr(C)]
+/// #[eldenring_dlrf::game_layout(size = 0x8)]
+/// struct Example<T> {
+///     value: T,
+/// }
+/// ```
+///
+/// Tuple struct:
+///
+/// ```compile_fail
+/// #[repr(C)]
+/// #[eldenring_dlrf::game_layout(size = 0x8)]
+/// struct Example(usize);
+/// ```
+///
+/// Unknown argument:
+///
+/// ```compile_fail
+/// #[repr(C)]
+/// #[eldenring_dlrf::game_layout(align = 0x8)]
+/// struct Example {
+///     vftable: usize,
+/// }
+/// ```
+///
+/// Missing `#[repr(C)]`:
+///
+/// ```compile_fail
+/// #[eldenring_dlrf::game_layout(size = 0x8)]
+/// struct Example {
+///     vftable: usize,
+/// }
+/// ```
+#[cfg(doctest)]
+pub struct GameLayoutTests;
@@ -5,6 +5,8 @@ mod character_debug_flags;
 mod character_type_properties;
 mod chr_ins;
 mod event_flag;
+mod event_flag_registry;
+mod event_flag_store;
 mod ez_select_bot;
 mod ez_state;
 mod fade;
@@ -48,6 +50,8 @@ pub use character_debug_flags::*;
 pub use character_type_properties::*;
 pub use chr_ins::*;
 pub use event_flag::*;
+pub use event_flag_registry::*;
+pub use event_flag_store::*;
 pub use ez_select_bot::*;
 pub use ez_state::*;
 pub use fade::*;
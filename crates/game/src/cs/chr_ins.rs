@@ -480,6 +480,14 @@ pub struct CSChrTimeActModule {
     unkd4: u32,
 }
 
+impl CSChrTimeActModule {
+    /// Animation that was most recently played or updated. Its `play_time` can be matched
+    /// against the event times in the character's TAE file.
+    pub fn current_anim(&self) -> &CSChrTimeActModuleAnim {
+        &self.anim_queue[self.read_idx as usize % self.anim_queue.len()]
+    }
+}
+
 #[repr(C)]
 pub struct CSChrBehaviorModule {
     vftable: usize,
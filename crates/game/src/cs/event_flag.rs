@@ -1,7 +1,8 @@
-use std::mem::ManuallyDrop;
+use std::{mem::ManuallyDrop, ptr::NonNull};
 
 use crate::{pointer::OwnedPtr, Tree};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EventFlag(u32);
 
 impl From<u32> for EventFlag {
@@ -10,6 +11,12 @@ impl From<u32> for EventFlag {
     }
 }
 
+impl From<EventFlag> for u32 {
+    fn from(value: EventFlag) -> Self {
+        value.0
+    }
+}
+
 impl EventFlag {
     pub fn group(&self) -> u32 {
         self.0 / 1000
@@ -60,15 +67,7 @@ impl CSFD4VirtualMemoryFlag {
     /// Sets the event flag bit for a given event flag. Does not inherently network set flags.
     pub fn set_flag(&mut self, flag: impl Into<EventFlag>, state: bool) {
         let flag: EventFlag = flag.into();
-        let Some(group) = self
-            .flag_block_descriptors
-            .iter()
-            .find(|d| d.group == flag.group())
-        else {
-            return;
-        };
-
-        let Some(location) = self.flag_block(group) else {
+        let Some(location) = self.group_block_mut(flag.group()) else {
             return;
         };
 
@@ -78,32 +77,45 @@ impl CSFD4VirtualMemoryFlag {
     /// Retrieves the event flag current state.
     pub fn get_flag(&self, flag: impl Into<EventFlag>) -> bool {
         let flag: EventFlag = flag.into();
-        let Some(group) = self
-            .flag_block_descriptors
-            .iter()
-            .find(|d| d.group == flag.group())
-        else {
-            return false;
-        };
+        self.group_block(flag.group())
+            .is_some_and(|location| location.get(flag))
+    }
 
-        let Some(location) = self.flag_block(group) else {
-            return false;
-        };
+    /// Looks up the descriptor for a group. The descriptor tree is keyed by group.
+    pub fn descriptor(&self, group: u32) -> Option<&FlagBlockDescriptor> {
+        self.flag_block_descriptors.find(&group, |d| d.group)
+    }
+
+    /// Locates the flag block holding a group's flags.
+    pub fn group_block(&self, group: u32) -> Option<&FlagBlock> {
+        self.flag_block(self.descriptor(group)?)
+    }
 
-        location.get(flag)
+    /// Locates the flag block holding a group's flags.
+    pub fn group_block_mut(&mut self, group: u32) -> Option<&mut FlagBlock> {
+        let block = self.flag_block_ptr(self.descriptor(group)?)?;
+        Some(unsafe { &mut *block.as_ptr() })
     }
 
     /// Locates a flag block for a given FlagBlockDescriptor.
-    fn flag_block<'a>(&self, descriptor: &'a mut FlagBlockDescriptor) -> Option<&'a mut FlagBlock> {
-        Some(match descriptor.location_mode {
-            1 => unsafe {
+    pub(crate) fn flag_block(&self, descriptor: &FlagBlockDescriptor) -> Option<&FlagBlock> {
+        Some(unsafe { self.flag_block_ptr(descriptor)?.as_ref() })
+    }
+
+    /// Pointer to the flag block for a given FlagBlockDescriptor. Blocks live in the flag holder
+    /// or behind the descriptor, so they can be mutated while the descriptor is borrowed.
+    pub(crate) fn flag_block_ptr(
+        &self,
+        descriptor: &FlagBlockDescriptor,
+    ) -> Option<NonNull<FlagBlock>> {
+        match descriptor.location_mode {
+            1 => NonNull::new(unsafe {
                 self.flag_blocks
                     .add(descriptor.location.holder_offset as usize)
-                    .as_mut()?
-            },
-            2 => unsafe { (*descriptor.location.external_location).as_mut() },
-            _ => return None,
-        })
+            }),
+            2 => NonNull::new(unsafe { descriptor.location.external_location.as_ptr() }),
+            _ => None,
+        }
     }
 }
 
@@ -118,6 +130,16 @@ pub struct FlagBlockDescriptor {
     location: FlagBlockLocationUnion,
 }
 
+impl FlagBlockDescriptor {
+    /// Index of the flag block within the flag holder, if the block is stored there.
+    pub fn holder_offset(&self) -> Option<u32> {
+        match self.location_mode {
+            1 => Some(unsafe { self.location.holder_offset }),
+            _ => None,
+        }
+    }
+}
+
 union FlagBlockLocationUnion {
     holder_offset: u32,
     external_location: ManuallyDrop<OwnedPtr<FlagBlock>>,
@@ -129,12 +151,30 @@ pub enum FlagBlockLocation {
 }
 
 #[repr(C)]
+#[derive(Clone, PartialEq, Eq)]
 /// Contains the actual flag bits
 pub struct FlagBlock {
     bytes: [u8; 125],
 }
 
+impl Default for FlagBlock {
+    fn default() -> Self {
+        Self { bytes: [0; 125] }
+    }
+}
+
 impl FlagBlock {
+    /// Number of flags a block can hold.
+    pub const FLAG_COUNT: u32 = 125 * 8;
+
+    pub(crate) fn bytes(&self) -> &[u8; 125] {
+        &self.bytes
+    }
+
+    pub(crate) fn bytes_mut(&mut self) -> &mut [u8; 125] {
+        &mut self.bytes
+    }
+
     pub fn set(&mut self, flag: EventFlag, state: bool) {
         let byte = &mut self.bytes[flag.byte() as usize];
         let mask = 0b00000001 << flag.bit();
@@ -151,4 +191,12 @@ impl FlagBlock {
 
         (*byte & mask) != 0
     }
+
+    /// Retrieves the flag at `index` within its group, for groups that aren't 1000 flags in size.
+    /// Indices past the end of the block are never set.
+    pub fn get_index(&self, index: u32) -> bool {
+        self.bytes
+            .get((index / 8) as usize)
+            .is_some_and(|byte| byte & (0b1000_0000 >> (index % 8)) != 0)
+    }
 }
@@ -0,0 +1,261 @@
+//! Names and categories for event flags, loaded from a data file so tools don't have to hard-code
+//! flag IDs.
+use std::ops::{Range, RangeInclusive};
+
+use super::{CSEventFlagMan, CSFD4VirtualMemoryFlag, EventFlag, EventFlagStore, FlagBlock};
+
+/// Anything event flags can be read from, live or offline.
+pub trait EventFlagSource {
+    fn get_flag(&self, flag: EventFlag) -> bool;
+
+    /// Number of flags per group, flag `n` belongs to group `n / divisor`.
+    fn divisor(&self) -> u32;
+
+    /// The block holding a group's flags, if the group exists.
+    fn group_block(&self, group: u32) -> Option<&FlagBlock>;
+}
+
+impl EventFlagSource for CSFD4VirtualMemoryFlag {
+    fn get_flag(&self, flag: EventFlag) -> bool {
+        CSFD4VirtualMemoryFlag::get_flag(self, flag)
+    }
+
+    fn divisor(&self) -> u32 {
+        self.event_flag_divisor
+    }
+
+    fn group_block(&self, group: u32) -> Option<&FlagBlock> {
+        CSFD4VirtualMemoryFlag::group_block(self, group)
+    }
+}
+
+impl EventFlagSource for CSEventFlagMan {
+    fn get_flag(&self, flag: EventFlag) -> bool {
+        self.virtual_memory_flag.get_flag(flag)
+    }
+
+    fn divisor(&self) -> u32 {
+        self.virtual_memory_flag.event_flag_divisor
+    }
+
+    fn group_block(&self, group: u32) -> Option<&FlagBlock> {
+        self.virtual_memory_flag.group_block(group)
+    }
+}
+
+impl EventFlagSource for EventFlagStore {
+    fn get_flag(&self, flag: EventFlag) -> bool {
+        self.get(flag)
+    }
+
+    fn divisor(&self) -> u32 {
+        self.layout().divisor()
+    }
+
+    fn group_block(&self, group: u32) -> Option<&FlagBlock> {
+        self.block(group)
+    }
+}
+
+/// A flag or a range of flags with a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedFlag {
+    pub category: String,
+    pub name: String,
+    pub flags: RangeInclusive<u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventFlagRegistry {
+    entries: Vec<NamedFlag>,
+}
+
+impl EventFlagRegistry {
+    /// Parses entries written one per line as `category flag name` or `category first-last name`
+    /// for an inclusive range of flags. The name is the rest of the line. Empty lines and lines
+    /// starting with `#` are skipped.
+    ///
+    /// ```text
+    /// grace 71190 Table of Lost Grace
+    /// boss 9101 Godrick the Grafted
+    /// pickup 1042367000-1042367020 Stormhill pickups
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut entries = vec![];
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: &str| format!("line {}: {reason}", line_number + 1);
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let category = parts
+                .next()
+                .ok_or_else(|| error("expected category"))?
+                .to_string();
+            let flags = parts.next().ok_or_else(|| error("expected flag"))?;
+            let name = parts
+                .next()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .ok_or_else(|| error("expected name"))?
+                .to_string();
+
+            let parse_flag = |flag: &str| {
+                flag.parse::<u32>()
+                    .map_err(|_| error(&format!("invalid flag {flag}")))
+            };
+            let flags = match flags.split_once('-') {
+                Some((first, last)) => parse_flag(first)?..=parse_flag(last)?,
+                None => parse_flag(flags)?..=parse_flag(flags)?,
+            };
+            if flags.is_empty() {
+                return Err(error("empty flag range"));
+            }
+
+            entries.push(NamedFlag {
+                category,
+                name,
+                flags,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[NamedFlag] {
+        &self.entries
+    }
+
+    pub fn category<'a>(&'a self, category: &'a str) -> impl Iterator<Item = &'a NamedFlag> + 'a {
+        self.entries.iter().filter(move |e| e.category == category)
+    }
+
+    /// First entry covering the flag.
+    pub fn lookup(&self, flag: impl Into<EventFlag>) -> Option<&NamedFlag> {
+        let flag = u32::from(flag.into());
+        self.entries.iter().find(|e| e.flags.contains(&flag))
+    }
+
+    /// Entries covering any flag in `range`.
+    pub fn range(&self, range: Range<u32>) -> impl Iterator<Item = &NamedFlag> + '_ {
+        self.entries
+            .iter()
+            .filter(move |e| *e.flags.start() < range.end && *e.flags.end() >= range.start)
+    }
+
+    /// Pairs the registry with a set of flags to query them by name.
+    pub fn with<'a, S: EventFlagSource + ?Sized>(
+        &'a self,
+        source: &'a S,
+    ) -> NamedEventFlags<'a, S> {
+        NamedEventFlags {
+            registry: self,
+            source,
+        }
+    }
+}
+
+/// State of a single named flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedFlagState<'a> {
+    pub entry: &'a NamedFlag,
+    pub flag: EventFlag,
+    pub state: bool,
+}
+
+/// Event flags read through an [`EventFlagRegistry`].
+pub struct NamedEventFlags<'a, S: ?Sized> {
+    registry: &'a EventFlagRegistry,
+    source: &'a S,
+}
+
+impl<'a, S: EventFlagSource + ?Sized> NamedEventFlags<'a, S> {
+    /// Every flag of a category, ranges yield one item per flag.
+    pub fn iter_named(&self, category: &'a str) -> impl Iterator<Item = NamedFlagState<'a>> + 'a {
+        let source = self.source;
+        self.registry.category(category).flat_map(move |entry| {
+            entry.flags.clone().map(move |flag| NamedFlagState {
+                entry,
+                flag: flag.into(),
+                state: source.get_flag(flag.into()),
+            })
+        })
+    }
+
+    /// Whether all flags of the entry with the given name are set. `None` if there's no such
+    /// entry.
+    pub fn get_named(&self, name: &str) -> Option<bool> {
+        let entry = self.registry.entries.iter().find(|e| e.name == name)?;
+        Some(
+            entry
+                .flags
+                .clone()
+                .all(|flag| self.source.get_flag(flag.into())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EventFlagRegistry;
+    use crate::cs::{EventFlagLayout, EventFlagStore};
+
+    const REGISTRY: &str = "
+        # Limgrave
+        grace 71190 Table of Lost Grace
+        grace 76101 The First Step
+        boss 9101 Godrick the Grafted
+        pickup 1042367000-1042367002 Stormhill pickups
+    ";
+
+    #[test]
+    fn iter_named() {
+        let registry = EventFlagRegistry::parse(REGISTRY).unwrap();
+        let layout = EventFlagLayout::parse("divisor 1000\n71 0\n76 1\n9 2\n1042367 3").unwrap();
+        let mut flags = EventFlagStore::new(layout);
+        flags.set(76101, true);
+        flags.set(1042367000, true);
+        flags.set(1042367001, true);
+
+        let graces = registry
+            .with(&flags)
+            .iter_named("grace")
+            .collect::<Vec<_>>();
+        assert_eq!(graces.len(), 2);
+        assert_eq!(graces[0].entry.name, "Table of Lost Grace");
+        assert!(!graces[0].state);
+        assert!(graces[1].state);
+
+        let pickups = registry.with(&flags).iter_named("pickup");
+        assert_eq!(pickups.filter(|p| p.state).count(), 2);
+        assert_eq!(
+            registry.with(&flags).get_named("Stormhill pickups"),
+            Some(false)
+        );
+        assert_eq!(
+            registry.with(&flags).get_named("The First Step"),
+            Some(true)
+        );
+        assert_eq!(registry.with(&flags).get_named("Margit"), None);
+    }
+
+    #[test]
+    fn lookup() {
+        let registry = EventFlagRegistry::parse(REGISTRY).unwrap();
+        assert_eq!(registry.lookup(9101).unwrap().category, "boss");
+        assert_eq!(
+            registry.lookup(1042367002).unwrap().name,
+            "Stormhill pickups"
+        );
+        assert!(registry.lookup(1042367003).is_none());
+        assert_eq!(registry.range(70000..80000).count(), 2);
+        assert_eq!(registry.range(1042367002..1042368000).count(), 1);
+
+        assert!(EventFlagRegistry::parse("grace 71190").is_err());
+        assert!(EventFlagRegistry::parse("grace 5-2 Backwards").is_err());
+        assert!(EventFlagRegistry::parse("grace first Name").is_err());
+    }
+}
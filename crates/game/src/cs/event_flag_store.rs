@@ -0,0 +1,570 @@
+//! Owned copy of the game's event flags. Uses the same group, divisor and holder layout as
+//! [`CSFD4VirtualMemoryFlag`] so flag logic can run against save files or in tests.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    ops::Range,
+};
+
+use thiserror::Error;
+
+use super::{CSFD4VirtualMemoryFlag, EventFlag, FlagBlock};
+
+/// Amount of flags a single flag block can hold.
+const FLAG_BLOCK_BITS: u32 = size_of::<FlagBlock>() as u32 * 8;
+
+#[derive(Debug, Error)]
+pub enum EventFlagStoreError {
+    #[error("Divisor {0} does not fit in a flag block")]
+    InvalidDivisor(u32),
+    #[error("Group {group} is at holder offset {offset} but the holder has {count} blocks")]
+    MissingBlock {
+        group: u32,
+        offset: u32,
+        count: usize,
+    },
+    #[error("Invalid layout on line {line}: {reason}")]
+    InvalidLayout { line: usize, reason: String },
+    #[error("Layout does not match the live flags: {0}")]
+    LayoutMismatch(String),
+}
+
+/// Describes which flag block holds each group. Mirrors the holder offsets of the game's
+/// [`super::FlagBlockDescriptor`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventFlagLayout {
+    divisor: u32,
+    /// Group to holder offset.
+    groups: BTreeMap<u32, u32>,
+}
+
+impl EventFlagLayout {
+    pub fn new(divisor: u32) -> Result<Self, EventFlagStoreError> {
+        if divisor == 0 || divisor > FLAG_BLOCK_BITS {
+            return Err(EventFlagStoreError::InvalidDivisor(divisor));
+        }
+
+        Ok(Self {
+            divisor,
+            groups: BTreeMap::new(),
+        })
+    }
+
+    /// Reads the layout of the running game. Groups stored outside of the holder are not part of
+    /// the layout.
+    pub fn from_live(flags: &CSFD4VirtualMemoryFlag) -> Self {
+        Self {
+            divisor: flags.event_flag_divisor,
+            groups: flags
+                .flag_block_descriptors
+                .iter()
+                .filter_map(|d| Some((d.group, d.holder_offset()?)))
+                .collect(),
+        }
+    }
+
+    /// Parses a layout written as a `divisor` line followed by one `group holder_offset` pair
+    /// per line. Empty lines and lines starting with `#` are skipped.
+    ///
+    /// ```text
+    /// divisor 1000
+    /// 0 0
+    /// 1 1
+    /// 1040290 2
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, EventFlagStoreError> {
+        let mut layout: Option<Self> = None;
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: &str| EventFlagStoreError::InvalidLayout {
+                line: line_number + 1,
+                reason: reason.to_string(),
+            };
+            let mut parts = line.split_whitespace();
+            let first = parts.next().unwrap_or_default();
+            let second = parts
+                .next()
+                .and_then(|p| p.parse::<u32>().ok())
+                .ok_or_else(|| error("expected a number"))?;
+            if parts.next().is_some() {
+                return Err(error("trailing input"));
+            }
+
+            match (&mut layout, first) {
+                (None, "divisor") => layout = Some(Self::new(second)?),
+                (None, _) => return Err(error("expected divisor")),
+                (Some(_), "divisor") => return Err(error("duplicate divisor")),
+                (Some(layout), group) => {
+                    let group = group.parse().map_err(|_| error("expected a group"))?;
+                    layout.insert(group, second);
+                }
+            }
+        }
+
+        layout.ok_or(EventFlagStoreError::InvalidLayout {
+            line: 0,
+            reason: "missing divisor".to_string(),
+        })
+    }
+
+    /// Writes the layout in the format read by [`EventFlagLayout::parse`].
+    pub fn to_text(&self) -> String {
+        let mut text = format!("divisor {}\n", self.divisor);
+        for (group, offset) in self.groups.iter() {
+            writeln!(text, "{group} {offset}").unwrap();
+        }
+        text
+    }
+
+    pub fn insert(&mut self, group: u32, holder_offset: u32) {
+        self.groups.insert(group, holder_offset);
+    }
+
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    pub fn holder_offset(&self, group: u32) -> Option<u32> {
+        self.groups.get(&group).copied()
+    }
+
+    /// Groups and their holder offsets, ordered by group.
+    pub fn groups(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.groups.iter().map(|(g, o)| (*g, *o))
+    }
+
+    /// Amount of blocks a holder needs to contain every group.
+    pub fn holder_len(&self) -> usize {
+        self.groups
+            .values()
+            .max()
+            .map_or(0, |offset| *offset as usize + 1)
+    }
+}
+
+/// A flag whose state differs between two stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagChange {
+    pub flag: EventFlag,
+    /// State of the flag in the store compared against.
+    pub state: bool,
+}
+
+/// Event flags owned by Rust, laid out like [`CSFD4VirtualMemoryFlag`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct EventFlagStore {
+    layout: EventFlagLayout,
+    holder: Vec<FlagBlock>,
+    /// Blocks the game keeps outside of the holder, by group.
+    external: BTreeMap<u32, FlagBlock>,
+}
+
+impl EventFlagStore {
+    /// Creates a store with every flag cleared.
+    pub fn new(layout: EventFlagLayout) -> Self {
+        let holder = vec![FlagBlock::default(); layout.holder_len()];
+        Self {
+            layout,
+            holder,
+            external: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a store from flag blocks in holder order, like the ones found in save files.
+    pub fn from_holder(
+        layout: EventFlagLayout,
+        holder: Vec<FlagBlock>,
+    ) -> Result<Self, EventFlagStoreError> {
+        if let Some((group, offset)) = layout
+            .groups()
+            .find(|(_, offset)| *offset as usize >= holder.len())
+        {
+            return Err(EventFlagStoreError::MissingBlock {
+                group,
+                offset,
+                count: holder.len(),
+            });
+        }
+
+        Ok(Self {
+            layout,
+            holder,
+            external: BTreeMap::new(),
+        })
+    }
+
+    /// Copies the flags of the running game.
+    pub fn from_live(flags: &CSFD4VirtualMemoryFlag) -> Self {
+        let holder = match flags.flag_blocks.is_null() {
+            true => vec![],
+            false => unsafe {
+                std::slice::from_raw_parts(
+                    flags.flag_blocks,
+                    flags.event_flag_holder_count as usize,
+                )
+            }
+            .to_vec(),
+        };
+
+        let external = flags
+            .flag_block_descriptors
+            .iter()
+            .filter(|d| d.holder_offset().is_none())
+            .filter_map(|d| {
+                let group = d.group;
+                Some((group, flags.flag_block(d)?.clone()))
+            })
+            .collect();
+
+        Self {
+            layout: EventFlagLayout::from_live(flags),
+            holder,
+            external,
+        }
+    }
+
+    /// Overwrites the flags of the running game. Fails without changing anything if the game's
+    /// layout differs from the store's.
+    pub fn write_live(
+        &self,
+        flags: &mut CSFD4VirtualMemoryFlag,
+    ) -> Result<(), EventFlagStoreError> {
+        if EventFlagLayout::from_live(flags) != self.layout {
+            return Err(EventFlagStoreError::LayoutMismatch(
+                "flag block descriptors differ".to_string(),
+            ));
+        }
+
+        if flags.event_flag_holder_count as usize != self.holder.len()
+            || flags.flag_blocks.is_null()
+        {
+            return Err(EventFlagStoreError::LayoutMismatch(format!(
+                "holder has {} blocks, expected {}",
+                flags.event_flag_holder_count,
+                self.holder.len()
+            )));
+        }
+
+        unsafe { std::slice::from_raw_parts_mut(flags.flag_blocks, self.holder.len()) }
+            .clone_from_slice(&self.holder);
+
+        for descriptor in flags.flag_block_descriptors.iter() {
+            let Some(block) = self.external.get(&descriptor.group) else {
+                continue;
+            };
+
+            if descriptor.holder_offset().is_none() {
+                if let Some(target) = flags.flag_block_ptr(descriptor) {
+                    unsafe { *target.as_ptr() = block.clone() };
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn layout(&self) -> &EventFlagLayout {
+        &self.layout
+    }
+
+    /// Flag blocks in holder order, as stored in save files.
+    pub fn holder(&self) -> &[FlagBlock] {
+        &self.holder
+    }
+
+    pub fn into_holder(self) -> Vec<FlagBlock> {
+        self.holder
+    }
+
+    /// Whether the store has a block for the flag's group.
+    pub fn contains(&self, flag: impl Into<EventFlag>) -> bool {
+        self.block(self.group(flag.into())).is_some()
+    }
+
+    /// Retrieves the flag's state. Flags in unknown groups are never set.
+    pub fn get(&self, flag: impl Into<EventFlag>) -> bool {
+        let flag = flag.into();
+        let (byte, mask) = self.position(flag);
+        self.block(self.group(flag))
+            .is_some_and(|block| block.bytes()[byte] & mask != 0)
+    }
+
+    /// Sets the flag's state. Does nothing for flags in unknown groups, like the game.
+    pub fn set(&mut self, flag: impl Into<EventFlag>, state: bool) {
+        let flag = flag.into();
+        let (byte, mask) = self.position(flag);
+        let Some(block) = self.block_mut(self.group(flag)) else {
+            return;
+        };
+
+        let byte = &mut block.bytes_mut()[byte];
+        *byte = match state {
+            true => *byte | mask,
+            false => *byte & !mask,
+        };
+    }
+
+    /// States of the flags in `range` that belong to a known group.
+    pub fn get_range(&self, range: Range<u32>) -> impl Iterator<Item = (EventFlag, bool)> + '_ {
+        range
+            .map(EventFlag::from)
+            .filter(|flag| self.contains(*flag))
+            .map(|flag| (flag, self.get(flag)))
+    }
+
+    pub fn set_range(&mut self, range: Range<u32>, state: bool) {
+        for flag in range {
+            self.set(flag, state);
+        }
+    }
+
+    /// Flags that are set, in ascending order.
+    pub fn iter_set(&self) -> impl Iterator<Item = EventFlag> + '_ {
+        self.blocks().flat_map(|(group, block)| {
+            changed_flags(group, self.layout.divisor, block.bytes(), &[0; 125])
+        })
+    }
+
+    /// Flags whose state differs in `other`, in ascending order. Groups missing from one of the
+    /// stores are compared as if all of their flags were cleared. Fails if the stores use
+    /// different divisors, as their blocks can't be compared then.
+    pub fn diff<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> Result<impl Iterator<Item = FlagChange> + 'a, EventFlagStoreError> {
+        if self.layout.divisor != other.layout.divisor {
+            return Err(EventFlagStoreError::LayoutMismatch(format!(
+                "divisor {} differs from {}",
+                other.layout.divisor, self.layout.divisor
+            )));
+        }
+
+        let groups = self
+            .blocks()
+            .chain(other.blocks())
+            .map(|(group, _)| group)
+            .collect::<BTreeSet<_>>();
+
+        Ok(groups.into_iter().flat_map(move |group| {
+            let ours = self.block(group).map_or(&[0; 125], FlagBlock::bytes);
+            let theirs = other.block(group).map_or(&[0; 125], FlagBlock::bytes);
+            changed_flags(group, self.layout.divisor, ours, theirs).map(|flag| FlagChange {
+                flag,
+                state: other.get(flag),
+            })
+        }))
+    }
+
+    /// Like [`EventFlagStore::diff`] but only for the flags in `range`.
+    pub fn diff_range<'a>(
+        &'a self,
+        other: &'a Self,
+        range: Range<u32>,
+    ) -> Result<impl Iterator<Item = FlagChange> + 'a, EventFlagStoreError> {
+        Ok(self
+            .diff(other)?
+            .skip_while(move |change| u32::from(change.flag) < range.start)
+            .take_while(move |change| u32::from(change.flag) < range.end))
+    }
+
+    /// Every group with a block, ordered by group.
+    fn blocks(&self) -> impl Iterator<Item = (u32, &FlagBlock)> + '_ {
+        let holder = self
+            .layout
+            .groups()
+            .filter_map(|(group, offset)| Some((group, self.holder.get(offset as usize)?)));
+        let external = self.external.iter().map(|(group, block)| (*group, block));
+
+        let mut blocks = holder.chain(external).collect::<Vec<_>>();
+        blocks.sort_by_key(|(group, _)| *group);
+        blocks.into_iter()
+    }
+
+    /// The flag block of a group, if the store has one.
+    pub fn block(&self, group: u32) -> Option<&FlagBlock> {
+        match self.layout.holder_offset(group) {
+            Some(offset) => self.holder.get(offset as usize),
+            None => self.external.get(&group),
+        }
+    }
+
+    fn block_mut(&mut self, group: u32) -> Option<&mut FlagBlock> {
+        match self.layout.holder_offset(group) {
+            Some(offset) => self.holder.get_mut(offset as usize),
+            None => self.external.get_mut(&group),
+        }
+    }
+
+    fn group(&self, flag: EventFlag) -> u32 {
+        u32::from(flag) / self.layout.divisor
+    }
+
+    /// Byte within the flag's block and the mask of its bit.
+    fn position(&self, flag: EventFlag) -> (usize, u8) {
+        let index = u32::from(flag) % self.layout.divisor;
+        ((index / 8) as usize, 0b1000_0000 >> (index % 8))
+    }
+}
+
+/// Flags of a group whose bits differ between two blocks, in ascending order. Groups too large to
+/// hold any flag yield nothing.
+fn changed_flags<'a>(
+    group: u32,
+    divisor: u32,
+    ours: &'a [u8; 125],
+    theirs: &'a [u8; 125],
+) -> impl Iterator<Item = EventFlag> + 'a {
+    ours.iter()
+        .zip(theirs.iter())
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .flat_map(move |(byte, (a, b))| {
+            (0..8)
+                .filter(move |bit| (a ^ b) & (0b1000_0000 >> bit) != 0)
+                .map(move |bit| byte as u32 * 8 + bit)
+        })
+        .filter(move |index| *index < divisor)
+        .map_while(move |index| group.checked_mul(divisor)?.checked_add(index))
+        .map(EventFlag::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EventFlagLayout, EventFlagStore, FlagBlock, FlagChange};
+    use crate::cs::EventFlag;
+
+    const LAYOUT: &str = "
+        # Flags of the test map
+        divisor 1000
+        0 0
+        1040290 2
+        62010 1
+    ";
+
+    fn store() -> EventFlagStore {
+        EventFlagStore::new(EventFlagLayout::parse(LAYOUT).unwrap())
+    }
+
+    #[test]
+    fn layout_round_trip() {
+        let layout = EventFlagLayout::parse(LAYOUT).unwrap();
+        assert_eq!(layout.divisor(), 1000);
+        assert_eq!(layout.holder_offset(62010), Some(1));
+        assert_eq!(layout.holder_len(), 3);
+        assert_eq!(EventFlagLayout::parse(&layout.to_text()).unwrap(), layout);
+
+        assert!(EventFlagLayout::parse("0 0").is_err());
+        assert!(EventFlagLayout::parse("divisor 1001").is_err());
+        assert!(EventFlagLayout::parse("divisor 1000\n0 zero").is_err());
+    }
+
+    #[test]
+    fn get_and_set() {
+        let mut flags = store();
+        assert!(flags.contains(62010123));
+        assert!(!flags.contains(62011000));
+
+        flags.set(62010123, true);
+        flags.set(1040290999, true);
+        flags.set(62011000, true);
+        assert!(flags.get(62010123));
+        assert!(!flags.get(62010122));
+        assert!(flags.get(1040290999));
+        assert!(!flags.get(62011000));
+
+        // Same bit order as the game's flag blocks.
+        let mut block = FlagBlock::default();
+        block.set(EventFlag::from(62010123), true);
+        assert!(flags.holder()[1] == block);
+
+        flags.set(62010123, false);
+        assert!(!flags.get(62010123));
+    }
+
+    #[test]
+    fn ranges() {
+        let mut flags = store();
+        flags.set_range(62010990..62011010, true);
+        assert_eq!(flags.iter_set().count(), 10);
+        assert!(flags.get_range(62010990..62011010).all(|(_, state)| state));
+        assert_eq!(flags.get_range(62010990..62011010).count(), 10);
+
+        flags.set_range(62010995..62011000, false);
+        assert_eq!(
+            flags.iter_set().map(u32::from).collect::<Vec<_>>(),
+            [62010990, 62010991, 62010992, 62010993, 62010994]
+        );
+    }
+
+    #[test]
+    fn from_holder() {
+        let layout = EventFlagLayout::parse(LAYOUT).unwrap();
+        let mut holder = vec![FlagBlock::default(); 3];
+        holder[2].set(EventFlag::from(1040290007), true);
+
+        let flags = EventFlagStore::from_holder(layout.clone(), holder.clone()).unwrap();
+        assert!(flags.get(1040290007));
+        assert!(flags.clone().into_holder() == holder);
+
+        holder.pop();
+        assert!(EventFlagStore::from_holder(layout, holder).is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let mut before = store();
+        before.set(7, true);
+        before.set(62010500, true);
+
+        let mut after = before.clone();
+        after.set(7, false);
+        after.set(1040290001, true);
+        after.set(62010501, true);
+
+        let changes = before.diff(&after).unwrap().collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                FlagChange {
+                    flag: 7.into(),
+                    state: false
+                },
+                FlagChange {
+                    flag: 62010501.into(),
+                    state: true
+                },
+                FlagChange {
+                    flag: 1040290001.into(),
+                    state: true
+                },
+            ]
+        );
+        assert_eq!(
+            before
+                .diff_range(&after, 62010000..62011000)
+                .unwrap()
+                .count(),
+            1
+        );
+        assert_eq!(after.diff(&after).unwrap().count(), 0);
+
+        let other = EventFlagStore::new(EventFlagLayout::new(100).unwrap());
+        assert!(before.diff(&other).is_err());
+    }
+
+    #[test]
+    fn skips_flags_past_u32() {
+        let layout = EventFlagLayout::parse("divisor 1000\n4294967 0").unwrap();
+        let mut flags = EventFlagStore::new(layout);
+        // 4294967295 is the last flag, the rest of the group can't be addressed.
+        flags.block_mut(4294967).unwrap().bytes_mut().fill(0xFF);
+
+        assert_eq!(flags.iter_set().count(), 296);
+        assert_eq!(flags.iter_set().last(), Some(u32::MAX.into()));
+    }
+}
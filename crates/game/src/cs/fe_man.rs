@@ -1,7 +1,6 @@
 use std::{
     ffi::{OsStr, OsString},
     fmt::Display,
-    os::windows::ffi::OsStringExt,
     ptr::NonNull,
 };
 
@@ -7,19 +7,30 @@ use crate::pointer::OwnedPtr;
 use super::ItemId;
 #[repr(C)]
 #[dlrf::singleton("CSGaitem")]
+#[dlrf::game_layout(size = 0x19038)]
 pub struct CSGaitemImp {
     vftable: usize,
-    pub gaitems: [Option<OwnedPtr<CSGaitemIns>>; 5120],
+    #[offset(0x8)]
+    pub gaitems: [Option<OwnedPtr<CSGaitemIns>>; CSGaitemImp::CAPACITY],
     // TODO: fact-check this
-    gaitem_descriptors: [CSGaitemImpEntry; 5120],
-    indexes: [u32; 5120],
+    #[offset(0xa008)]
+    gaitem_descriptors: [CSGaitemImpEntry; CSGaitemImp::CAPACITY],
+    #[offset(0x14008)]
+    indexes: [u32; CSGaitemImp::CAPACITY],
+    #[offset(0x19008)]
     write_index: u32,
     read_index: u32,
     rand_xorshift: [u8; 0x18],
-    unk23028: [u8; 8],
+    unk19028: [u8; 8],
     /// Becomes true if the CSGaitemImp is being serialized for saving to the save file.
+    #[offset(0x19030)]
     pub is_being_serialized: bool,
-    unk23038: [u8; 7],
+    unk19031: [u8; 7],
+}
+
+impl CSGaitemImp {
+    /// Number of gaitem instances that can exist at once.
+    pub const CAPACITY: usize = 5120;
 }
 
 #[repr(C)]
@@ -121,6 +132,18 @@ impl GaitemHandle {
     }
 }
 
+impl From<i32> for GaitemHandle {
+    fn from(value: i32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<GaitemHandle> for i32 {
+    fn from(value: GaitemHandle) -> Self {
+        value.0
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum GaitemCategory {
@@ -178,13 +201,10 @@ pub struct CSGemGaitemIns {
 
 #[cfg(test)]
 mod test {
-    use crate::cs::{
-        CSGaitemImp, CSGaitemIns, CSGemGaitemIns, CSGemSlot, CSGemSlotTable, CSWepGaitemIns,
-    };
+    use crate::cs::{CSGaitemIns, CSGemGaitemIns, CSGemSlot, CSGemSlotTable, CSWepGaitemIns};
 
     #[test]
     fn proper_sizes() {
-        assert_eq!(0x19038, size_of::<CSGaitemImp>());
         assert_eq!(0x10, size_of::<CSGaitemIns>());
         assert_eq!(0x30, size_of::<CSWepGaitemIns>());
         assert_eq!(0x18, size_of::<CSGemSlotTable>());
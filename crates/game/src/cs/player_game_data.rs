@@ -1,4 +1,5 @@
-use std::ops::Index;
+use std::mem::offset_of;
+use std::ops::{Index, Range};
 use std::ptr::NonNull;
 
 use crate::pointer::OwnedPtr;
@@ -7,11 +8,14 @@ use crate::Vector;
 use crate::cs::{FieldInsHandle, GaitemHandle, ItemId};
 
 #[repr(C)]
+#[dlrf::game_layout(size = 0xae8)]
 /// Source of name: RTTI
 pub struct PlayerGameData {
     vftable: usize,
+    #[offset(0x8)]
     pub character_type: u32,
     unkc: u32,
+    #[offset(0x10)]
     pub current_hp: u32,
     pub current_max_hp: u32,
     pub base_max_hp: u32,
@@ -23,6 +27,7 @@ pub struct PlayerGameData {
     pub current_max_stamina: u32,
     pub base_max_stamina: u32,
     unk38: f32,
+    #[offset(0x3c)]
     pub vigor: u32,
     pub mind: u32,
     pub endurance: u32,
@@ -34,6 +39,7 @@ pub struct PlayerGameData {
     unk5c: f32,
     unk60: f32,
     unk64: f32,
+    #[offset(0x68)]
     pub level: u32,
     pub rune_count: u32,
     pub rune_memory: u32,
@@ -47,9 +53,11 @@ pub struct PlayerGameData {
     pub madness_resist: u32,
     unk94: u32,
     unk98: u32,
+    #[offset(0x9c)]
     character_name: [u16; 16],
     unkbc: u8,
     unkbd: u8,
+    #[offset(0xbe)]
     pub gender: u8,
     pub archetype: u8,
     pub vow_type: u8,
@@ -69,10 +77,12 @@ pub struct PlayerGameData {
     pub team_type: u8,
     unke6: u8,
     /// True if the player is in their own world.
+    #[offset(0xe7)]
     pub is_my_world: bool,
     unke8: [u8; 0x4],
     unkec: u32,
     unkf0: [u8; 0x4],
+    #[offset(0xf4)]
     pub solo_breakin_point: u32,
     unkf8: u32,
     pub scadutree_blessing: u8,
@@ -85,6 +95,7 @@ pub struct PlayerGameData {
     unk103: [u8; 0x6],
     pub reached_max_rune_memory: u8,
     unk10a: [u8; 0xE],
+    #[offset(0x118)]
     pub password: [u16; 0x8],
     unk128: u16,
     group_password_1: [u16; 0x8],
@@ -98,8 +109,10 @@ pub struct PlayerGameData {
     group_password_5: [u16; 0x8],
     unk182: u16,
     unk184: [u8; 0x34],
-    pub sp_effects: [PlayerGameDataSpEffect; 0xD],
+    #[offset(0x1b8)]
+    pub sp_effects: [PlayerGameDataSpEffect; PlayerGameData::SP_EFFECT_COUNT],
     /// Level after any buffs and corrections
+    #[offset(0x288)]
     pub effective_vigor: u32,
     /// Level after any buffs and corrections
     pub effective_mind: u32,
@@ -118,18 +131,23 @@ pub struct PlayerGameData {
     /// Level after any buffs and corrections
     pub effective_arcane: u32,
     unk2ac: u32,
+    #[offset(0x2b0)]
     pub equipment: EquipGameData,
+    #[offset(0x760)]
     pub face_data: FaceData,
     /// Describes the storage box contents.
+    #[offset(0x8d0)]
     pub storage: OwnedPtr<EquipInventoryData>,
     gesture_game_data: usize,
     ride_game_data: usize,
     unk8e8: usize,
+    #[offset(0x8f0)]
     pub is_main_player: bool,
     unk8f1: u8,
     unk8f2: [u8; 6],
     unk8f8: usize,
     unk900: [u8; 36],
+    #[offset(0x924)]
     pub hp_estus_rate: f32,
     pub hp_estus_additional: u8,
     _pad929: [u8; 3],
@@ -137,8 +155,10 @@ pub struct PlayerGameData {
     pub fp_estus_additional: u8,
     _pad931: [u8; 3],
     unk934: [u8; 0x1c],
+    #[offset(0x950)]
     pub mount_handle: FieldInsHandle,
     unk958: [u8; 0x10f],
+    #[offset(0xa67)]
     pub quickmatch_kill_count: u8,
     unka68: [u8; 11],
     menu_ref_special_effect_1: usize,
@@ -146,20 +166,30 @@ pub struct PlayerGameData {
     menu_ref_special_effect_3: usize,
     pub is_using_festering_bloody_finger: bool,
     unka91: [u8; 3],
+    #[offset(0xa94)]
     pub networked_speffect_entry_count: u32,
     pub quick_match_team: u8,
     unka99: [u8; 0x13],
+    #[offset(0xaac)]
     pub quick_match_map_load_ready: bool,
     unkaad: [u8; 0x2],
     /// Should sign cooldown be enabled?
     /// Each time your coop player dies and you have someone in your world
     /// you will get a cooldown depending on WhiteSignCoolTimeParam and level from SosSignMan
+    #[offset(0xaaf)]
     pub sign_cooldown_enabled: bool,
     unkab0: [u8; 0x2f],
     unkadf: u8,
     unkae0: [u8; 0x8],
 }
 
+impl PlayerGameData {
+    /// Part of the structure that the game writes to save files as-is.
+    pub(crate) const SAVED_RANGE: Range<usize> = 0x8..0x1b8;
+    pub(crate) const CHARACTER_NAME_OFFSET: usize = offset_of!(PlayerGameData, character_name);
+    pub const SP_EFFECT_COUNT: usize = 0xD;
+}
+
 #[repr(C)]
 pub struct FaceData {
     vftable: usize,
@@ -400,6 +430,15 @@ pub struct EquipInventoryDataListEntry {
     pub pot_group: i32,
 }
 
+impl EquipInventoryData {
+    /// Capacity of the normal and key item lists of the player's inventory.
+    pub const INVENTORY_CAPACITY: usize = 0xa80;
+    pub const INVENTORY_KEY_CAPACITY: usize = 0x180;
+    /// Capacity of the normal and key item lists of the storage box.
+    pub const STORAGE_CAPACITY: usize = 0x780;
+    pub const STORAGE_KEY_CAPACITY: usize = 0x80;
+}
+
 #[repr(C)]
 pub struct EquipMagicData {
     vftable: usize,
@@ -531,10 +570,17 @@ pub struct ChrAsm {
     unk4: i32,
     pub equipment: ChrAsmEquipment,
     /// Holds references to the inventory slots for each equipment piece.
-    pub gaitem_handles: [GaitemHandle; 22],
+    pub gaitem_handles: [GaitemHandle; ChrAsm::SLOT_COUNT],
     /// Holds the param IDs for each equipment piece.
-    pub equipment_param_ids: [i32; 22],
+    pub equipment_param_ids: [i32; ChrAsm::SLOT_COUNT],
     unkd4: u32,
     unkd8: u32,
     _paddc: [u8; 12],
 }
+
+impl ChrAsm {
+    /// Number of equipment slots with a gaitem handle and param ID.
+    pub const SLOT_COUNT: usize = 22;
+    /// Part of the structure that the game writes to save files as-is.
+    pub(crate) const SAVED_RANGE: Range<usize> = 0x8..0xd4;
+}
@@ -1,7 +1,15 @@
 mod common;
 mod file_device;
+mod memory_binder;
+mod mod_overlay;
+mod stream_adapters;
 mod streams;
+mod virtual_path;
 
 pub use common::*;
 pub use file_device::*;
+pub use memory_binder::*;
+pub use mod_overlay::*;
+pub use stream_adapters::*;
 pub use streams::*;
+pub use virtual_path::*;
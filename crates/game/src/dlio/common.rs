@@ -13,6 +13,7 @@ pub enum DLFileSeekDirection {
 }
 
 #[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DLIOResult {
     DirNotEmpty = -17,
     OutOfMemory = -13,
@@ -28,6 +29,22 @@ pub enum DLIOResult {
     NoMoreFiles = 1,
 }
 
+impl From<DLIOResult> for std::io::Error {
+    fn from(result: DLIOResult) -> Self {
+        let kind = match result {
+            DLIOResult::NotFound => std::io::ErrorKind::NotFound,
+            DLIOResult::AccessDenied => std::io::ErrorKind::PermissionDenied,
+            DLIOResult::OperationUnsupported => std::io::ErrorKind::Unsupported,
+            DLIOResult::DiskFull => std::io::ErrorKind::StorageFull,
+            DLIOResult::OutOfMemory => std::io::ErrorKind::OutOfMemory,
+            DLIOResult::DirNotEmpty => std::io::ErrorKind::DirectoryNotEmpty,
+            DLIOResult::Invalid => std::io::ErrorKind::InvalidInput,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, format!("{result:?}"))
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct OpenFileMode(pub u32);
@@ -1,6 +1,7 @@
 use std::{
     fmt::Display,
-    io::{Cursor, Read, Seek, SeekFrom},
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    mem::ManuallyDrop,
     ptr::NonNull,
 };
 
@@ -15,7 +16,7 @@ use crate::{
     Vector,
 };
 
-use super::{DLFileSeekDirection, OpenFileMode};
+use super::{DLFileSeekDirection, MemoryBinderImage, OpenFileMode};
 
 #[repr(u32)]
 pub enum DLFileDeviceDriveType {
@@ -266,11 +267,16 @@ where
 {
     pub fn new(
         vftable: VPtr<dyn DLFileOperatorVmt, T>,
-        allocator: &DLAllocatorBase,
+        allocator: &mut DLAllocatorBase,
         path: &DLString,
         operator_container: &DLFileOperatorContainer,
         file_device: &DLFileDeviceBase,
     ) -> Self {
+        let mut path_copy = DLString::new(allocator);
+        if let Err(e) = path_copy.assign_utf16(path.as_utf16()) {
+            tracing::error!("Could not copy operator path {path}: {e}");
+        }
+
         Self {
             vftable,
             allocator: NonNull::from(allocator),
@@ -278,8 +284,7 @@ where
             owning_operator_container: NonNull::from(operator_container),
             io_state: DLFileOperatorIOState::default(),
             owning_file_device: NonNull::from(file_device),
-            // TODO: Implement DLString copy constructor
-            path: DLString::default(),
+            path: path_copy,
         }
     }
 }
@@ -304,11 +309,24 @@ pub struct BndEntry {
 #[repr(C)]
 pub struct DLFileOperatorContainer {
     allocator: NonNull<DLAllocatorBase>,
-    read_file_operator: OwnedPtr<DLFileOperatorBase>,
-    write_file_operator: OwnedPtr<DLFileOperatorBase>,
+    read_file_operator: Option<OwnedPtr<DLFileOperatorBase>>,
+    write_file_operator: Option<OwnedPtr<DLFileOperatorBase>>,
     flags: u32,
 }
 
+impl DLFileOperatorContainer {
+    /// Creates a container without operators, like the ones passed to
+    /// [`DLFileDeviceVmt::get_file_operator`].
+    pub fn new(allocator: &mut DLAllocatorBase) -> Self {
+        Self {
+            allocator: NonNull::from(allocator),
+            read_file_operator: None,
+            write_file_operator: None,
+            flags: 0,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct DLFileDeviceManager {
     pub devices: Vector<NonNull<DLFileDeviceBase>>,
@@ -358,28 +376,49 @@ impl DLFileDeviceVmt for DLFileDeviceBase {
     }
 }
 
+/// File operator serving a file from any Rust [`Read`] + [`Seek`] backend. Writing is supported
+/// for backends created with [`AdapterFileOperator::new_writable`].
 #[repr(C)]
 pub struct AdapterFileOperator<R>
 where
     R: Read + Seek + 'static,
 {
-    pub base: DLFileOperatorBase,
-    buffer: R,
+    pub base: DLFileOperatorBase<Self>,
+    /// Dropped by the destructor, as operators handed to the game are freed by its allocator.
+    buffer: ManuallyDrop<R>,
+    released: bool,
+    writer: Option<AdapterWriter<R>>,
+    open_mode: OpenFileMode,
+    read_only: bool,
+    position: u64,
+    size: u64,
+    /// Bytes moved by the last async read or write, which complete immediately.
+    async_transferred: usize,
+    /// FILETIME reported as both the last access and modify time.
+    pub file_time: u64,
+}
+
+/// Write access to the backend, captured where `R: Write` is known.
+struct AdapterWriter<R> {
+    write: fn(&mut R, &[u8]) -> std::io::Result<()>,
+    flush: fn(&mut R) -> std::io::Result<()>,
 }
 
 impl<R> AdapterFileOperator<R>
 where
     R: Read + Seek + 'static,
 {
+    const ASYNC_BLOCK_SIZE: usize = 0x10000;
+
     pub fn new(
-        allocator: &DLAllocatorBase,
+        allocator: &mut DLAllocatorBase,
         path: &DLString,
         operator_container: &DLFileOperatorContainer,
         file_device: &DLFileDeviceBase,
         buffer: R,
     ) -> Self {
-        Self {
-            base: DLFileOperatorBase::new(
+        Self::from_base(
+            DLFileOperatorBase::new(
                 Default::default(),
                 allocator,
                 path,
@@ -387,8 +426,196 @@ where
                 file_device,
             ),
             buffer,
+            None,
+        )
+    }
+
+    fn from_base(
+        base: DLFileOperatorBase<Self>,
+        buffer: R,
+        writer: Option<AdapterWriter<R>>,
+    ) -> Self {
+        Self {
+            base,
+            buffer: ManuallyDrop::new(buffer),
+            released: false,
+            writer,
+            open_mode: OpenFileMode(0),
+            read_only: false,
+            position: 0,
+            size: 0,
+            async_transferred: 0,
+            file_time: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        let mut operator = ManuallyDrop::new(self);
+        assert!(!operator.released, "backend was already released");
+        unsafe { ManuallyDrop::take(&mut operator.buffer) }
+    }
+
+    /// Moves the operator into memory from `allocator`, which is where the game expects
+    /// operators handed out by file devices to live. Null if the allocation fails.
+    pub fn into_allocated(self, allocator: &mut DLAllocatorBase) -> *const DLFileOperatorBase {
+        let memory = allocator
+            .allocate_aligned(std::mem::size_of::<Self>(), std::mem::align_of::<Self>())
+            as *mut Self;
+        if memory.is_null() {
+            return std::ptr::null();
+        }
+
+        unsafe { memory.write(self) };
+        memory as *const DLFileOperatorBase
+    }
+
+    /// Drops the backend, closing any handles it holds.
+    fn release(&mut self) {
+        if !self.released {
+            self.released = true;
+            unsafe { ManuallyDrop::drop(&mut self.buffer) };
         }
     }
+
+    /// Records the result of an operation and passes on whether it succeeded.
+    fn finish(&mut self, result: DLIOResult) -> bool {
+        self.base.result = result;
+        result == DLIOResult::Success
+    }
+
+    /// Checks that the file is open, recording [`DLIOResult::IsNotOpen`] otherwise.
+    fn check_open(&mut self) -> bool {
+        self.base.io_state.is_open() || self.finish(DLIOResult::IsNotOpen)
+    }
+
+    fn can_write(&self) -> bool {
+        self.writer.is_some() && !self.read_only
+    }
+
+    fn refresh_size(&mut self) -> std::io::Result<()> {
+        self.size = self.buffer.seek(SeekFrom::End(0))?;
+        self.position = self.buffer.seek(SeekFrom::Start(self.position))?;
+        Ok(())
+    }
+
+    /// Reads the whole backend, leaving the position where it was.
+    fn read_all(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut data = vec![];
+        self.buffer.seek(SeekFrom::Start(0))?;
+        let read = self.buffer.read_to_end(&mut data);
+        self.buffer.seek(SeekFrom::Start(self.position))?;
+        read.map(|_| data)
+    }
+
+    /// Copies `path` into the operator's own path before updating the path state.
+    fn assign_path(&mut self, path: &DLString, param_3: bool, param_4: bool) -> bool {
+        if let Err(e) = self.base.path.assign_utf16(path.as_utf16()) {
+            tracing::error!("{self} could not copy path {path}: {e}");
+            return self.finish(DLIOResult::OutOfMemory);
+        }
+
+        self.update_path_state(param_3, param_4);
+        true
+    }
+
+    fn update_path_state(&mut self, param_3: bool, param_4: bool) {
+        self.base.io_state.0 &= 0xfffffff9;
+        self.base.io_state.0 |= ((((param_4 as u32 & 1) * 2) | (param_3 as u32 & 1)) * 2);
+    }
+
+    /// # Safety
+    ///
+    /// `output` must be valid for writes of `length` bytes.
+    unsafe fn read_into(&mut self, output: *mut u8, length: usize) -> Option<usize> {
+        if !self.check_open() {
+            return None;
+        }
+
+        let output = unsafe { std::slice::from_raw_parts_mut(output, length) };
+        let mut total = 0;
+        while total < length {
+            match self.buffer.read(&mut output[total..]) {
+                Ok(0) => break,
+                Ok(read) => total += read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.finish(DLIOResult::Invalid);
+                    return None;
+                }
+            }
+        }
+
+        self.position += total as u64;
+        self.finish(DLIOResult::Success);
+        Some(total)
+    }
+
+    fn write_from(&mut self, input: *const u8, length: usize) -> Option<usize> {
+        if !self.check_open() {
+            return None;
+        }
+
+        let Some(writer) = self.writer.as_ref().filter(|_| !self.read_only) else {
+            self.finish(DLIOResult::AccessDenied);
+            return None;
+        };
+        if !self.open_mode.write() && !self.open_mode.append() {
+            self.finish(DLIOResult::AccessDenied);
+            return None;
+        }
+
+        let input = unsafe { std::slice::from_raw_parts(input, length) };
+        if (writer.write)(&mut self.buffer, input).is_err() {
+            self.finish(DLIOResult::DiskFull);
+            return None;
+        }
+
+        self.position += length as u64;
+        self.size = self.size.max(self.position);
+        self.finish(DLIOResult::Success);
+        Some(length)
+    }
+
+    fn write_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
+        if let Some(time) = unsafe { (ptr as *mut DLDateTime).as_mut() } {
+            *time = DLDateTime::from_time64(self.file_time, true);
+        }
+        ptr
+    }
+}
+
+impl<R> AdapterFileOperator<R>
+where
+    R: Read + Write + Seek + 'static,
+{
+    /// Creates an operator that also supports writing to the backend.
+    pub fn new_writable(
+        allocator: &mut DLAllocatorBase,
+        path: &DLString,
+        operator_container: &DLFileOperatorContainer,
+        file_device: &DLFileDeviceBase,
+        buffer: R,
+    ) -> Self {
+        let mut operator = Self::new(allocator, path, operator_container, file_device, buffer);
+        operator.writer = Some(Self::writer());
+        operator
+    }
+
+    fn writer() -> AdapterWriter<R> {
+        AdapterWriter {
+            write: |buffer, input| buffer.write_all(input),
+            flush: |buffer| buffer.flush(),
+        }
+    }
+}
+
+impl<R> Drop for AdapterFileOperator<R>
+where
+    R: Read + Seek + 'static,
+{
+    fn drop(&mut self) {
+        self.release();
+    }
 }
 
 impl<R> Display for AdapterFileOperator<R>
@@ -406,11 +633,16 @@ where
 {
     extern "C" fn destructor(&mut self) {
         tracing::debug!("{self}::destructor()");
+        self.release();
+        // The game frees the operator without running drop glue, so the path's buffer has to be
+        // freed here. Rust owned operators drop the empty string again later.
+        self.base.path = DLString::default();
     }
 
     extern "C" fn copy_from(&mut self, source: &DLFileOperatorBase) -> bool {
         tracing::debug!("{self}::copy_from()");
-        unimplemented!()
+        // The source's backend is unknown, so there's nothing that can be copied over.
+        self.finish(DLIOResult::OperationUnsupported)
     }
 
     extern "C" fn set_path(&mut self, path: &DLString, param_3: bool, param_4: bool) -> bool {
@@ -421,10 +653,7 @@ where
             param_4
         );
 
-        self.base.io_state.0 &= 0xfffffff9;
-        self.base.io_state.0 |= ((((param_4 as u32 & 1) * 2) | (param_3 as u32 & 1)) * 2);
-
-        true
+        self.assign_path(path, param_3, param_4)
     }
 
     extern "C" fn set_path_other_1(
@@ -439,7 +668,8 @@ where
             param_3,
             param_4
         );
-        unimplemented!()
+
+        self.assign_path(path, param_3, param_4)
     }
 
     extern "C" fn set_path_other_2(
@@ -454,26 +684,28 @@ where
             param_3,
             param_4
         );
-        unimplemented!()
+
+        self.assign_path(path, param_3, param_4)
     }
 
     extern "C" fn set_state(&mut self, param_2: bool, param_3: bool) -> bool {
         tracing::debug!("{self}::set_state({}, {})", param_2, param_3);
 
-        self.base.io_state.0 &= 0xfffffff9;
-        self.base.io_state.0 |= ((((param_3 as u32 & 1) * 2) | (param_2 as u32 & 1)) * 2);
-
+        self.update_path_state(param_2, param_3);
         true
     }
 
     extern "C" fn clear_file_info(&mut self) -> bool {
         tracing::debug!("{self}::clear_file_info()");
-        unimplemented!()
+
+        self.base.io_state = DLFileOperatorIOState::default();
+        self.open_mode = OpenFileMode(0);
+        self.finish(DLIOResult::Success)
     }
 
     extern "C" fn get_virtual_disk_operator(&self) -> *const DLFileOperatorBase {
         tracing::debug!("{self}::get_virtual_disk_operator()");
-        unimplemented!()
+        std::ptr::null()
     }
 
     extern "C" fn bind_device_image(
@@ -481,79 +713,146 @@ where
         image_spi: &DLFileDeviceImageSPIBase,
     ) -> *const DLFileDeviceImageSPIBase {
         tracing::debug!("{self}::bind_device_image()");
-        unimplemented!()
+        if !self.check_open() {
+            return std::ptr::null();
+        }
+
+        let Ok(data) = self.read_all() else {
+            self.finish(DLIOResult::Invalid);
+            return std::ptr::null();
+        };
+        let image = match MemoryBinderImage::parse(&data) {
+            Ok(image) => image,
+            Err(e) => {
+                tracing::warn!("{self} is not a binder: {e}");
+                self.finish(DLIOResult::Invalid);
+                return std::ptr::null();
+            }
+        };
+
+        let image = image.into_allocated(unsafe { self.base.allocator.as_mut() });
+        match image.is_null() {
+            true => self.finish(DLIOResult::OutOfMemory),
+            false => self.finish(DLIOResult::Success),
+        };
+        image
     }
 
     extern "C" fn is_readable(&mut self) -> bool {
         tracing::debug!("{self}::is_readable()");
-        unimplemented!()
+        self.finish(DLIOResult::Success)
     }
+
     extern "C" fn is_writable(&mut self) -> bool {
         tracing::debug!("{self}::is_writable()");
-        unimplemented!()
+        let writable = self.can_write();
+        self.finish(DLIOResult::Success);
+        writable
     }
 
     extern "C" fn last_access_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
         tracing::debug!("{self}::last_access_time()");
-        unimplemented!()
+        self.write_time(ptr)
     }
 
     extern "C" fn last_modify_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
         tracing::debug!("{self}::last_modify_time()");
-        unimplemented!()
+        self.write_time(ptr)
     }
 
     extern "C" fn file_size(&mut self) -> usize {
-        let current = self.buffer.stream_position().unwrap();
-        let end = self.buffer.seek(SeekFrom::End(0)).unwrap() as usize;
-        self.buffer.seek(SeekFrom::Start(current));
-        tracing::debug!("{self}::file_size() -> {end}");
-        end
+        if self.refresh_size().is_err() {
+            self.finish(DLIOResult::Invalid);
+            return 0;
+        }
+
+        tracing::debug!("{self}::file_size() -> {}", self.size);
+        self.finish(DLIOResult::Success);
+        self.size as usize
     }
 
     extern "C" fn get_read_size(&mut self) -> usize {
         tracing::debug!("{self}::get_read_size()");
-        unimplemented!()
+        self.file_size().saturating_sub(self.position as usize)
     }
 
     extern "C" fn get_write_size(&self) -> usize {
         tracing::debug!("{self}::get_write_size()");
-        unimplemented!()
+        match self.can_write() {
+            true => self.size.saturating_sub(self.position) as usize,
+            false => 0,
+        }
     }
 
     extern "C" fn set_eof(&mut self) {
         tracing::debug!("{self}::set_eof()");
-        unimplemented!()
+        // Truncating isn't part of the std::io traits.
+        self.finish(DLIOResult::OperationUnsupported);
     }
 
     extern "C" fn is_eof(&self) -> bool {
         tracing::debug!("{self}::is_eof()");
-        unimplemented!()
+        self.position >= self.size
     }
 
     extern "C" fn is_directory(&self) -> bool {
         tracing::debug!("{self}::is_directory()");
-        unimplemented!()
+        false
     }
 
     extern "C" fn is_open(&self) -> bool {
         tracing::debug!("{self}::is_open()");
-        true
+        self.base.io_state.is_open()
     }
 
     extern "C" fn open(&mut self, open_mode: OpenFileMode) -> bool {
         tracing::debug!("{self}::open({:?})", open_mode);
-        true
+        if self.base.io_state.is_open() {
+            return self.finish(DLIOResult::AlreadyOpen);
+        }
+
+        if (open_mode.write() || open_mode.append()) && !self.can_write() {
+            return self.finish(DLIOResult::AccessDenied);
+        }
+
+        let start = match open_mode.append() {
+            true => SeekFrom::End(0),
+            false => SeekFrom::Start(0),
+        };
+        let Ok(position) = self.buffer.seek(start) else {
+            return self.finish(DLIOResult::NotFound);
+        };
+
+        self.position = position;
+        if self.refresh_size().is_err() {
+            return self.finish(DLIOResult::NotFound);
+        }
+
+        self.open_mode = open_mode;
+        self.base.io_state.0 |= 0x1;
+        self.finish(DLIOResult::Success)
     }
 
     extern "C" fn close(&mut self) -> bool {
         tracing::debug!("{self}::close()");
-        true
+        if !self.check_open() {
+            return false;
+        }
+
+        if let Some(writer) = self.writer.as_ref() {
+            if (writer.flush)(&mut self.buffer).is_err() {
+                return self.finish(DLIOResult::Invalid);
+            }
+        }
+
+        self.base.io_state.0 &= !0x1;
+        self.finish(DLIOResult::Success)
     }
 
-    extern "C" fn set_read_only(&mut self, is_open: bool) -> bool {
-        tracing::debug!("{self}::set_read_only({})", is_open);
-        unimplemented!()
+    extern "C" fn set_read_only(&mut self, read_only: bool) -> bool {
+        tracing::debug!("{self}::set_read_only({})", read_only);
+        self.read_only = read_only;
+        self.finish(DLIOResult::Success)
     }
 
     extern "C" fn seek(
@@ -563,91 +862,245 @@ where
         seek_mode: DLFileSeekDirection,
     ) -> bool {
         tracing::debug!("{self}::seek({}, {}, {:?})", is_stream, offset, seek_mode);
-        unimplemented!()
+        if !self.check_open() {
+            return false;
+        }
+
+        let target = match seek_mode {
+            DLFileSeekDirection::Head => 0,
+            DLFileSeekDirection::Current => self.position as i64,
+            DLFileSeekDirection::Tail => self.size as i64,
+        }
+        .checked_add(offset)
+        .filter(|target| *target >= 0);
+        let Some(target) = target else {
+            return self.finish(DLIOResult::Invalid);
+        };
+
+        match self.buffer.seek(SeekFrom::Start(target as u64)) {
+            Ok(position) => {
+                self.position = position;
+                self.finish(DLIOResult::Success)
+            }
+            Err(_) => self.finish(DLIOResult::Invalid),
+        }
     }
 
     extern "C" fn cursor_position(&self) -> usize {
         tracing::debug!("{self}::cursor_position()");
-        unimplemented!()
+        self.position as usize
     }
 
     unsafe extern "C" fn read(&mut self, output: *mut u8, length: usize) -> i32 {
         tracing::debug!("{self}::read({:x?}, {})", output, length);
-        let mut buffer = vec![0x0u8; length];
-        self.buffer.read_exact(&mut buffer).unwrap();
-
-        unsafe { std::ptr::copy_nonoverlapping(buffer.as_ptr(), output, length) };
-        self.base.result = DLIOResult::Success;
-        length as i32
+        let length = length.min(i32::MAX as usize);
+        match unsafe { self.read_into(output, length) } {
+            Some(read) => read as i32,
+            None => -1,
+        }
     }
 
     extern "C" fn write(&mut self, input: *const u8, length: usize) -> usize {
         tracing::debug!("{self}::write_file({:x?}, {})", input, length);
-        unimplemented!()
+        self.write_from(input, length).unwrap_or(0)
     }
 
     extern "C" fn get_async_block_size(&self) -> usize {
         tracing::debug!("{self}::get_async_block_size()");
-        unimplemented!()
+        Self::ASYNC_BLOCK_SIZE
     }
+
     extern "C" fn get_async_buffer_alignment_size(&self) -> usize {
         tracing::debug!("{self}::get_async_buffer_alignment_size()");
-        unimplemented!()
+        1
     }
 
     unsafe extern "C" fn start_async_read(&mut self, output: *mut u8, length: usize) -> bool {
         tracing::debug!("{self}::start_async_read({:x?}, {})", output, length);
-        unimplemented!()
+        let read = unsafe { self.read_into(output, length) };
+        self.async_transferred = read.unwrap_or(0);
+        read.is_some()
     }
+
     extern "C" fn start_async_write(&mut self, input: *const u8, length: usize) -> bool {
         tracing::debug!("{self}::start_async_write({:x?}, {})", input, length);
-        unimplemented!()
+        let written = self.write_from(input, length);
+        self.async_transferred = written.unwrap_or(0);
+        written.is_some()
     }
+
     extern "C" fn query_async_status(
         &mut self,
         bytes_remaining: &mut usize,
         bytes_transferred: Option<&mut usize>,
     ) -> bool {
         tracing::debug!("{self}::query_async_status()");
-        unimplemented!()
+        // Async operations complete before they return.
+        *bytes_remaining = 0;
+        if let Some(bytes_transferred) = bytes_transferred {
+            *bytes_transferred = self.async_transferred;
+        }
+        self.finish(DLIOResult::Success)
     }
+
     extern "C" fn get_open_mode(&self) -> OpenFileMode {
         tracing::debug!("{self}::get_open_mode()");
-        unimplemented!()
+        self.open_mode
     }
 
     extern "C" fn delete(&mut self) -> bool {
         tracing::debug!("{self}::delete()");
-        unimplemented!()
+        self.finish(DLIOResult::OperationUnsupported)
     }
 
     extern "C" fn flush(&mut self) {
         tracing::debug!("{self}::flush()");
-        unimplemented!()
+        let result = match self.writer.as_ref() {
+            Some(writer) if (writer.flush)(&mut self.buffer).is_err() => DLIOResult::Invalid,
+            _ => DLIOResult::Success,
+        };
+        self.finish(result);
     }
 
     extern "C" fn populate_file_info(&mut self) -> bool {
         tracing::debug!("{self}::populate_file_info()");
-        unimplemented!()
+        match self.refresh_size() {
+            Ok(_) => self.finish(DLIOResult::Success),
+            Err(_) => self.finish(DLIOResult::NotFound),
+        }
     }
 
     extern "C" fn unk2(&mut self) -> bool {
         tracing::debug!("{self}::unk2()");
-        unimplemented!()
+        self.finish(DLIOResult::OperationUnsupported)
     }
 
     extern "C" fn rename_w(&mut self, path: *const u16) -> bool {
         tracing::debug!("{self}::rename_w({:x?})", path);
-        unimplemented!()
+        self.finish(DLIOResult::OperationUnsupported)
     }
 
     extern "C" fn rename(&mut self, path: *const u8) -> bool {
         tracing::debug!("{self}::rename({:x?})", path);
-        unimplemented!()
+        self.finish(DLIOResult::OperationUnsupported)
     }
 
     extern "C" fn create_directory(&mut self) -> bool {
         tracing::debug!("{self}::create_directory()");
-        unimplemented!()
+        self.finish(DLIOResult::OperationUnsupported)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, ptr::NonNull};
+
+    use super::{
+        AdapterFileOperator, DLFileOperatorBase, DLFileOperatorIOState, DLFileSeekDirection,
+        OpenFileMode,
+    };
+    use crate::{dlio::DLIOResult, dltx::DLString, dlut::DLDateTime};
+
+    const READ: OpenFileMode = OpenFileMode(0x1);
+    const WRITE: OpenFileMode = OpenFileMode(0x2);
+    const APPEND: OpenFileMode = OpenFileMode(0x6);
+
+    fn operator(data: &[u8], writable: bool) -> AdapterFileOperator<Cursor<Vec<u8>>> {
+        let base = DLFileOperatorBase {
+            vftable: Default::default(),
+            allocator: NonNull::dangling(),
+            result: DLIOResult::Success,
+            owning_operator_container: NonNull::dangling(),
+            io_state: DLFileOperatorIOState::default(),
+            owning_file_device: NonNull::dangling(),
+            path: DLString::default(),
+        };
+        let writer = writable.then(AdapterFileOperator::writer);
+        AdapterFileOperator::from_base(base, Cursor::new(data.to_vec()), writer)
+    }
+
+    #[test]
+    fn reads_through_vtable() {
+        let mut file = operator(b"hello world", false);
+        let vmt = file.base.vftable;
+
+        let mut output = [0; 5];
+        assert_eq!(unsafe { (vmt.read)(&mut file, output.as_mut_ptr(), 5) }, -1);
+        assert_eq!(file.base.result, DLIOResult::IsNotOpen);
+
+        assert!((vmt.open)(&mut file, READ));
+        assert!((vmt.is_open)(&file));
+        assert!(!(vmt.open)(&mut file, READ));
+        assert_eq!(file.base.result, DLIOResult::AlreadyOpen);
+
+        assert_eq!((vmt.file_size)(&mut file), 11);
+        assert!((vmt.seek)(&mut file, false, 6, DLFileSeekDirection::Head));
+        assert_eq!((vmt.get_read_size)(&mut file), 5);
+        assert_eq!(unsafe { (vmt.read)(&mut file, output.as_mut_ptr(), 5) }, 5);
+        assert_eq!(&output, b"world");
+        assert!((vmt.is_eof)(&file));
+
+        assert!((vmt.seek)(&mut file, false, -5, DLFileSeekDirection::Tail));
+        let mut output = [0; 8];
+        assert_eq!(unsafe { (vmt.read)(&mut file, output.as_mut_ptr(), 8) }, 5);
+        assert!(!(vmt.seek)(&mut file, false, -1, DLFileSeekDirection::Head));
+        assert_eq!(file.base.result, DLIOResult::Invalid);
+        assert_eq!((vmt.cursor_position)(&file), 11);
+
+        assert_eq!((vmt.write)(&mut file, b"!".as_ptr(), 1), 0);
+        assert_eq!(file.base.result, DLIOResult::AccessDenied);
+        assert!(!(vmt.is_writable)(&mut file));
+        assert!(!(vmt.rename)(&mut file, c"other".as_ptr().cast()));
+        assert_eq!(file.base.result, DLIOResult::OperationUnsupported);
+
+        assert!((vmt.close)(&mut file));
+        assert!(!(vmt.is_open)(&file));
+        assert!(!(vmt.close)(&mut file));
+        assert_eq!(file.base.result, DLIOResult::IsNotOpen);
+    }
+
+    #[test]
+    fn writes_through_vtable() {
+        let mut file = operator(b"hello", true);
+        let vmt = file.base.vftable;
+        assert!((vmt.is_writable)(&mut file));
+
+        assert!((vmt.open)(&mut file, APPEND));
+        assert_eq!((vmt.write)(&mut file, b" world".as_ptr(), 6), 6);
+        assert_eq!((vmt.file_size)(&mut file), 11);
+        assert!((vmt.close)(&mut file));
+
+        assert!((vmt.open)(&mut file, WRITE));
+        let mut remaining = 1;
+        let mut transferred = 0;
+        assert!((vmt.start_async_write)(&mut file, b"J".as_ptr(), 1));
+        assert!((vmt.query_async_status)(
+            &mut file,
+            &mut remaining,
+            Some(&mut transferred)
+        ));
+        assert_eq!((remaining, transferred), (0, 1));
+
+        assert!((vmt.set_read_only)(&mut file, true));
+        assert_eq!((vmt.write)(&mut file, b"!".as_ptr(), 1), 0);
+        assert_eq!(file.base.result, DLIOResult::AccessDenied);
+        assert!((vmt.close)(&mut file));
+
+        assert!((vmt.clear_file_info)(&mut file));
+        assert_eq!(file.into_inner().into_inner(), b"Jello world");
+    }
+
+    #[test]
+    fn reports_times() {
+        let mut file = operator(b"", false);
+        file.file_time = 0x1F;
+        let vmt = file.base.vftable;
+
+        let mut time = DLDateTime::from_time64(0, false);
+        let result = (vmt.last_modify_time)(&file, &mut time);
+        assert_eq!(result, &time as *const _);
+        assert_eq!(time.time64, 0x1F);
+        assert!(time.is_utc());
+        assert!((vmt.last_access_time)(&file, std::ptr::null()).is_null());
     }
 }
@@ -0,0 +1,389 @@
+//! BND4 binders held in memory and served to the game as if they were loaded from its archives.
+use std::{io::Cursor, ptr::NonNull, sync::Arc};
+
+use vtable_rs::VPtr;
+
+use crate::{
+    dlkr::{DLAllocatorBase, DLPlainLightMutex},
+    dltx::{DLString, DLStringError},
+    formats::{bnd4::Bnd4, FormatError},
+};
+
+use super::{
+    normalize_path, split_root, AdapterFileOperator, BndEntry, DLFileDeviceBase,
+    DLFileDeviceDriveType, DLFileDeviceImageSPIBase, DLFileDeviceImageSPIVmt, DLFileDeviceManager,
+    DLFileDeviceVmt, DLFileOperatorBase, DLFileOperatorContainer,
+};
+
+/// Contents of a binder entry, shared by every operator reading it.
+pub type MemoryBinderEntryData = Arc<[u8]>;
+
+/// Device image over a BND4 in memory, for example one generated at runtime. Entries are looked up
+/// by their normalized name or by any trailing part of it, so `item.fmg` and `msg/engus/item.fmg`
+/// both find `N:\GR\data\INTERROOT_win64\msg\engUS\item.fmg`.
+#[repr(C)]
+pub struct MemoryBinderImage {
+    pub vftable: VPtr<dyn DLFileDeviceImageSPIVmt, Self>,
+    entries: Vec<(String, MemoryBinderEntryData)>,
+}
+
+impl MemoryBinderImage {
+    /// Takes the named entries of the binder. Entries without a name can't be opened by path and
+    /// are left out.
+    pub fn new(binder: Bnd4) -> Self {
+        let entries = binder
+            .entries
+            .into_iter()
+            .filter_map(|entry| {
+                let name = normalize_path(entry.name.as_deref()?);
+                (!name.is_empty()).then(|| (name, entry.data.into()))
+            })
+            .collect();
+
+        Self {
+            vftable: Default::default(),
+            entries,
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        Bnd4::parse(data).map(Self::new)
+    }
+
+    /// Normalized names of the entries in binder order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&MemoryBinderEntryData> {
+        let name = normalize_path(name);
+        let name = name.trim_start_matches('/');
+        if name.is_empty() {
+            return None;
+        }
+
+        self.entries
+            .iter()
+            .find(|(entry, _)| {
+                entry == name
+                    || entry
+                        .strip_suffix(name)
+                        .is_some_and(|rest| rest.ends_with(['/', ':']))
+            })
+            .map(|(_, data)| data)
+    }
+
+    /// Total size of the entries in bytes.
+    pub fn size(&self) -> u64 {
+        self.entries.iter().map(|(_, data)| data.len() as u64).sum()
+    }
+
+    /// Mounts the image as a device serving its entries under `root`, like `genmsg:`. The game
+    /// only sees the device once it's registered with [`MemoryBinderFileDevice::install`].
+    pub fn mount(self, root: &str) -> Box<MemoryBinderFileDevice> {
+        MemoryBinderFileDevice::new(self, root)
+    }
+
+    /// Moves the image into memory from `allocator`, which is where the game expects images bound
+    /// by file operators to live. Null if the allocation fails.
+    pub fn into_allocated(
+        self,
+        allocator: &mut DLAllocatorBase,
+    ) -> *const DLFileDeviceImageSPIBase {
+        let memory = allocator
+            .allocate_aligned(std::mem::size_of::<Self>(), std::mem::align_of::<Self>())
+            as *mut Self;
+        if memory.is_null() {
+            return std::ptr::null();
+        }
+
+        unsafe { memory.write(self) };
+        memory as *const DLFileDeviceImageSPIBase
+    }
+}
+
+impl DLFileDeviceImageSPIVmt for MemoryBinderImage {
+    extern "C" fn destructor(&mut self) {
+        tracing::debug!("MemoryBinderImage::destructor()");
+        // The game frees the memory of images it owns without running drop glue. Taking the
+        // entries keeps this safe for images owned by Rust, which drop an empty list later.
+        self.entries = Vec::new();
+    }
+}
+
+/// File device serving the entries of a [`MemoryBinderImage`] for paths under its root. Opened
+/// entries are read-only. The device must stay pinned at its address while the game uses it.
+#[repr(C)]
+pub struct MemoryBinderFileDevice {
+    pub vftable: VPtr<dyn DLFileDeviceVmt, Self>,
+    unk8: bool,
+    ref_count: u32,
+    pub mutex: DLPlainLightMutex,
+    root: String,
+    image: MemoryBinderImage,
+}
+
+impl MemoryBinderFileDevice {
+    fn new(image: MemoryBinderImage, root: &str) -> Box<Self> {
+        let root = normalize_path(root)
+            .trim_end_matches([':', '/'])
+            .to_string();
+        let mut device = Box::<Self>::new_uninit();
+        let ptr = device.as_mut_ptr();
+        unsafe {
+            (&raw mut (*ptr).vftable).write(Default::default());
+            (&raw mut (*ptr).unk8).write(false);
+            (&raw mut (*ptr).ref_count).write(1);
+            DLPlainLightMutex::initialize_at(&raw mut (*ptr).mutex);
+            (&raw mut (*ptr).root).write(root);
+            (&raw mut (*ptr).image).write(image);
+            device.assume_init()
+        }
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn image(&self) -> &MemoryBinderImage {
+        &self.image
+    }
+
+    /// Pointer to hand to the game, the device starts with the same layout as
+    /// [`DLFileDeviceBase`].
+    pub fn as_device(&mut self) -> NonNull<DLFileDeviceBase> {
+        NonNull::from(self).cast()
+    }
+
+    /// Puts the device in front of the manager's device list and registers the binder with its
+    /// service provider, the way the game does for the binders it mounts from its archives.
+    pub fn install(&mut self, manager: &mut DLFileDeviceManager) -> Result<(), DLStringError> {
+        let device = self.as_device();
+        let image = NonNull::from(&mut self.image).cast::<DLFileDeviceImageSPIBase>();
+        let mut allocator = manager.bnd4_files.allocator();
+        let entry = BndEntry {
+            name: DLString::from_str(unsafe { allocator.as_mut() }, &format!("{}:", self.root))?,
+            device,
+            file_size: self.image.size(),
+        };
+
+        manager.mutex.lock();
+        manager.devices.insert(0, device);
+        manager.service_providers.push(image);
+        manager.bnd4_files.push(entry);
+        manager.mutex.unlock();
+        Ok(())
+    }
+
+    /// Removes everything [`MemoryBinderFileDevice::install`] registered. Operators that were
+    /// already handed out keep pointing at the device.
+    pub fn uninstall(&mut self, manager: &mut DLFileDeviceManager) {
+        let device = self.as_device();
+        let image = NonNull::from(&mut self.image).cast::<DLFileDeviceImageSPIBase>();
+
+        manager.mutex.lock();
+        manager.devices.retain(|d| *d != device);
+        manager.service_providers.retain(|i| *i != image);
+        manager.bnd4_files.retain(|entry| entry.device != device);
+        manager.mutex.unlock();
+    }
+
+    /// Entry a path refers to, `None` if the path is outside of the device's root.
+    pub fn entry_for_path(&self, path: &str) -> Option<&MemoryBinderEntryData> {
+        let path = normalize_path(path);
+        let (root, rest) = split_root(&path)?;
+        if root != self.root {
+            return None;
+        }
+        self.image.entry(rest)
+    }
+}
+
+impl DLFileDeviceVmt for MemoryBinderFileDevice {
+    extern "C" fn destructor(&mut self) {
+        tracing::debug!("MemoryBinderFileDevice::destructor()");
+    }
+
+    extern "C" fn get_file_operator(
+        &mut self,
+        path_dlstring: &DLString,
+        path_u16: *const u16,
+        operator_container: &mut DLFileOperatorContainer,
+        allocator: &mut DLAllocatorBase,
+        is_temp_file: bool,
+    ) -> *const DLFileOperatorBase {
+        let path = path_dlstring.to_string();
+        let Some(data) = self.entry_for_path(&path).cloned() else {
+            return std::ptr::null();
+        };
+        tracing::debug!(
+            "MemoryBinderFileDevice::get_file_operator({path}) -> {} bytes",
+            data.len()
+        );
+
+        let device = unsafe { &*(self as *const Self as *const DLFileDeviceBase) };
+        AdapterFileOperator::new(
+            allocator,
+            path_dlstring,
+            operator_container,
+            device,
+            Cursor::new(data),
+        )
+        .into_allocated(allocator)
+    }
+
+    extern "C" fn file_enumerator(&self) -> *const u8 {
+        std::ptr::null()
+    }
+
+    extern "C" fn get_drive_type(&self, path: *const u16) -> DLFileDeviceDriveType {
+        DLFileDeviceDriveType::Default
+    }
+
+    extern "C" fn is_encrypted(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, ptr::NonNull};
+
+    use super::{MemoryBinderEntryData, MemoryBinderFileDevice, MemoryBinderImage};
+    use crate::{
+        dlio::{
+            AdapterFileOperator, DLFileDeviceImageSPIBase, DLFileOperatorContainer, DLIOResult,
+            OpenFileMode,
+        },
+        dlkr::RustAllocator,
+        dltx::DLString,
+        formats::bnd4::{format, Bnd4, Bnd4Entry},
+    };
+
+    fn binder() -> Vec<u8> {
+        let mut binder = Bnd4::new(format::IDS | format::NAMES1 | format::NAMES2, true);
+        for (id, name, data) in [
+            (
+                0,
+                Some(r"N:\GR\data\INTERROOT_win64\msg\engUS\item.fmg"),
+                b"items".as_slice(),
+            ),
+            (
+                1,
+                Some(r"N:\GR\data\INTERROOT_win64\msg\engUS\menu.fmg"),
+                b"menu",
+            ),
+            (2, None, b"unnamed"),
+        ] {
+            binder.entries.push(Bnd4Entry {
+                flags: 0x40,
+                id,
+                name: name.map(str::to_string),
+                data: data.to_vec(),
+            });
+        }
+        binder.write()
+    }
+
+    fn image() -> MemoryBinderImage {
+        MemoryBinderImage::parse(&binder()).unwrap()
+    }
+
+    #[test]
+    fn finds_entries() {
+        let image = image();
+        assert_eq!(image.names().count(), 2);
+        assert_eq!(&**image.entry("Item.fmg").unwrap(), b"items");
+        assert_eq!(&**image.entry(r"msg\engus\menu.fmg").unwrap(), b"menu");
+        assert!(image.entry("em.fmg").is_none());
+        assert!(image.entry("").is_none());
+    }
+
+    #[test]
+    fn serves_paths_under_root() {
+        let mut device = image().mount("GenMsg:");
+        assert_eq!(device.root(), "genmsg");
+        assert_eq!(
+            &**device.entry_for_path("genmsg:/item.fmg").unwrap(),
+            b"items"
+        );
+        assert!(device.entry_for_path("msg:/item.fmg").is_none());
+        assert!(device.entry_for_path("item.fmg").is_none());
+
+        let vmt = device.vftable;
+        let mut allocator = RustAllocator::new(0);
+        let mut container = DLFileOperatorContainer::new(allocator.as_base());
+        let mut open = |device: &mut MemoryBinderFileDevice, path: &str| {
+            let path = DLString::from_str(allocator.as_base(), path).unwrap();
+            (vmt.get_file_operator)(
+                device,
+                &path,
+                std::ptr::null(),
+                &mut container,
+                allocator.as_base(),
+                false,
+            )
+        };
+
+        // Paths the device doesn't serve fall through.
+        assert!(open(&mut device, "msg:/item.fmg").is_null());
+
+        let operator = open(&mut device, "genmsg:/menu.fmg")
+            as *mut AdapterFileOperator<Cursor<MemoryBinderEntryData>>;
+        let file = unsafe { &mut *operator };
+        let file_vmt = file.base.vftable;
+        assert_eq!(file.base.path.to_string(), "genmsg:/menu.fmg");
+        let renamed = DLString::from_str(allocator.as_base(), "genmsg:/msg/menu.fmg").unwrap();
+        assert!((file_vmt.set_path)(file, &renamed, false, false));
+        assert_eq!(file.base.path.to_string(), "genmsg:/msg/menu.fmg");
+        drop(renamed);
+        assert!((file_vmt.open)(file, OpenFileMode(0x1)));
+        let mut output = [0; 4];
+        assert_eq!(unsafe { (file_vmt.read)(file, output.as_mut_ptr(), 8) }, 4);
+        assert_eq!(&output, b"menu");
+        (file_vmt.destructor)(file);
+        allocator.as_base().deallocate(operator as *const u8);
+
+        assert!((vmt.file_enumerator)(&device).is_null());
+        assert_eq!(allocator.stats().live_allocations, 0);
+    }
+
+    #[test]
+    fn binds_image_through_operator() {
+        let mut device = image().mount("genmsg:");
+        let spi = NonNull::from(device.image()).cast::<DLFileDeviceImageSPIBase>();
+        let device = unsafe { device.as_device().as_ref() };
+        let mut allocator = RustAllocator::new(0);
+        let container = DLFileOperatorContainer::new(allocator.as_base());
+        let path = DLString::from_str(allocator.as_base(), "data0:/msg/item.msgbnd").unwrap();
+
+        let mut bind = |data: Vec<u8>| {
+            let mut file = AdapterFileOperator::new(
+                allocator.as_base(),
+                &path,
+                &container,
+                device,
+                Cursor::new(data),
+            );
+            let vmt = file.base.vftable;
+            assert!((vmt.open)(&mut file, OpenFileMode(0x1)));
+            let image = (vmt.bind_device_image)(&mut file, unsafe { spi.as_ref() });
+            (image as *mut MemoryBinderImage, file.base.result)
+        };
+
+        let (image, result) = bind(b"not a binder".to_vec());
+        assert!(image.is_null());
+        assert_eq!(result, DLIOResult::Invalid);
+
+        let (image, result) = bind(binder());
+        assert_eq!(result, DLIOResult::Success);
+        let bound = unsafe { &mut *image };
+        assert_eq!(bound.names().count(), 2);
+        assert_eq!(&**bound.entry("menu.fmg").unwrap(), b"menu");
+        (bound.vftable.destructor)(bound);
+        allocator.as_base().deallocate(image as *const u8);
+
+        drop(path);
+        assert_eq!(allocator.stats().live_allocations, 0);
+    }
+}
@@ -0,0 +1,571 @@
+//! File device serving loose files from mod directories ahead of the game's archives.
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::{Component, Path, PathBuf},
+    ptr::NonNull,
+    sync::Mutex,
+};
+
+use vtable_rs::VPtr;
+
+use crate::{
+    dlkr::{DLAllocatorBase, DLPlainLightMutex},
+    dltx::{DLBasicString, DLString, DLStringError},
+};
+
+use super::{
+    normalize_path, split_root, AdapterFileOperator, DLFileDeviceBase, DLFileDeviceDriveType,
+    DLFileDeviceManager, DLFileDeviceVmt, DLFileEnumeratorSPIBase, DLFileEnumeratorSPIVmt,
+    DLFileOperatorBase, DLFileOperatorContainer, DLIOResult,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModDirectory {
+    pub path: PathBuf,
+    pub priority: i32,
+}
+
+/// Mod directories laid out like the game's archives, so `data0:/chr/c0000.anibnd.dcx` is
+/// overridden by `<mod>/chr/c0000.anibnd.dcx`.
+#[derive(Debug, Clone, Default)]
+pub struct ModOverlay {
+    /// Sorted by descending priority, directories with equal priority keep their insertion order.
+    directories: Vec<ModDirectory>,
+}
+
+impl ModOverlay {
+    pub fn add_directory(&mut self, path: impl Into<PathBuf>, priority: i32) {
+        let index = self
+            .directories
+            .partition_point(|directory| directory.priority >= priority);
+        self.directories.insert(
+            index,
+            ModDirectory {
+                path: path.into(),
+                priority,
+            },
+        );
+    }
+
+    pub fn directories(&self) -> &[ModDirectory] {
+        &self.directories
+    }
+
+    /// File overriding a virtual path, taken from the directory with the highest priority.
+    /// Paths on disk are never overridden.
+    pub fn find(&self, path: &str) -> Option<PathBuf> {
+        let path = normalize_path(path);
+        let relative = Self::relative_path(&path)?;
+        self.directories
+            .iter()
+            .map(|directory| directory.path.join(relative))
+            .find(|path| path.is_file())
+    }
+
+    /// Mod files matching a search like `data0:/map/*.msb`. Only the last component may contain
+    /// `*` and `?` wildcards. Results keep the search's directory, files present in several mod
+    /// directories are reported once.
+    pub fn list(&self, search: &str) -> Vec<String> {
+        let search = normalize_path(search);
+        let Some(relative) = Self::relative_path(&search) else {
+            return vec![];
+        };
+        let (directory, pattern) = relative.rsplit_once('/').unwrap_or(("", relative));
+        let prefix = &search[..search.len() - pattern.len()];
+
+        let mut found = vec![];
+        let mut seen = HashSet::new();
+        for mod_directory in self.directories.iter() {
+            let Ok(entries) = mod_directory.path.join(directory).read_dir() else {
+                continue;
+            };
+
+            let mut names = entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+                .filter_map(|entry| entry.file_name().to_str().map(normalize_path))
+                .filter(|name| wildcard_match(pattern, name))
+                .collect::<Vec<_>>();
+            names.sort();
+
+            for name in names {
+                if seen.insert(name.clone()) {
+                    found.push(format!("{prefix}{name}"));
+                }
+            }
+        }
+        found
+    }
+
+    /// Path below the virtual root, `None` for paths on disk and for paths that could leave the
+    /// mod directories through `..` or a drive.
+    fn relative_path(path: &str) -> Option<&str> {
+        let (_, rest) = split_root(path)?;
+        let rest = rest.trim_start_matches(['/', '\\']);
+        let contained = !rest.contains(':')
+            && Path::new(rest)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        (!rest.is_empty() && contained).then_some(rest)
+    }
+}
+
+/// Matches a file name against a pattern with `*` and `?` wildcards.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Combines mod files with the files found by the game's own enumerator. Mod files come first
+/// and vanilla files with the same name as a mod file are dropped.
+pub fn merge_listings(mod_files: Vec<String>, vanilla_files: Vec<String>) -> Vec<String> {
+    fn file_name(path: &str) -> String {
+        let path = normalize_path(path);
+        match path.rsplit_once(['/', ':']) {
+            Some((_, name)) => name.to_string(),
+            None => path,
+        }
+    }
+
+    let names = mod_files
+        .iter()
+        .map(|path| file_name(path))
+        .collect::<HashSet<_>>();
+    let vanilla_files = vanilla_files
+        .into_iter()
+        .filter(|path| !names.contains(&file_name(path)));
+    mod_files.into_iter().chain(vanilla_files).collect()
+}
+
+/// Enumerator for [`ModOverlayFileDevice`]. Searches run to completion when they're started and
+/// the results are handed out one by one.
+#[repr(C)]
+pub struct ModOverlayEnumerator {
+    pub vftable: VPtr<dyn DLFileEnumeratorSPIVmt, Self>,
+    overlay: NonNull<ModOverlay>,
+    vanilla: Option<NonNull<DLFileEnumeratorSPIBase>>,
+    /// Allocator the game's search result strings are allocated with, used to grow them for
+    /// results that don't fit.
+    string_allocator: Option<NonNull<DLAllocatorBase>>,
+    searches: Mutex<HashMap<u64, std::vec::IntoIter<String>>>,
+    next_handle: u64,
+    result: DLIOResult,
+}
+
+impl ModOverlayEnumerator {
+    fn overlay(&self) -> &ModOverlay {
+        unsafe { self.overlay.as_ref() }
+    }
+
+    /// Result of the last search, the vtable has no way to report errors so searches that
+    /// failed end early and leave their reason here.
+    pub fn result(&self) -> DLIOResult {
+        self.result
+    }
+
+    /// Runs the search on the vanilla enumerator and collects its results. `found` is used as
+    /// scratch space so the strings are allocated by the enumerator that fills them.
+    fn vanilla_files(&mut self, search: &[u16], found: &mut DLBasicString) -> Vec<String> {
+        let Some(mut vanilla) = self.vanilla else {
+            return vec![];
+        };
+        let vanilla = unsafe { vanilla.as_mut() };
+
+        let mut files = vec![];
+        let mut handle = 0;
+        (vanilla.vftable.start_search)(vanilla, search, &mut handle, found);
+        while found.length != 0 {
+            files.push(found.to_string());
+            (vanilla.vftable.search_next)(vanilla, &mut handle, found);
+        }
+        (vanilla.vftable.close_search)(vanilla, &handle);
+        files
+    }
+
+    fn write_next(&mut self, search_handle: u64, found: &mut DLBasicString) {
+        let mut searches = self.searches.lock().unwrap();
+        let Some(path) = searches.get_mut(&search_handle).and_then(|r| r.next()) else {
+            unsafe { found.assign_in_place(&[]) };
+            return;
+        };
+
+        let path = path.encode_utf16().collect::<Vec<_>>();
+        let assigned = match self.string_allocator {
+            Some(mut allocator) => unsafe { found.assign_with(allocator.as_mut(), &path) },
+            None => match unsafe { found.assign_in_place(&path) } {
+                true => Ok(()),
+                false => Err(DLStringError::NoAllocator),
+            },
+        };
+        if let Err(e) = assigned {
+            tracing::error!("Ending search at {}: {e}", String::from_utf16_lossy(&path));
+            self.result = match e {
+                DLStringError::NoAllocator => DLIOResult::OperationUnsupported,
+                DLStringError::AllocationFailed(_) => DLIOResult::OutOfMemory,
+            };
+            searches.remove(&search_handle);
+            unsafe { found.assign_in_place(&[]) };
+        }
+    }
+}
+
+impl DLFileEnumeratorSPIVmt for ModOverlayEnumerator {
+    extern "C" fn destructor(&mut self) {
+        tracing::debug!("ModOverlayEnumerator::destructor()");
+    }
+
+    // The slice is part of the trait's signature.
+    #[allow(improper_ctypes_definitions)]
+    extern "C" fn start_search(
+        &mut self,
+        search: &[u16],
+        search_handle: &mut u64,
+        found: &mut DLBasicString,
+    ) {
+        let pattern = String::from_utf16_lossy(search);
+        let pattern = pattern.trim_end_matches('\0');
+        tracing::debug!("ModOverlayEnumerator::start_search({pattern})");
+
+        let vanilla_files = self.vanilla_files(search, found);
+        let files = merge_listings(self.overlay().list(pattern), vanilla_files);
+
+        self.next_handle += 1;
+        self.result = DLIOResult::Success;
+        *search_handle = self.next_handle;
+        self.searches
+            .lock()
+            .unwrap()
+            .insert(self.next_handle, files.into_iter());
+        self.write_next(*search_handle, found);
+    }
+
+    extern "C" fn close_search(&self, search_handle: &u64) {
+        tracing::debug!("ModOverlayEnumerator::close_search({search_handle})");
+        self.searches.lock().unwrap().remove(search_handle);
+    }
+
+    extern "C" fn search_next(&mut self, search_handle: &mut u64, found: &mut DLBasicString) {
+        self.write_next(*search_handle, found);
+    }
+}
+
+/// File device that serves files from a [`ModOverlay`] and falls through for everything else.
+/// Put it in front of the archive devices with [`ModOverlayFileDevice::install`]. The device must
+/// stay pinned at its address while the game uses it.
+#[repr(C)]
+pub struct ModOverlayFileDevice {
+    pub vftable: VPtr<dyn DLFileDeviceVmt, Self>,
+    unk8: bool,
+    ref_count: u32,
+    pub mutex: DLPlainLightMutex,
+    overlay: Box<ModOverlay>,
+    enumerator: ModOverlayEnumerator,
+}
+
+impl ModOverlayFileDevice {
+    pub fn new(overlay: ModOverlay) -> Box<Self> {
+        let overlay = Box::new(overlay);
+        let mut device = Box::<Self>::new_uninit();
+        let ptr = device.as_mut_ptr();
+        unsafe {
+            (&raw mut (*ptr).vftable).write(Default::default());
+            (&raw mut (*ptr).unk8).write(false);
+            (&raw mut (*ptr).ref_count).write(1);
+            DLPlainLightMutex::initialize_at(&raw mut (*ptr).mutex);
+            (&raw mut (*ptr).enumerator).write(ModOverlayEnumerator {
+                vftable: Default::default(),
+                overlay: NonNull::from(overlay.as_ref()),
+                vanilla: None,
+                string_allocator: None,
+                searches: Default::default(),
+                next_handle: 0,
+                result: DLIOResult::Success,
+            });
+            (&raw mut (*ptr).overlay).write(overlay);
+            device.assume_init()
+        }
+    }
+
+    pub fn overlay(&self) -> &ModOverlay {
+        &self.overlay
+    }
+
+    /// Enumerator whose results are merged with the mod files, usually the one of the archive
+    /// device the overlay sits in front of.
+    pub fn set_vanilla_enumerator(&mut self, enumerator: Option<NonNull<DLFileEnumeratorSPIBase>>) {
+        self.enumerator.vanilla = enumerator;
+    }
+
+    /// Allocator the game allocates its search result strings with. Without it results longer
+    /// than the strings' capacity end the search, see [`ModOverlayEnumerator::result`].
+    pub fn set_string_allocator(&mut self, allocator: Option<NonNull<DLAllocatorBase>>) {
+        self.enumerator.string_allocator = allocator;
+    }
+
+    pub fn enumerator(&self) -> &ModOverlayEnumerator {
+        &self.enumerator
+    }
+
+    /// Puts the device in front of the manager's device list so it's asked first.
+    pub fn install(&mut self, manager: &mut DLFileDeviceManager) {
+        let device = self.as_device();
+        manager.mutex.lock();
+        manager.devices.insert(0, device);
+        manager.mutex.unlock();
+    }
+
+    /// Removes the device from the manager's device list. Operators that were already handed out
+    /// keep pointing at it.
+    pub fn uninstall(&mut self, manager: &mut DLFileDeviceManager) {
+        let device = self.as_device();
+        manager.mutex.lock();
+        manager.devices.retain(|d| *d != device);
+        manager.mutex.unlock();
+    }
+
+    /// Pointer to hand to the game, the device starts with the same layout as
+    /// [`DLFileDeviceBase`].
+    pub fn as_device(&mut self) -> NonNull<DLFileDeviceBase> {
+        NonNull::from(self).cast()
+    }
+}
+
+impl DLFileDeviceVmt for ModOverlayFileDevice {
+    extern "C" fn destructor(&mut self) {
+        tracing::debug!("ModOverlayFileDevice::destructor()");
+    }
+
+    extern "C" fn get_file_operator(
+        &mut self,
+        path_dlstring: &DLString,
+        path_u16: *const u16,
+        operator_container: &mut DLFileOperatorContainer,
+        allocator: &mut DLAllocatorBase,
+        is_temp_file: bool,
+    ) -> *const DLFileOperatorBase {
+        let path = path_dlstring.to_string();
+        let Some(file_path) = self.overlay.find(&path) else {
+            return std::ptr::null();
+        };
+        tracing::debug!(
+            "ModOverlayFileDevice::get_file_operator({path}) -> {}",
+            file_path.display()
+        );
+
+        match File::open(&file_path) {
+            Ok(file) => {
+                let device = unsafe { &*(self as *const Self as *const DLFileDeviceBase) };
+                AdapterFileOperator::new(allocator, path_dlstring, operator_container, device, file)
+                    .into_allocated(allocator)
+            }
+            Err(e) => {
+                tracing::warn!("Could not open {}: {e}", file_path.display());
+                std::ptr::null()
+            }
+        }
+    }
+
+    extern "C" fn file_enumerator(&self) -> *const u8 {
+        &self.enumerator as *const ModOverlayEnumerator as *const u8
+    }
+
+    extern "C" fn get_drive_type(&self, path: *const u16) -> DLFileDeviceDriveType {
+        DLFileDeviceDriveType::Default
+    }
+
+    extern "C" fn is_encrypted(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, fs::File, path::PathBuf, ptr::NonNull};
+
+    use super::{
+        merge_listings, wildcard_match, ModOverlay, ModOverlayEnumerator, ModOverlayFileDevice,
+    };
+    use crate::{
+        dlio::{
+            AdapterFileOperator, DLFileEnumeratorSPIBase, DLFileOperatorContainer, DLIOResult,
+            OpenFileMode,
+        },
+        dlkr::RustAllocator,
+        dltx::DLString,
+    };
+
+    /// Directory holding one test's mods, removed when dropped.
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new(test: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "eldenring-mod-overlay-{}-{test}",
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+
+        /// Writes the files with the name of the directory as their content.
+        fn write(&self, name: &str, files: &[&str]) -> PathBuf {
+            let path = self.0.join(name);
+            for file in files {
+                let file = path.join(file);
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(file, name).unwrap();
+            }
+            path
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn overlay(directory: &TestDirectory) -> ModOverlay {
+        let mut overlay = ModOverlay::default();
+        overlay.add_directory(directory.write("low", &["map/a.msb", "map/b.msb"]), 0);
+        overlay.add_directory(
+            directory.write("high", &["map/b.msb", "map/c.txt", "regulation.bin"]),
+            10,
+        );
+        overlay
+    }
+
+    #[test]
+    fn finds_by_priority() {
+        let directory = TestDirectory::new("finds_by_priority");
+        let overlay = overlay(&directory);
+        assert_eq!(overlay.directories()[0].priority, 10);
+
+        let found = overlay.find("data0:/map/b.msb").unwrap();
+        assert_eq!(fs::read_to_string(found).unwrap(), "high");
+        let found = overlay.find(r"DATA0:\Map\A.msb").unwrap();
+        assert_eq!(fs::read_to_string(found).unwrap(), "low");
+        assert!(overlay.find("data0:/map/d.msb").is_none());
+        assert!(overlay.find("map/a.msb").is_none());
+
+        // Paths escaping the mod directories are never served.
+        directory.write("outside", &["secret.txt"]);
+        assert!(overlay.find("data0:/../outside/secret.txt").is_none());
+        assert!(overlay
+            .find(r"data0:\map\..\..\outside\secret.txt")
+            .is_none());
+        assert!(overlay.list("data0:/../outside/*").is_empty());
+        let absolute = directory.0.join("outside/secret.txt");
+        assert!(overlay
+            .find(&format!("data0:{}", absolute.display()))
+            .is_none());
+
+        assert_eq!(
+            overlay.list("data0:/map/*.msb"),
+            ["data0:/map/b.msb", "data0:/map/a.msb"]
+        );
+        assert!(wildcard_match("m60_*_?0.msb", "m60_44_36_00.msb"));
+        assert!(!wildcard_match("*.msb", "a.msb.dcx"));
+    }
+
+    #[test]
+    fn merges_with_vanilla() {
+        assert_eq!(
+            merge_listings(
+                vec!["data0:/map/b.msb".to_string()],
+                vec![
+                    "data0:/map/a.msb".to_string(),
+                    "data0:/Map/B.msb".to_string()
+                ],
+            ),
+            ["data0:/map/b.msb", "data0:/map/a.msb"]
+        );
+    }
+
+    #[test]
+    fn drives_device_through_vtable() {
+        let directory = TestDirectory::new("drives_device_through_vtable");
+        let mut device = ModOverlayFileDevice::new(overlay(&directory));
+        let vmt = device.vftable;
+        let mut allocator = RustAllocator::new(0);
+
+        let enumerator = (vmt.file_enumerator)(&device) as *mut DLFileEnumeratorSPIBase;
+        let enumerator = unsafe { &mut *enumerator.cast::<ModOverlayEnumerator>() };
+        let search = "data0:/*".encode_utf16().collect::<Vec<_>>();
+        let mut handle = 0;
+        let mut found = DLString::new(allocator.as_base());
+        let mut search_all = |enumerator: &mut ModOverlayEnumerator| {
+            let mut results = vec![];
+            (enumerator.vftable.start_search)(enumerator, &search, &mut handle, &mut found.inner);
+            while !found.is_empty() {
+                results.push(found.to_string());
+                (enumerator.vftable.search_next)(enumerator, &mut handle, &mut found.inner);
+            }
+            (enumerator.vftable.close_search)(enumerator, &handle);
+            results
+        };
+
+        // Longer paths don't fit the string's inline buffer and can't be grown without knowing
+        // its allocator.
+        assert!(search_all(enumerator).is_empty());
+        assert_eq!(enumerator.result(), DLIOResult::OperationUnsupported);
+
+        device.set_string_allocator(Some(NonNull::from(allocator.as_base())));
+        assert_eq!(search_all(enumerator), ["data0:/regulation.bin"]);
+        assert_eq!(enumerator.result(), DLIOResult::Success);
+        drop(found);
+
+        let mut container = DLFileOperatorContainer::new(allocator.as_base());
+        let mut open = |device: &mut ModOverlayFileDevice, path: &str| {
+            let path = DLString::from_str(allocator.as_base(), path).unwrap();
+            (vmt.get_file_operator)(
+                device,
+                &path,
+                std::ptr::null(),
+                &mut container,
+                allocator.as_base(),
+                false,
+            )
+        };
+
+        // Files missing from the overlay fall through.
+        assert!(open(&mut device, "data0:/map/d.msb").is_null());
+
+        let operator = open(&mut device, "data0:/map/b.msb") as *mut AdapterFileOperator<File>;
+        let file = unsafe { &mut *operator };
+        let file_vmt = file.base.vftable;
+        assert!((file_vmt.open)(file, OpenFileMode(0x1)));
+        let mut output = [0; 8];
+        assert_eq!(unsafe { (file_vmt.read)(file, output.as_mut_ptr(), 8) }, 4);
+        assert_eq!(&output[..4], b"high");
+        (file_vmt.destructor)(file);
+        allocator.as_base().deallocate(operator as *const u8);
+
+        assert_eq!(allocator.stats().live_allocations, 0);
+    }
+}
@@ -0,0 +1,585 @@
+//! Bridges between the game's streams and [`std::io`].
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use vtable_rs::VPtr;
+
+use super::{
+    DLFileSeekDirection, DLIOResult, DLInputStreamVmt, DLOutputStreamVmt, DLSeekableInputStreamVmt,
+    DLSeekableOutputStreamVmt,
+};
+
+/// Largest read or write the streams accept in one call, lengths are cast to 32 bits.
+const MAX_TRANSFER: usize = i32::MAX as usize;
+
+/// Input stream [`DLStreamReader`] can read from. Implemented for every [`DLInputStreamVmt`] and
+/// for the game's own streams, which are called through their vftable. Methods are named apart
+/// from the vmt's so both traits can be in scope at once.
+pub trait DLInputStream {
+    fn read_into(&mut self, output: *mut u8, length: usize) -> i32;
+
+    fn status(&self) -> DLIOResult;
+
+    fn bytes_left(&self) -> usize;
+}
+
+pub trait DLSeekableInputStream: DLInputStream {
+    fn seek_to(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool;
+
+    fn position(&self) -> usize;
+}
+
+/// Output stream [`DLStreamWriter`] can write to. Implemented for every [`DLOutputStreamVmt`] and
+/// for the game's own streams, which are called through their vftable.
+pub trait DLOutputStream {
+    fn write_from(&mut self, input: *const u8, length: usize) -> usize;
+
+    fn last_error(&self) -> DLIOResult;
+
+    fn flush_stream(&mut self);
+}
+
+pub trait DLSeekableOutputStream: DLOutputStream {
+    fn seek_to(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool;
+
+    fn pad_to(&mut self, alignment: usize, padding_byte: u8) -> bool;
+}
+
+impl<S: DLInputStreamVmt + ?Sized> DLInputStream for S {
+    fn read_into(&mut self, output: *mut u8, length: usize) -> i32 {
+        DLInputStreamVmt::read_bytes(self, output, length)
+    }
+
+    fn status(&self) -> DLIOResult {
+        DLInputStreamVmt::get_status(self)
+    }
+
+    fn bytes_left(&self) -> usize {
+        DLInputStreamVmt::get_bytes_left(self)
+    }
+}
+
+impl<S: DLSeekableInputStreamVmt + ?Sized> DLSeekableInputStream for S {
+    fn seek_to(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+        DLSeekableInputStreamVmt::seek(self, offset, mode)
+    }
+
+    fn position(&self) -> usize {
+        DLSeekableInputStreamVmt::current_position(self)
+    }
+}
+
+impl<S: DLOutputStreamVmt + ?Sized> DLOutputStream for S {
+    fn write_from(&mut self, input: *const u8, length: usize) -> usize {
+        DLOutputStreamVmt::write(self, input, length)
+    }
+
+    fn last_error(&self) -> DLIOResult {
+        DLOutputStreamVmt::get_last_error(self)
+    }
+
+    fn flush_stream(&mut self) {
+        DLOutputStreamVmt::flush(self)
+    }
+}
+
+impl<S: DLSeekableOutputStreamVmt + ?Sized> DLSeekableOutputStream for S {
+    fn seek_to(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+        DLSeekableOutputStreamVmt::seek(self, offset, mode)
+    }
+
+    fn pad_to(&mut self, alignment: usize, padding_byte: u8) -> bool {
+        DLSeekableOutputStreamVmt::pad_to_align(self, alignment, padding_byte)
+    }
+}
+
+/// Implements [`Read`] and [`Seek`] over a game input stream.
+pub struct DLStreamReader<'a, S: ?Sized> {
+    stream: &'a mut S,
+}
+
+impl<'a, S: DLInputStream + ?Sized> DLStreamReader<'a, S> {
+    pub fn new(stream: &'a mut S) -> Self {
+        Self { stream }
+    }
+
+    pub fn bytes_left(&self) -> usize {
+        self.stream.bytes_left()
+    }
+}
+
+impl<S: DLInputStream + ?Sized> Read for DLStreamReader<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let length = buf.len().min(MAX_TRANSFER);
+        match self.stream.read_into(buf.as_mut_ptr(), length) {
+            read if read < 0 => Err(self.stream.status().into()),
+            read => Ok(read as usize),
+        }
+    }
+}
+
+impl<S: DLSeekableInputStream + ?Sized> Seek for DLStreamReader<'_, S> {
+    /// Seeks relative to the start of the stream as the game takes unsigned offsets.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let current = self.stream.position() as u64;
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => current.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                (current + self.stream.bytes_left() as u64).checked_add_signed(offset)
+            }
+        };
+        let Some(target) = target else {
+            return Err(DLIOResult::Invalid.into());
+        };
+
+        match self
+            .stream
+            .seek_to(target as usize, DLFileSeekDirection::Head)
+        {
+            true => Ok(self.stream.position() as u64),
+            false => Err(self.stream.status().into()),
+        }
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.stream.position() as u64)
+    }
+}
+
+/// Implements [`Write`] over a game output stream.
+pub struct DLStreamWriter<'a, S: ?Sized> {
+    stream: &'a mut S,
+}
+
+impl<'a, S: DLOutputStream + ?Sized> DLStreamWriter<'a, S> {
+    pub fn new(stream: &'a mut S) -> Self {
+        Self { stream }
+    }
+}
+
+impl<S: DLSeekableOutputStream + ?Sized> DLStreamWriter<'_, S> {
+    /// Output streams don't report their position so this can't implement [`Seek`].
+    pub fn seek(&mut self, offset: usize, direction: DLFileSeekDirection) -> io::Result<()> {
+        match self.stream.seek_to(offset, direction) {
+            true => Ok(()),
+            false => Err(self.stream.last_error().into()),
+        }
+    }
+
+    pub fn pad_to_align(&mut self, alignment: usize, padding_byte: u8) -> io::Result<()> {
+        match self.stream.pad_to(alignment, padding_byte) {
+            true => Ok(()),
+            false => Err(self.stream.last_error().into()),
+        }
+    }
+}
+
+impl<S: DLOutputStream + ?Sized> Write for DLStreamWriter<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let length = buf.len().min(MAX_TRANSFER);
+        match self.stream.write_from(buf.as_ptr(), length) {
+            0 => Err(self.stream.last_error().into()),
+            written => Ok(written),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush_stream();
+        match self.stream.last_error() {
+            DLIOResult::Success => Ok(()),
+            error => Err(error.into()),
+        }
+    }
+}
+
+/// Input stream the game can read from, backed by any [`Read`] + [`Seek`].
+#[repr(C)]
+pub struct RustInputStream<R: Read + Seek + 'static> {
+    pub vftable: VPtr<dyn DLSeekableInputStreamVmt, Self>,
+    reader: R,
+    position: u64,
+    len: u64,
+    /// Amount of bytes read by the last async read, which completes immediately.
+    last_read_bytes: u32,
+    is_open: bool,
+    status: DLIOResult,
+}
+
+impl<R: Read + Seek + 'static> RustInputStream<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let position = reader.stream_position()?;
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(position))?;
+
+        Ok(Self {
+            vftable: Default::default(),
+            reader,
+            position,
+            len,
+            last_read_bytes: 0,
+            is_open: true,
+            status: DLIOResult::Success,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn finish(&mut self, result: DLIOResult) -> bool {
+        self.status = result;
+        result == DLIOResult::Success
+    }
+
+    fn move_to(&mut self, target: u64) -> bool {
+        if target > self.len {
+            return self.finish(DLIOResult::Invalid);
+        }
+
+        match self.reader.seek(SeekFrom::Start(target)) {
+            Ok(position) => {
+                self.position = position;
+                self.finish(DLIOResult::Success)
+            }
+            Err(_) => self.finish(DLIOResult::Invalid),
+        }
+    }
+
+    fn read_raw(&mut self, output: *mut u8, length: usize) -> i32 {
+        if !self.is_open {
+            self.finish(DLIOResult::IsNotOpen);
+            return -1;
+        }
+
+        let output = unsafe { std::slice::from_raw_parts_mut(output, length.min(MAX_TRANSFER)) };
+        let mut total = 0;
+        while total < output.len() {
+            match self.reader.read(&mut output[total..]) {
+                Ok(0) => break,
+                Ok(read) => total += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.finish(DLIOResult::Invalid);
+                    return -1;
+                }
+            }
+        }
+
+        self.position += total as u64;
+        self.finish(DLIOResult::Success);
+        total as i32
+    }
+}
+
+impl<R: Read + Seek + 'static> DLInputStreamVmt for RustInputStream<R> {
+    extern "C" fn set_last_error(&mut self, status: DLIOResult) {
+        self.status = status;
+    }
+
+    extern "C" fn destructor(&mut self, param_2: u32) {
+        self.is_open = false;
+    }
+
+    extern "C" fn get_status(&self) -> DLIOResult {
+        self.status
+    }
+
+    extern "C" fn read_bytes(&mut self, output: *mut u8, length: usize) -> i32 {
+        self.read_raw(output, length)
+    }
+
+    extern "C" fn has_bytes_left(&self) -> bool {
+        self.is_open && self.position < self.len
+    }
+
+    extern "C" fn get_bytes_left(&self) -> usize {
+        self.len.saturating_sub(self.position) as usize
+    }
+
+    extern "C" fn skip_bytes(&mut self, count: usize) -> usize {
+        let count = (count.min(MAX_TRANSFER) as u64).min(self.len.saturating_sub(self.position));
+        match self.move_to(self.position + count) {
+            true => count as usize,
+            false => 0,
+        }
+    }
+
+    extern "C" fn close_stream(&mut self) {
+        self.is_open = false;
+    }
+
+    extern "C" fn stream_open(&self) -> bool {
+        self.is_open
+    }
+
+    extern "C" fn get_disk_sector_size(&self) -> u32 {
+        0
+    }
+
+    extern "C" fn get_disk_sector_size_2(&self) -> u32 {
+        0
+    }
+
+    extern "C" fn query_async_status(
+        &mut self,
+        bytes_remaining: &mut usize,
+        bytes_transferred: Option<&mut usize>,
+    ) -> bool {
+        *bytes_remaining = 0;
+        if let Some(bytes_transferred) = bytes_transferred {
+            *bytes_transferred = self.last_read_bytes as usize;
+        }
+        true
+    }
+
+    extern "C" fn read_bytes_async(&mut self, output: *mut u8, length: usize) -> bool {
+        let read = self.read_bytes(output, length);
+        self.last_read_bytes = read.max(0) as u32;
+        read >= 0
+    }
+}
+
+impl<R: Read + Seek + 'static> DLSeekableInputStreamVmt for RustInputStream<R> {
+    extern "C" fn seek(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+        let base = match mode {
+            DLFileSeekDirection::Head => 0,
+            DLFileSeekDirection::Current => self.position,
+            DLFileSeekDirection::Tail => self.len,
+        };
+        match base.checked_add(offset as u64) {
+            Some(target) => self.move_to(target),
+            None => self.finish(DLIOResult::Invalid),
+        }
+    }
+
+    extern "C" fn current_position(&self) -> usize {
+        self.position as usize
+    }
+
+    extern "C" fn reached_end(&self) -> bool {
+        self.position >= self.len
+    }
+}
+
+/// Output stream the game can write to, backed by any [`Write`] + [`Seek`].
+#[repr(C)]
+pub struct RustOutputStream<W: Write + Seek + 'static> {
+    pub vftable: VPtr<dyn DLSeekableOutputStreamVmt, Self>,
+    writer: W,
+    position: u64,
+    /// Amount of bytes written by the last async write, which completes immediately.
+    last_bytes_written: usize,
+    auto_flush: bool,
+    is_open: bool,
+    status: DLIOResult,
+}
+
+impl<W: Write + Seek + 'static> RustOutputStream<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        Ok(Self {
+            vftable: Default::default(),
+            position: writer.stream_position()?,
+            writer,
+            last_bytes_written: 0,
+            auto_flush: false,
+            is_open: true,
+            status: DLIOResult::Success,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn finish(&mut self, result: DLIOResult) -> bool {
+        self.status = result;
+        result == DLIOResult::Success
+    }
+
+    fn write_raw(&mut self, input: *const u8, length: usize) -> usize {
+        let input = unsafe { std::slice::from_raw_parts(input, length.min(MAX_TRANSFER)) };
+        match self.write_all(input) {
+            true => input.len(),
+            false => 0,
+        }
+    }
+
+    fn write_all(&mut self, input: &[u8]) -> bool {
+        if !self.is_open {
+            return self.finish(DLIOResult::IsNotOpen);
+        }
+        if self.writer.write_all(input).is_err() {
+            return self.finish(DLIOResult::DiskFull);
+        }
+
+        self.position += input.len() as u64;
+        if self.auto_flush && self.writer.flush().is_err() {
+            return self.finish(DLIOResult::Invalid);
+        }
+        self.finish(DLIOResult::Success)
+    }
+}
+
+impl<W: Write + Seek + 'static> DLOutputStreamVmt for RustOutputStream<W> {
+    extern "C" fn set_last_error(&mut self, status: DLIOResult) {
+        self.status = status;
+    }
+
+    extern "C" fn destructor(&mut self, param_2: u32) {
+        self.close();
+    }
+
+    extern "C" fn get_last_error(&self) -> DLIOResult {
+        self.status
+    }
+
+    extern "C" fn write(&mut self, input: *const u8, length: usize) -> usize {
+        self.write_raw(input, length)
+    }
+
+    extern "C" fn get_write_size(&self) -> usize {
+        match self.is_open {
+            true => MAX_TRANSFER,
+            false => 0,
+        }
+    }
+
+    extern "C" fn close(&mut self) {
+        if self.is_open {
+            self.flush();
+            self.is_open = false;
+        }
+    }
+
+    extern "C" fn flush(&mut self) {
+        let result = match self.writer.flush() {
+            Ok(_) => DLIOResult::Success,
+            Err(_) => DLIOResult::Invalid,
+        };
+        self.finish(result);
+    }
+
+    extern "C" fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    extern "C" fn get_async_block_size(&self) -> u32 {
+        0x10000
+    }
+
+    extern "C" fn get_async_buffer_alignment_size(&self) -> u32 {
+        1
+    }
+
+    extern "C" fn query_async_status(
+        &mut self,
+        bytes_remaining: &mut usize,
+        bytes_transferred: Option<&mut usize>,
+    ) -> bool {
+        *bytes_remaining = 0;
+        if let Some(bytes_transferred) = bytes_transferred {
+            *bytes_transferred = self.last_bytes_written;
+        }
+        true
+    }
+
+    unsafe extern "C" fn start_async_write(&mut self, input: *const u8, length: usize) -> bool {
+        self.last_bytes_written = self.write(input, length);
+        self.status == DLIOResult::Success
+    }
+}
+
+impl<W: Write + Seek + 'static> DLSeekableOutputStreamVmt for RustOutputStream<W> {
+    extern "C" fn seek(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+        let position = match mode {
+            DLFileSeekDirection::Head => SeekFrom::Start(offset as u64),
+            DLFileSeekDirection::Current => SeekFrom::Current(offset as i64),
+            DLFileSeekDirection::Tail => SeekFrom::End(offset as i64),
+        };
+        match self.writer.seek(position) {
+            Ok(position) => {
+                self.position = position;
+                self.finish(DLIOResult::Success)
+            }
+            Err(_) => self.finish(DLIOResult::Invalid),
+        }
+    }
+
+    extern "C" fn set_auto_flush(&mut self, auto_flush: bool) {
+        self.auto_flush = auto_flush;
+    }
+
+    extern "C" fn pad_to_align(&mut self, alignment: usize, padding_byte: u8) -> bool {
+        if alignment == 0 {
+            return self.finish(DLIOResult::Success);
+        }
+
+        let padding = (alignment - (self.position as usize % alignment)) % alignment;
+        self.write_all(&vec![padding_byte; padding])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    use super::{DLStreamReader, DLStreamWriter, RustInputStream, RustOutputStream};
+    use crate::dlio::{
+        DLFileOutputStream, DLFileSeekDirection, DLIOResult, DLOutputStreamVmt,
+        PseudoAsyncInputStream,
+    };
+
+    #[test]
+    fn reads_through_vtable() {
+        let mut stream = RustInputStream::new(Cursor::new(b"0123456789".to_vec())).unwrap();
+        // Read through the vftable like the game would with one of its own streams.
+        let game_stream = unsafe { &mut *(&mut stream as *mut _ as *mut PseudoAsyncInputStream) };
+        let mut reader = DLStreamReader::new(game_stream);
+
+        let mut output = [0; 4];
+        reader.read_exact(&mut output).unwrap();
+        assert_eq!(&output, b"0123");
+        assert_eq!(reader.seek(SeekFrom::Current(-2)).unwrap(), 2);
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 7);
+        assert_eq!(reader.bytes_left(), 3);
+
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"789");
+
+        let error = reader.seek(SeekFrom::Start(11)).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(reader.seek(SeekFrom::Current(-20)).is_err());
+    }
+
+    #[test]
+    fn writes_through_vtable() {
+        let mut stream = RustOutputStream::new(Cursor::new(vec![])).unwrap();
+        let game_stream = unsafe { &mut *(&mut stream as *mut _ as *mut DLFileOutputStream) };
+        let mut writer = DLStreamWriter::new(game_stream);
+
+        writer.write_all(b"abc").unwrap();
+        writer.pad_to_align(8, 0xFF).unwrap();
+        writer.write_all(b"d").unwrap();
+        writer.seek(1, DLFileSeekDirection::Head).unwrap();
+        writer.write_all(b"B").unwrap();
+        writer.flush().unwrap();
+
+        stream.close();
+        assert!(!stream.is_open());
+        assert_eq!(stream.write(b"e".as_ptr(), 1), 0);
+        assert_eq!(stream.get_last_error(), DLIOResult::IsNotOpen);
+        assert_eq!(
+            stream.into_inner().into_inner(),
+            b"aBc\xFF\xFF\xFF\xFF\xFFd"
+        );
+    }
+}
@@ -4,7 +4,10 @@ use vtable_rs::VPtr;
 
 use crate::{dlkr::DLAllocatorBase, pointer::OwnedPtr};
 
-use super::{DLFileOperatorContainer, DLFileSeekDirection, DLIOResult};
+use super::{
+    DLFileOperatorContainer, DLFileSeekDirection, DLIOResult, DLInputStream, DLOutputStream,
+    DLSeekableInputStream, DLSeekableOutputStream,
+};
 
 #[vtable_rs::vtable]
 pub trait DLInputStreamVmt {
@@ -239,3 +242,207 @@ pub struct DLMemoryOutputStream {
     pub status: DLIOResult,
     // _pad: [u8; 4],
 }
+
+/// Calls go through the vftable of the stream. The game's streams deliberately don't implement the
+/// vmts themselves, a vftable that forwards to itself would recurse forever.
+macro_rules! forward_seekable_input_stream {
+    ($($stream:ty),*) => {$(
+        impl $stream {
+            pub fn set_last_error(&mut self, status: DLIOResult) {
+                (self.vftable.set_last_error)(self, status)
+            }
+
+            pub fn destructor(&mut self, param_2: u32) {
+                (self.vftable.destructor)(self, param_2)
+            }
+
+            pub fn get_status(&self) -> DLIOResult {
+                (self.vftable.get_status)(self)
+            }
+
+            pub fn read_bytes(&mut self, output: *mut u8, length: usize) -> i32 {
+                (self.vftable.read_bytes)(self, output, length)
+            }
+
+            pub fn has_bytes_left(&self) -> bool {
+                (self.vftable.has_bytes_left)(self)
+            }
+
+            pub fn get_bytes_left(&self) -> usize {
+                (self.vftable.get_bytes_left)(self)
+            }
+
+            pub fn skip_bytes(&mut self, count: usize) -> usize {
+                (self.vftable.skip_bytes)(self, count)
+            }
+
+            pub fn close_stream(&mut self) {
+                (self.vftable.close_stream)(self)
+            }
+
+            pub fn stream_open(&self) -> bool {
+                (self.vftable.stream_open)(self)
+            }
+
+            pub fn get_disk_sector_size(&self) -> u32 {
+                (self.vftable.get_disk_sector_size)(self)
+            }
+
+            pub fn get_disk_sector_size_2(&self) -> u32 {
+                (self.vftable.get_disk_sector_size_2)(self)
+            }
+
+            pub fn query_async_status(
+                &mut self,
+                bytes_remaining: &mut usize,
+                bytes_transferred: Option<&mut usize>,
+            ) -> bool {
+                (self.vftable.query_async_status)(self, bytes_remaining, bytes_transferred)
+            }
+
+            pub fn read_bytes_async(&mut self, output: *mut u8, length: usize) -> bool {
+                (self.vftable.read_bytes_async)(self, output, length)
+            }
+
+            pub fn seek(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+                (self.vftable.seek)(self, offset, mode)
+            }
+
+            pub fn current_position(&self) -> usize {
+                (self.vftable.current_position)(self)
+            }
+
+            pub fn reached_end(&self) -> bool {
+                (self.vftable.reached_end)(self)
+            }
+        }
+
+        impl DLInputStream for $stream {
+            fn read_into(&mut self, output: *mut u8, length: usize) -> i32 {
+                self.read_bytes(output, length)
+            }
+
+            fn status(&self) -> DLIOResult {
+                self.get_status()
+            }
+
+            fn bytes_left(&self) -> usize {
+                self.get_bytes_left()
+            }
+        }
+
+        impl DLSeekableInputStream for $stream {
+            fn seek_to(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+                self.seek(offset, mode)
+            }
+
+            fn position(&self) -> usize {
+                self.current_position()
+            }
+        }
+    )*};
+}
+
+macro_rules! forward_seekable_output_stream {
+    ($($stream:ty),*) => {$(
+        impl $stream {
+            pub fn set_last_error(&mut self, status: DLIOResult) {
+                (self.vftable.set_last_error)(self, status)
+            }
+
+            pub fn destructor(&mut self, param_2: u32) {
+                (self.vftable.destructor)(self, param_2)
+            }
+
+            pub fn get_last_error(&self) -> DLIOResult {
+                (self.vftable.get_last_error)(self)
+            }
+
+            pub fn write(&mut self, input: *const u8, length: usize) -> usize {
+                (self.vftable.write)(self, input, length)
+            }
+
+            pub fn get_write_size(&self) -> usize {
+                (self.vftable.get_write_size)(self)
+            }
+
+            pub fn close(&mut self) {
+                (self.vftable.close)(self)
+            }
+
+            pub fn flush(&mut self) {
+                (self.vftable.flush)(self)
+            }
+
+            pub fn is_open(&self) -> bool {
+                (self.vftable.is_open)(self)
+            }
+
+            pub fn get_async_block_size(&self) -> u32 {
+                (self.vftable.get_async_block_size)(self)
+            }
+
+            pub fn get_async_buffer_alignment_size(&self) -> u32 {
+                (self.vftable.get_async_buffer_alignment_size)(self)
+            }
+
+            pub fn query_async_status(
+                &mut self,
+                bytes_remaining: &mut usize,
+                bytes_transferred: Option<&mut usize>,
+            ) -> bool {
+                (self.vftable.query_async_status)(self, bytes_remaining, bytes_transferred)
+            }
+
+            /// # Safety
+            ///
+            /// The caller must ensure that the input is valid and the length is correct.
+            pub unsafe fn start_async_write(&mut self, input: *const u8, length: usize) -> bool {
+                unsafe { (self.vftable.start_async_write)(self, input, length) }
+            }
+
+            pub fn seek(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+                (self.vftable.seek)(self, offset, mode)
+            }
+
+            pub fn set_auto_flush(&mut self, auto_flush: bool) {
+                (self.vftable.set_auto_flush)(self, auto_flush)
+            }
+
+            pub fn pad_to_align(&mut self, alignment: usize, padding_byte: u8) -> bool {
+                (self.vftable.pad_to_align)(self, alignment, padding_byte)
+            }
+        }
+
+        impl DLOutputStream for $stream {
+            fn write_from(&mut self, input: *const u8, length: usize) -> usize {
+                self.write(input, length)
+            }
+
+            fn last_error(&self) -> DLIOResult {
+                self.get_last_error()
+            }
+
+            fn flush_stream(&mut self) {
+                self.flush()
+            }
+        }
+
+        impl DLSeekableOutputStream for $stream {
+            fn seek_to(&mut self, offset: usize, mode: DLFileSeekDirection) -> bool {
+                self.seek(offset, mode)
+            }
+
+            fn pad_to(&mut self, alignment: usize, padding_byte: u8) -> bool {
+                self.pad_to_align(alignment, padding_byte)
+            }
+        }
+    )*};
+}
+
+forward_seekable_input_stream!(
+    DLFileInputStream,
+    DLMemoryInputStream,
+    PseudoAsyncInputStream
+);
+forward_seekable_output_stream!(DLFileOutputStream, DLMemoryOutputStream);
@@ -0,0 +1,211 @@
+use std::{collections::HashMap, ptr::NonNull};
+
+use thiserror::Error;
+
+use super::{DLFileDeviceBase, DLFileDeviceManager};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VirtualPathError {
+    #[error("Virtual root {0} expands to itself")]
+    Cycle(String),
+}
+
+/// Path with every virtual root expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPath {
+    /// Normalized path, see [`normalize_path`].
+    pub path: String,
+    /// Root that is left after expansion, like `data0` for archive paths. `None` for paths on
+    /// disk, which are served by the MSVC file device.
+    pub root: Option<String>,
+}
+
+impl ResolvedPath {
+    pub fn is_disk(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// The path without its root, which is what archive entries are looked up by. See
+    /// [`crate::formats::bhd5::path_hash`].
+    pub fn relative_path(&self) -> &str {
+        match &self.root {
+            Some(root) => &self.path[root.len() + 1..],
+            None => &self.path,
+        }
+    }
+}
+
+/// Lowercases a path, uses forward slashes as separators and collapses repeated separators.
+/// The game compares paths case-insensitively.
+pub fn normalize_path(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len());
+    for c in path.chars().flat_map(char::to_lowercase) {
+        let c = if c == '\\' { '/' } else { c };
+        if c == '/' && normalized.ends_with('/') {
+            continue;
+        }
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// Splits `root:rest` into the root name and the rest. Drive letters aren't roots.
+pub(crate) fn split_root(path: &str) -> Option<(&str, &str)> {
+    let (root, rest) = path.split_once(':')?;
+    match root.len() == 1 && root.chars().all(|c| c.is_ascii_alphabetic()) {
+        true => None,
+        false => Some((root, rest)),
+    }
+}
+
+/// Virtual roots like `data0:` or `regulation:` and the paths they stand for.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualRoots {
+    roots: HashMap<String, String>,
+}
+
+impl VirtualRoots {
+    /// Adds a root, which may be given with or without its trailing `:`. The target may itself
+    /// start with a virtual root.
+    pub fn insert(&mut self, root: &str, target: &str) {
+        let root = normalize_path(root);
+        let root = root.trim_end_matches([':', '/']);
+        self.roots.insert(root.to_string(), normalize_path(target));
+    }
+
+    /// Normalizes the path and expands its virtual roots until it starts with a root that isn't
+    /// mapped any further.
+    pub fn resolve(&self, path: &str) -> Result<ResolvedPath, VirtualPathError> {
+        let mut path = normalize_path(path);
+        let mut expanded = vec![];
+
+        while let Some((root, rest)) = split_root(&path) {
+            let Some(target) = self.roots.get(root) else {
+                let root = root.to_string();
+                return Ok(ResolvedPath {
+                    path,
+                    root: Some(root),
+                });
+            };
+
+            if expanded.iter().any(|r| r == root) {
+                return Err(VirtualPathError::Cycle(root.to_string()));
+            }
+            expanded.push(root.to_string());
+
+            path = match (target.ends_with('/'), rest.strip_prefix('/')) {
+                (true, Some(rest)) => format!("{target}{rest}"),
+                (false, None) if !rest.is_empty() && !target.ends_with(':') => {
+                    format!("{target}/{rest}")
+                }
+                _ => format!("{target}{rest}"),
+            };
+        }
+
+        Ok(ResolvedPath { path, root: None })
+    }
+}
+
+impl DLFileDeviceManager {
+    /// Copies the manager's virtual roots.
+    pub fn virtual_root_map(&self) -> VirtualRoots {
+        let mut roots = VirtualRoots::default();
+        for [root, target] in self.virtual_roots.items() {
+            roots.insert(&root.to_string(), &target.to_string());
+        }
+        roots
+    }
+
+    pub fn resolve_path(&self, path: &str) -> Result<ResolvedPath, VirtualPathError> {
+        self.virtual_root_map().resolve(path)
+    }
+
+    /// Device that would serve the path: the binder mounted under the path's root or the MSVC
+    /// file device for paths on disk. `None` if the root belongs to a device the manager doesn't
+    /// keep a name for, like the archive devices.
+    pub fn device_for_path(
+        &self,
+        path: &str,
+    ) -> Result<Option<NonNull<DLFileDeviceBase>>, VirtualPathError> {
+        let resolved = self.resolve_path(path)?;
+        let Some(root) = resolved.root else {
+            return Ok(NonNull::new(self.msvc_file_device.as_ptr()));
+        };
+
+        Ok(self
+            .bnd4_files
+            .items()
+            .iter()
+            .chain(self.bnd3_files.items())
+            .find(|entry| {
+                normalize_path(&entry.name.to_string()).trim_end_matches([':', '/']) == root
+            })
+            .map(|entry| entry.device))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize_path, ResolvedPath, VirtualPathError, VirtualRoots};
+
+    fn roots() -> VirtualRoots {
+        let mut roots = VirtualRoots::default();
+        roots.insert("regulation:", "gamedata:");
+        roots.insert("gamedata", "data0:/");
+        roots.insert("map:", "data0:/map");
+        roots.insert("savedata:", r"C:\Users\Tarnished\AppData\Roaming\EldenRing");
+        roots
+    }
+
+    #[test]
+    fn normalizes() {
+        assert_eq!(
+            normalize_path(r"DATA0:\\Map\m60//A.BIN"),
+            "data0:/map/m60/a.bin"
+        );
+    }
+
+    #[test]
+    fn expands_roots() {
+        let roots = roots();
+        assert_eq!(
+            roots.resolve("Regulation:/regulation.bin"),
+            Ok(ResolvedPath {
+                path: "data0:/regulation.bin".to_string(),
+                root: Some("data0".to_string()),
+            })
+        );
+        assert_eq!(
+            roots
+                .resolve("regulation:/regulation.bin")
+                .unwrap()
+                .relative_path(),
+            "/regulation.bin"
+        );
+        assert_eq!(
+            roots.resolve(r"map:\m60\m60_44_36_00").unwrap().path,
+            "data0:/map/m60/m60_44_36_00"
+        );
+
+        let save = roots.resolve("savedata:/ER0000.sl2").unwrap();
+        assert!(save.is_disk());
+        assert_eq!(
+            save.path,
+            "c:/users/tarnished/appdata/roaming/eldenring/er0000.sl2"
+        );
+
+        let unknown = roots.resolve("sound:/bgm.fsb").unwrap();
+        assert_eq!(unknown.root.as_deref(), Some("sound"));
+        assert!(roots.resolve("mod/file.txt").unwrap().is_disk());
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let mut roots = roots();
+        roots.insert("data0:", "regulation:/data0");
+        assert_eq!(
+            roots.resolve("map:/m60"),
+            Err(VirtualPathError::Cycle("data0".to_string()))
+        );
+    }
+}
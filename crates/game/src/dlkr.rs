@@ -1,7 +1,9 @@
 mod allocator;
 mod mutex;
+mod rust_allocator;
 mod signal;
 
 pub use allocator::*;
 pub use mutex::*;
+pub use rust_allocator::*;
 pub use signal::*;
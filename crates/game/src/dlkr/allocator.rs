@@ -65,118 +65,121 @@ pub trait DLAllocatorVmt {
     fn get_memory_block_for_allocation(&mut self, allocation: *const u8) -> *const u8;
 }
 
+#[repr(C)]
 pub struct DLAllocatorBase {
     pub vftable: VPtr<dyn DLAllocatorVmt, Self>,
 }
 
-impl DLAllocatorVmt for DLAllocatorBase {
-    extern "C" fn destructor(&mut self, param_2: bool) {
-        todo!()
+/// Calls go through the vftable of the allocator behind the base. The base deliberately has no
+/// vftable of its own, one that forwards to itself would recurse forever.
+impl DLAllocatorBase {
+    pub fn destructor(&mut self, param_2: bool) {
+        (self.vftable.destructor)(self, param_2)
     }
 
-    extern "C" fn allocator_id(&self) -> u32 {
-        todo!()
+    pub fn allocator_id(&self) -> u32 {
+        (self.vftable.allocator_id)(self)
     }
 
-    extern "C" fn unk10(&self) {
-        todo!()
+    pub fn unk10(&self) {
+        (self.vftable.unk10)(self)
     }
 
-    extern "C" fn heap_flags(&self) -> &u64 {
-        todo!()
+    pub fn heap_flags(&self) -> &u64 {
+        (self.vftable.heap_flags)(self)
     }
 
-    extern "C" fn heap_capacity(&self) -> usize {
-        todo!()
+    pub fn heap_capacity(&self) -> usize {
+        (self.vftable.heap_capacity)(self)
     }
 
-    extern "C" fn heap_size(&self) -> usize {
-        todo!()
+    pub fn heap_size(&self) -> usize {
+        (self.vftable.heap_size)(self)
     }
 
-    extern "C" fn backing_heap_capacity(&self) -> usize {
-        todo!()
+    pub fn backing_heap_capacity(&self) -> usize {
+        (self.vftable.backing_heap_capacity)(self)
     }
 
-    extern "C" fn heap_allocation_count(&self) -> usize {
-        todo!()
+    pub fn heap_allocation_count(&self) -> usize {
+        (self.vftable.heap_allocation_count)(self)
     }
 
-    extern "C" fn allocation_size(&self, allocation: *const u8) -> usize {
-        todo!()
+    pub fn allocation_size(&self, allocation: *const u8) -> usize {
+        (self.vftable.allocation_size)(self, allocation)
     }
 
-    extern "C" fn allocate(&mut self, size: usize) -> *const u8 {
-        todo!()
+    pub fn allocate(&mut self, size: usize) -> *const u8 {
+        (self.vftable.allocate)(self, size)
     }
 
-    extern "C" fn allocate_aligned(&mut self, size: usize, alignment: usize) -> *const u8 {
+    pub fn allocate_aligned(&mut self, size: usize, alignment: usize) -> *const u8 {
         (self.vftable.allocate_aligned)(self, size, alignment)
     }
 
-    extern "C" fn reallocate(&mut self, allocation: *const u8, size: usize) -> *const u8 {
-        todo!()
+    pub fn reallocate(&mut self, allocation: *const u8, size: usize) -> *const u8 {
+        (self.vftable.reallocate)(self, allocation, size)
     }
 
-    extern "C" fn reallocate_aligned(
+    pub fn reallocate_aligned(
         &mut self,
         allocation: *const u8,
         size: usize,
         alignment: usize,
     ) -> *const u8 {
-        todo!()
+        (self.vftable.reallocate_aligned)(self, allocation, size, alignment)
     }
 
-    extern "C" fn deallocate(&mut self, allocation: *const u8) {
-        todo!()
+    pub fn deallocate(&mut self, allocation: *const u8) {
+        (self.vftable.deallocate)(self, allocation)
     }
 
-    extern "C" fn allocate_second(&mut self, size: usize) -> *const u8 {
-        todo!()
+    pub fn allocate_second(&mut self, size: usize) -> *const u8 {
+        (self.vftable.allocate_second)(self, size)
     }
 
-    extern "C" fn allocate_aligned_second(&mut self, size: usize, alignment: usize) -> *const u8 {
-        todo!()
+    pub fn allocate_aligned_second(&mut self, size: usize, alignment: usize) -> *const u8 {
+        (self.vftable.allocate_aligned_second)(self, size, alignment)
     }
 
-    extern "C" fn reallocate_second(&mut self, allocation: *const u8, size: usize) -> *const u8 {
-        todo!()
+    pub fn reallocate_second(&mut self, allocation: *const u8, size: usize) -> *const u8 {
+        (self.vftable.reallocate_second)(self, allocation, size)
     }
 
-    extern "C" fn reallocate_aligned_second(
+    pub fn reallocate_aligned_second(
         &mut self,
         allocation: *const u8,
         size: usize,
         alignment: usize,
     ) -> *const u8 {
-        todo!()
+        (self.vftable.reallocate_aligned_second)(self, allocation, size, alignment)
     }
 
-    extern "C" fn deallocate_second(&mut self, allocation: *const u8) {
-        todo!()
+    pub fn deallocate_second(&mut self, allocation: *const u8) {
+        (self.vftable.deallocate_second)(self, allocation)
     }
 
-    extern "C" fn unka0(&self) -> bool {
-        todo!()
+    pub fn unka0(&self) -> bool {
+        (self.vftable.unka0)(self)
     }
 
-    extern "C" fn allocation_belongs_to_first_allocator(&mut self, allocation: *const u8) -> bool {
-        todo!()
+    pub fn allocation_belongs_to_first_allocator(&mut self, allocation: *const u8) -> bool {
+        (self.vftable.allocation_belongs_to_first_allocator)(self, allocation)
     }
 
-    extern "C" fn allocation_belongs_to_second_allocator(&mut self, allocation: *const u8) -> bool {
-        todo!()
+    pub fn allocation_belongs_to_second_allocator(&mut self, allocation: *const u8) -> bool {
+        (self.vftable.allocation_belongs_to_second_allocator)(self, allocation)
     }
 
-    extern "C" fn lock(&mut self) {
-        todo!()
+    pub fn lock(&mut self) {
+        (self.vftable.lock)(self)
     }
 
-    extern "C" fn unlock(&mut self) {
-        todo!()
+    pub fn unlock(&mut self) {
+        (self.vftable.unlock)(self)
     }
 
-    extern "C" fn get_memory_block_for_allocation(&mut self, allocation: *const u8) -> *const u8 {
-        todo!()
+    pub fn get_memory_block_for_allocation(&mut self, allocation: *const u8) -> *const u8 {
+        (self.vftable.get_memory_block_for_allocation)(self, allocation)
     }
 }
@@ -1,9 +1,15 @@
 use std::ffi;
 
 use vtable_rs::VPtr;
+use windows::Win32::System::Threading::CRITICAL_SECTION;
+#[cfg(windows)]
 use windows::Win32::System::Threading::{
     DeleteCriticalSection, EnterCriticalSection, InitializeCriticalSection, LeaveCriticalSection,
-    CRITICAL_SECTION,
+};
+
+#[cfg(not(windows))]
+use critical_section::{
+    DeleteCriticalSection, EnterCriticalSection, InitializeCriticalSection, LeaveCriticalSection,
 };
 
 #[vtable_rs::vtable]
@@ -38,6 +44,18 @@ impl Drop for DLPlainLightMutex {
 }
 
 impl DLPlainLightMutex {
+    /// Initializes a mutex at its final address. Windows keeps track of critical sections by
+    /// address, so a mutex embedded in a heap object shouldn't be initialized and then moved.
+    ///
+    /// # Safety
+    ///
+    /// `mutex` must be valid for writes and must not be moved afterwards.
+    pub unsafe fn initialize_at(mutex: *mut Self) {
+        (&raw mut (*mutex).vftable).write(Default::default());
+        (&raw mut (*mutex).critical_section).write(Default::default());
+        InitializeCriticalSection(&raw mut (*mutex).critical_section);
+    }
+
     pub fn lock(&mut self) {
         unsafe { EnterCriticalSection(&mut self.critical_section) }
     }
@@ -52,3 +70,57 @@ impl DLPlainLightMutexVmt for DLPlainLightMutex {
         unimplemented!();
     }
 }
+
+/// Stand-in for the critical section API on other platforms, so the structures that embed a mutex
+/// and the offline tooling built on them can be tested off Windows. Keeps the recursive locking of
+/// a critical section by spinning on its lock count.
+#[cfg(not(windows))]
+#[allow(non_snake_case)]
+mod critical_section {
+    use std::{
+        sync::atomic::{AtomicI32, AtomicIsize, Ordering},
+        thread,
+    };
+
+    use windows::Win32::System::Threading::CRITICAL_SECTION;
+
+    /// Non-zero and unique among running threads.
+    fn thread_id() -> isize {
+        thread_local!(static ID: u8 = const { 0 });
+        ID.with(|id| id as *const u8 as isize)
+    }
+
+    unsafe fn owner<'a>(section: *mut CRITICAL_SECTION) -> &'a AtomicIsize {
+        AtomicIsize::from_ptr(&raw mut (*section).OwningThread.0)
+    }
+
+    pub unsafe fn InitializeCriticalSection(section: *mut CRITICAL_SECTION) {
+        section.write(Default::default());
+    }
+
+    pub unsafe fn DeleteCriticalSection(_section: *mut CRITICAL_SECTION) {}
+
+    pub unsafe fn EnterCriticalSection(section: *mut CRITICAL_SECTION) {
+        let id = thread_id();
+        if owner(section).load(Ordering::Relaxed) != id {
+            let lock = AtomicI32::from_ptr(&raw mut (*section).LockCount);
+            while lock
+                .compare_exchange_weak(0, 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                thread::yield_now();
+            }
+            owner(section).store(id, Ordering::Relaxed);
+        }
+
+        (*section).RecursionCount += 1;
+    }
+
+    pub unsafe fn LeaveCriticalSection(section: *mut CRITICAL_SECTION) {
+        (*section).RecursionCount -= 1;
+        if (*section).RecursionCount == 0 {
+            owner(section).store(0, Ordering::Relaxed);
+            AtomicI32::from_ptr(&raw mut (*section).LockCount).store(0, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    alloc::Layout,
+    collections::HashMap,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use vtable_rs::VPtr;
+
+use super::{DLAllocatorBase, DLAllocatorVmt, DLPlainLightMutex};
+
+/// Alignment used by the unaligned allocation functions, matching the game's heaps.
+const DEFAULT_ALIGNMENT: usize = 0x10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Allocations that haven't been freed yet.
+    pub live_allocations: usize,
+    pub bytes_in_use: usize,
+    pub peak_bytes_in_use: usize,
+    /// Allocations made over the allocator's lifetime, including reallocations.
+    pub total_allocations: usize,
+    /// Allocations that were refused because they would exceed the capacity.
+    pub failed_allocations: usize,
+}
+
+/// Allocator the game can use, backed by Rust's global allocator. Every allocation is tracked so
+/// sizes and ownership can be queried, and anything still allocated is freed when the allocator
+/// is dropped.
+#[repr(C)]
+pub struct RustAllocator {
+    pub vftable: VPtr<dyn DLAllocatorVmt, Self>,
+    allocator_id: u32,
+    heap_flags: u64,
+    capacity: usize,
+    allocations: Mutex<HashMap<usize, Layout>>,
+    stats: Mutex<AllocatorStats>,
+    /// Lock taken by the game through [`DLAllocatorVmt::lock`].
+    mutex: DLPlainLightMutex,
+    lock_depth: AtomicUsize,
+}
+
+impl RustAllocator {
+    pub fn new(allocator_id: u32) -> Box<Self> {
+        Self::with_capacity(allocator_id, usize::MAX)
+    }
+
+    /// Creates an allocator that refuses allocations once `capacity` bytes are in use.
+    ///
+    /// The allocator is boxed because containers keep pointers to it and its mutex must stay at
+    /// the address it was initialized at. Don't move it out of the box.
+    pub fn with_capacity(allocator_id: u32, capacity: usize) -> Box<Self> {
+        let mut allocator = Box::<Self>::new_uninit();
+        let ptr = allocator.as_mut_ptr();
+        unsafe {
+            (&raw mut (*ptr).vftable).write(Default::default());
+            (&raw mut (*ptr).allocator_id).write(allocator_id);
+            (&raw mut (*ptr).heap_flags).write(0);
+            (&raw mut (*ptr).capacity).write(capacity);
+            (&raw mut (*ptr).allocations).write(Default::default());
+            (&raw mut (*ptr).stats).write(Default::default());
+            DLPlainLightMutex::initialize_at(&raw mut (*ptr).mutex);
+            (&raw mut (*ptr).lock_depth).write(AtomicUsize::new(0));
+            allocator.assume_init()
+        }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// The allocator as the game sees it, for passing to containers and engine calls.
+    pub fn as_base(&mut self) -> &mut DLAllocatorBase {
+        unsafe { &mut *(self as *mut Self as *mut DLAllocatorBase) }
+    }
+
+    pub fn owns(&self, allocation: *const u8) -> bool {
+        self.allocations
+            .lock()
+            .unwrap()
+            .contains_key(&(allocation as usize))
+    }
+
+    fn allocate_layout(&self, size: usize, alignment: usize) -> *const u8 {
+        let Ok(layout) = Layout::from_size_align(size.max(1), alignment.max(1)) else {
+            return std::ptr::null();
+        };
+
+        let mut stats = self.stats.lock().unwrap();
+        if stats.bytes_in_use.saturating_add(layout.size()) > self.capacity {
+            stats.failed_allocations += 1;
+            return std::ptr::null();
+        }
+
+        let allocation = unsafe { std::alloc::alloc(layout) };
+        if allocation.is_null() {
+            stats.failed_allocations += 1;
+            return std::ptr::null();
+        }
+
+        stats.live_allocations += 1;
+        stats.total_allocations += 1;
+        stats.bytes_in_use += layout.size();
+        stats.peak_bytes_in_use = stats.peak_bytes_in_use.max(stats.bytes_in_use);
+        self.allocations
+            .lock()
+            .unwrap()
+            .insert(allocation as usize, layout);
+        allocation
+    }
+
+    fn reallocate_layout(&self, allocation: *const u8, size: usize, alignment: usize) -> *const u8 {
+        if allocation.is_null() {
+            return self.allocate_layout(size, alignment);
+        }
+        let Some(old) = self.layout(allocation) else {
+            tracing::warn!("Reallocating {allocation:x?} which wasn't allocated here");
+            return std::ptr::null();
+        };
+
+        let reallocation = self.allocate_layout(size, alignment);
+        if !reallocation.is_null() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    allocation,
+                    reallocation as *mut u8,
+                    old.size().min(size),
+                )
+            };
+            self.free(allocation);
+        }
+        reallocation
+    }
+
+    fn free(&self, allocation: *const u8) {
+        if allocation.is_null() {
+            return;
+        }
+        let Some(layout) = self
+            .allocations
+            .lock()
+            .unwrap()
+            .remove(&(allocation as usize))
+        else {
+            tracing::warn!("Freeing {allocation:x?} which wasn't allocated here");
+            return;
+        };
+
+        unsafe { std::alloc::dealloc(allocation as *mut u8, layout) };
+        let mut stats = self.stats.lock().unwrap();
+        stats.live_allocations -= 1;
+        stats.bytes_in_use -= layout.size();
+    }
+
+    fn layout(&self, allocation: *const u8) -> Option<Layout> {
+        self.allocations
+            .lock()
+            .unwrap()
+            .get(&(allocation as usize))
+            .copied()
+    }
+}
+
+impl Drop for RustAllocator {
+    fn drop(&mut self) {
+        let allocations = std::mem::take(self.allocations.get_mut().unwrap());
+        if !allocations.is_empty() {
+            tracing::debug!(
+                "Freeing {} allocations left in allocator {}",
+                allocations.len(),
+                self.allocator_id
+            );
+        }
+        for (allocation, layout) in allocations {
+            unsafe { std::alloc::dealloc(allocation as *mut u8, layout) };
+        }
+    }
+}
+
+impl DLAllocatorVmt for RustAllocator {
+    extern "C" fn destructor(&mut self, param_2: bool) {
+        tracing::debug!("RustAllocator::destructor({param_2})");
+    }
+
+    extern "C" fn allocator_id(&self) -> u32 {
+        self.allocator_id
+    }
+
+    extern "C" fn unk10(&self) {}
+
+    extern "C" fn heap_flags(&self) -> &u64 {
+        &self.heap_flags
+    }
+
+    extern "C" fn heap_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    extern "C" fn heap_size(&self) -> usize {
+        self.stats().bytes_in_use
+    }
+
+    extern "C" fn backing_heap_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    extern "C" fn heap_allocation_count(&self) -> usize {
+        self.stats().live_allocations
+    }
+
+    extern "C" fn allocation_size(&self, allocation: *const u8) -> usize {
+        self.layout(allocation).map_or(0, |layout| layout.size())
+    }
+
+    extern "C" fn allocate(&mut self, size: usize) -> *const u8 {
+        self.allocate_layout(size, DEFAULT_ALIGNMENT)
+    }
+
+    extern "C" fn allocate_aligned(&mut self, size: usize, alignment: usize) -> *const u8 {
+        self.allocate_layout(size, alignment)
+    }
+
+    extern "C" fn reallocate(&mut self, allocation: *const u8, size: usize) -> *const u8 {
+        self.reallocate_layout(allocation, size, DEFAULT_ALIGNMENT)
+    }
+
+    extern "C" fn reallocate_aligned(
+        &mut self,
+        allocation: *const u8,
+        size: usize,
+        alignment: usize,
+    ) -> *const u8 {
+        self.reallocate_layout(allocation, size, alignment)
+    }
+
+    extern "C" fn deallocate(&mut self, allocation: *const u8) {
+        self.free(allocation)
+    }
+
+    extern "C" fn allocate_second(&mut self, size: usize) -> *const u8 {
+        self.allocate(size)
+    }
+
+    extern "C" fn allocate_aligned_second(&mut self, size: usize, alignment: usize) -> *const u8 {
+        self.allocate_aligned(size, alignment)
+    }
+
+    extern "C" fn reallocate_second(&mut self, allocation: *const u8, size: usize) -> *const u8 {
+        self.reallocate(allocation, size)
+    }
+
+    extern "C" fn reallocate_aligned_second(
+        &mut self,
+        allocation: *const u8,
+        size: usize,
+        alignment: usize,
+    ) -> *const u8 {
+        self.reallocate_aligned(allocation, size, alignment)
+    }
+
+    extern "C" fn deallocate_second(&mut self, allocation: *const u8) {
+        self.deallocate(allocation)
+    }
+
+    extern "C" fn unka0(&self) -> bool {
+        false
+    }
+
+    extern "C" fn allocation_belongs_to_first_allocator(&mut self, allocation: *const u8) -> bool {
+        self.owns(allocation)
+    }
+
+    /// There's only one heap, see [`DLAllocatorVmt::allocation_belongs_to_first_allocator`].
+    extern "C" fn allocation_belongs_to_second_allocator(&mut self, allocation: *const u8) -> bool {
+        false
+    }
+
+    extern "C" fn lock(&mut self) {
+        self.mutex.lock();
+        self.lock_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    extern "C" fn unlock(&mut self) {
+        if self.lock_depth.load(Ordering::Relaxed) == 0 {
+            tracing::warn!("RustAllocator::unlock() without lock()");
+            return;
+        }
+        self.lock_depth.fetch_sub(1, Ordering::Relaxed);
+        self.mutex.unlock();
+    }
+
+    extern "C" fn get_memory_block_for_allocation(&mut self, allocation: *const u8) -> *const u8 {
+        match self.owns(allocation) {
+            true => allocation,
+            false => std::ptr::null(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AllocatorStats, RustAllocator};
+    use crate::{dlkr::DLAllocatorVmt, dltx::DLString};
+
+    #[test]
+    fn tracks_allocations() {
+        let mut allocator = RustAllocator::with_capacity(7, 0x100);
+        let base = allocator.as_base();
+
+        let a = base.allocate_aligned(0x20, 0x40);
+        assert_eq!(a as usize % 0x40, 0);
+        let b = base.allocate(0x10);
+        assert_eq!(base.allocation_size(b), 0x10);
+        assert!(base.allocation_belongs_to_first_allocator(a));
+        assert_eq!(base.allocator_id(), 7);
+        assert!(base.allocate(0x100).is_null());
+
+        unsafe { (b as *mut u8).write_bytes(0xAB, 0x10) };
+        let b = base.reallocate(b, 0x40);
+        assert_eq!(unsafe { *b.add(0xF) }, 0xAB);
+        assert_eq!(base.heap_size(), 0x60);
+
+        base.deallocate(a);
+        assert!(!base.allocation_belongs_to_first_allocator(a));
+        assert!(base.get_memory_block_for_allocation(a).is_null());
+        base.lock();
+        base.unlock();
+
+        assert_eq!(
+            allocator.stats(),
+            AllocatorStats {
+                live_allocations: 1,
+                bytes_in_use: 0x40,
+                peak_bytes_in_use: 0x70,
+                total_allocations: 3,
+                failed_allocations: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn backs_strings() {
+        let mut allocator = RustAllocator::new(0);
+        let mut string = DLString::from_str(allocator.as_base(), "data0:").unwrap();
+        string.push_str("/regulation.bin").unwrap();
+        assert_eq!(string.to_string(), "data0:/regulation.bin");
+        assert!(string.capacity() >= string.len());
+        assert_eq!(allocator.stats().live_allocations, 1);
+
+        string.assign("short").unwrap();
+        assert_eq!(string.to_string(), "short");
+        drop(string);
+        assert_eq!(allocator.stats().live_allocations, 0);
+    }
+}
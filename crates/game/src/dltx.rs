@@ -3,34 +3,237 @@ use std::fmt::Display;
 use std::marker;
 use std::marker::PhantomData;
 use std::mem;
+use std::ptr::NonNull;
 use std::slice;
 
+use thiserror::Error;
+
+use crate::dlkr::DLAllocatorBase;
+
+/// Character encodings as numbered by DLTX, used as the `U` parameter of [`DLCodedString`] and
+/// [`DLInplaceStr`].
+pub mod encoding {
+    pub const UTF8: usize = 0;
+    pub const UTF16: usize = 1;
+    pub const ISO_8859: usize = 2;
+    pub const SJIS: usize = 3;
+    pub const EUC_JP: usize = 4;
+    pub const UTF32: usize = 5;
+
+    /// Size of one code unit in bytes.
+    pub const fn unit_size(encoding: usize) -> usize {
+        match encoding {
+            UTF16 => 2,
+            UTF32 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Encodes a string without a terminator. Characters that can't be represented in
+    /// Shift-JIS, EUC-JP or ISO-8859 are replaced with `?`.
+    pub fn encode(encoding: usize, s: &str) -> Vec<u8> {
+        match encoding {
+            UTF16 => s.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            UTF32 => s.chars().flat_map(|c| (c as u32).to_le_bytes()).collect(),
+            ISO_8859 => s.chars().map(|c| u8::try_from(c).unwrap_or(b'?')).collect(),
+            SJIS | EUC_JP => s
+                .chars()
+                .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                .collect(),
+            _ => s.as_bytes().to_vec(),
+        }
+    }
+
+    /// Decodes a string, the multibyte Japanese encodings only have their ASCII subset decoded.
+    pub fn decode(encoding: usize, bytes: &[u8]) -> String {
+        match encoding {
+            UTF16 => {
+                let units = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                String::from_utf16_lossy(&units)
+            }
+            UTF32 => bytes
+                .chunks_exact(4)
+                .map(|c| {
+                    char::from_u32(u32::from_le_bytes(c.try_into().unwrap()))
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                })
+                .collect(),
+            ISO_8859 => bytes.iter().map(|b| *b as char).collect(),
+            SJIS | EUC_JP => bytes
+                .iter()
+                .map(|b| match b.is_ascii() {
+                    true => *b as char,
+                    false => char::REPLACEMENT_CHARACTER,
+                })
+                .collect(),
+            _ => String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DLStringError {
+    #[error("String has no allocator to grow its buffer with")]
+    NoAllocator,
+    #[error("Could not allocate {0} bytes for string")]
+    AllocationFailed(usize),
+}
+
+/// MSVC style string with a small-string optimization: contents up to 16 bytes including the
+/// terminator are stored inline, longer contents live on the heap and `inner` holds the pointer.
+/// `length` and `capacity` count code units, UTF-16 unless the owning type says otherwise.
 #[repr(C)]
-#[derive(Default)]
 pub struct DLBasicString {
     inner: [u8; 0x10],
     pub length: usize,
     pub capacity: usize,
 }
 
+impl Default for DLBasicString {
+    fn default() -> Self {
+        Self {
+            inner: [0; 0x10],
+            length: 0,
+            capacity: Self::INLINE_CAPACITY,
+        }
+    }
+}
+
 impl Display for DLBasicString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let slice_size = self.length * mem::size_of::<u16>();
-        let bytes = if slice_size >= 16 {
-            let ptr = usize::from_le_bytes(self.inner[0..8].try_into().unwrap());
-
-            unsafe { slice::from_raw_parts(ptr as *const u16, self.length).to_vec() }
-        } else {
-            self.inner[0..slice_size]
-                .chunks_exact(2)
-                .map(|c| ((c[1] as u16) << 8) | c[0] as u16)
-                .collect::<Vec<u16>>()
-        };
-
-        write!(f, "{}", String::from_utf16_lossy(bytes.as_slice()))
+        write!(f, "{}", String::from_utf16_lossy(self.as_utf16()))
     }
 }
+
 impl DLBasicString {
+    /// Amount of UTF-16 units that fit in the inline buffer, excluding the terminator.
+    const INLINE_CAPACITY: usize = 7;
+
+    /// Creates a string stored inline, `None` if it's longer than 7 UTF-16 units.
+    pub fn inline(s: &str) -> Option<Self> {
+        let mut string = Self::default();
+        let units = s.encode_utf16().collect::<Vec<_>>();
+        unsafe { string.assign_in_place(&units) }.then_some(string)
+    }
+
+    fn is_heap(&self) -> bool {
+        self.is_heap_with(2)
+    }
+
+    fn inline_capacity_with(unit: usize) -> usize {
+        0x10 / unit - 1
+    }
+
+    fn is_heap_with(&self, unit: usize) -> bool {
+        self.capacity > Self::inline_capacity_with(unit)
+    }
+
+    fn heap_ptr(&self) -> *mut u8 {
+        usize::from_le_bytes(self.inner[0..8].try_into().unwrap()) as *mut u8
+    }
+
+    fn set_heap_ptr(&mut self, ptr: *mut u8) {
+        self.inner[0..8].copy_from_slice(&(ptr as usize).to_le_bytes());
+    }
+
+    fn buffer_with(&mut self, unit: usize) -> *mut u8 {
+        match self.is_heap_with(unit) {
+            true => self.heap_ptr(),
+            false => self.inner.as_mut_ptr(),
+        }
+    }
+
+    /// Contents as bytes for strings made of `unit` sized code units.
+    fn bytes_with(&self, unit: usize) -> &[u8] {
+        let length = self.length * unit;
+        match self.is_heap_with(unit) {
+            true => unsafe { slice::from_raw_parts(self.heap_ptr(), length) },
+            false => &self.inner[..length.min(self.inner.len())],
+        }
+    }
+
+    /// Replaces the contents without allocating, see [`DLBasicString::assign_in_place`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`DLBasicString::assign_in_place`].
+    unsafe fn assign_bytes_in_place(&mut self, unit: usize, bytes: &[u8]) -> bool {
+        let units = bytes.len() / unit;
+        if units > self.capacity.max(Self::inline_capacity_with(unit)) {
+            return false;
+        }
+
+        let buffer = self.buffer_with(unit);
+        unsafe {
+            std::ptr::copy(bytes.as_ptr(), buffer, bytes.len());
+            std::ptr::write_bytes(buffer.add(bytes.len()), 0, unit);
+        }
+        self.length = units;
+        true
+    }
+
+    pub fn as_utf16(&self) -> &[u16] {
+        let bytes = self.bytes_with(2);
+        unsafe { slice::from_raw_parts(bytes.as_ptr() as *const u16, self.length) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Replaces the contents with `chars` without allocating. Returns false and leaves the
+    /// string untouched if `chars` doesn't fit in the current capacity.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the string is initialized and that a heap buffer, if any,
+    /// is valid for `capacity + 1` units.
+    pub(crate) unsafe fn assign_in_place(&mut self, chars: &[u16]) -> bool {
+        let bytes = chars
+            .iter()
+            .flat_map(|c| c.to_le_bytes())
+            .collect::<Vec<_>>();
+        unsafe { self.assign_bytes_in_place(2, &bytes) }
+    }
+
+    /// Replaces the contents with `chars`, moving to a new heap buffer from `allocator` if they
+    /// don't fit in the current capacity. For strings that don't carry their allocator, like the
+    /// results written by file enumerators.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`DLBasicString::assign_in_place`], additionally a heap buffer, if any, must have
+    /// been allocated by `allocator`.
+    pub(crate) unsafe fn assign_with(
+        &mut self,
+        allocator: &mut DLAllocatorBase,
+        chars: &[u16],
+    ) -> Result<(), DLStringError> {
+        if unsafe { self.assign_in_place(chars) } {
+            return Ok(());
+        }
+
+        let size = (chars.len() + 1) * mem::size_of::<u16>();
+        let buffer = allocator.allocate_aligned(size, 8) as *mut u8;
+        if buffer.is_null() {
+            return Err(DLStringError::AllocationFailed(size));
+        }
+        if self.is_heap() {
+            allocator.deallocate(self.heap_ptr());
+        }
+        self.set_heap_ptr(buffer);
+        self.capacity = chars.len();
+        unsafe { self.assign_in_place(chars) };
+        Ok(())
+    }
+
     /// # Safety
     ///
     /// The caller must ensure that the string is actually a DLBasicString and is
@@ -40,10 +243,11 @@ impl DLBasicString {
     }
 }
 
+/// UTF-16 string owning its buffer through a DL allocator.
 #[repr(C)]
 #[derive(Default)]
 pub struct DLString {
-    allocator: usize,
+    allocator: Option<NonNull<DLAllocatorBase>>,
     pub inner: DLBasicString,
     unk28: u32,
     unk2c: u32,
@@ -55,7 +259,132 @@ impl Display for DLString {
     }
 }
 
+impl PartialEq<str> for DLString {
+    fn eq(&self, other: &str) -> bool {
+        self.inner
+            .as_utf16()
+            .iter()
+            .copied()
+            .eq(other.encode_utf16())
+    }
+}
+
+impl PartialEq<&str> for DLString {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl Drop for DLString {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
 impl DLString {
+    /// Creates an empty string that allocates from `allocator` once it outgrows the inline
+    /// buffer. The allocator must outlive the string.
+    pub fn new(allocator: &mut DLAllocatorBase) -> Self {
+        Self {
+            allocator: Some(NonNull::from(allocator)),
+            inner: Default::default(),
+            unk28: 0,
+            unk2c: 0,
+        }
+    }
+
+    pub fn from_str(allocator: &mut DLAllocatorBase, s: &str) -> Result<Self, DLStringError> {
+        let mut string = Self::new(allocator);
+        string.assign(s)?;
+        Ok(string)
+    }
+
+    pub fn allocator(&self) -> Option<NonNull<DLAllocatorBase>> {
+        self.allocator
+    }
+
+    pub fn as_utf16(&self) -> &[u16] {
+        self.inner.as_utf16()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// Makes room for at least `capacity` UTF-16 units, moving the contents to a new heap buffer
+    /// if needed. Grows by half the current capacity at least, like the game does.
+    pub fn reserve(&mut self, capacity: usize) -> Result<(), DLStringError> {
+        if capacity <= self.inner.capacity.max(DLBasicString::INLINE_CAPACITY) {
+            return Ok(());
+        }
+        let mut allocator = self.allocator.ok_or(DLStringError::NoAllocator)?;
+
+        let capacity = capacity.max(self.inner.capacity + self.inner.capacity / 2);
+        let size = (capacity + 1) * mem::size_of::<u16>();
+        let buffer = unsafe { allocator.as_mut() }.allocate_aligned(size, 8) as *mut u8;
+        if buffer.is_null() {
+            return Err(DLStringError::AllocationFailed(size));
+        }
+
+        let current = self.inner.bytes_with(2);
+        unsafe {
+            std::ptr::copy_nonoverlapping(current.as_ptr(), buffer, current.len());
+            std::ptr::write_bytes(buffer.add(current.len()), 0, 2);
+        }
+        self.release_heap();
+        self.inner.set_heap_ptr(buffer);
+        self.inner.capacity = capacity;
+        Ok(())
+    }
+
+    pub fn assign(&mut self, s: &str) -> Result<(), DLStringError> {
+        self.assign_utf16(&s.encode_utf16().collect::<Vec<_>>())
+    }
+
+    pub fn assign_utf16(&mut self, units: &[u16]) -> Result<(), DLStringError> {
+        self.reserve(units.len())?;
+        unsafe { self.inner.assign_in_place(units) };
+        Ok(())
+    }
+
+    pub fn push_str(&mut self, s: &str) -> Result<(), DLStringError> {
+        let mut units = self.as_utf16().to_vec();
+        units.extend(s.encode_utf16());
+        self.assign_utf16(&units)
+    }
+
+    pub fn push(&mut self, c: char) -> Result<(), DLStringError> {
+        self.push_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Empties the string, keeping its buffer.
+    pub fn clear(&mut self) {
+        unsafe { self.inner.assign_in_place(&[]) };
+    }
+
+    /// Frees the heap buffer and leaves the string empty and inline.
+    fn release(&mut self) {
+        self.release_heap();
+        self.inner = DLBasicString::default();
+    }
+
+    fn release_heap(&mut self) {
+        if !self.inner.is_heap() {
+            return;
+        }
+        if let Some(mut allocator) = self.allocator {
+            unsafe { allocator.as_mut() }.deallocate(self.inner.heap_ptr());
+        }
+    }
+
     /// # Safety
     ///
     /// The caller must ensure that the string is actually a DLString and is
@@ -67,20 +396,37 @@ impl DLString {
 
 pub type DLAllocatedString = DLString;
 
+/// String in encoding `U`, see [`encoding`].
 #[repr(C)]
-// Unsure what the const generic U is used for
 pub struct DLCodedString<const U: usize> {
     inner: DLBasicString,
 }
 
 impl<const U: usize> Display for DLCodedString<U> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.inner)
+        write!(f, "{}", encoding::decode(U, self.as_bytes()))
+    }
+}
+
+impl<const U: usize> DLCodedString<U> {
+    const UNIT: usize = encoding::unit_size(U);
+
+    /// Encoded contents without the terminator.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.inner.bytes_with(Self::UNIT)
+    }
+
+    /// Replaces the contents without allocating as the string doesn't know its allocator.
+    /// Returns false and leaves the string untouched if the encoded string doesn't fit.
+    pub fn assign_in_place(&mut self, s: &str) -> bool {
+        let bytes = encoding::encode(U, s);
+        unsafe { self.inner.assign_bytes_in_place(Self::UNIT, &bytes) }
     }
 }
 
+/// String in encoding `U`, see [`encoding`], that keeps its contents in a fixed buffer of `N`
+/// UTF-16 units worth of bytes.
 #[repr(C)]
-// Unsure what the const generic U is used for
 pub struct DLInplaceStr<const U: usize, const N: usize> {
     vftable: usize,
     /// Inner string
@@ -91,6 +437,108 @@ pub struct DLInplaceStr<const U: usize, const N: usize> {
 
 impl<const U: usize, const N: usize> Display for DLInplaceStr<U, N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.inner)
+        write!(f, "{}", encoding::decode(U, self.as_bytes()))
+    }
+}
+
+impl<const U: usize, const N: usize> DLInplaceStr<U, N> {
+    const UNIT: usize = encoding::unit_size(U);
+
+    /// Amount of code units the buffer holds, excluding the terminator.
+    pub const CAPACITY: usize = N * 2 / Self::UNIT - 1;
+
+    /// Encoded contents without the terminator, read from the buffer so the string may be
+    /// moved.
+    pub fn as_bytes(&self) -> &[u8] {
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                self.bytes.as_ptr() as *const u8,
+                mem::size_of_val(&self.bytes),
+            )
+        };
+        &bytes[..(self.inner.length * Self::UNIT).min(bytes.len())]
+    }
+
+    /// Replaces the contents, pointing the inner string at the buffer. Returns false and leaves
+    /// the string untouched if the encoded string doesn't fit.
+    pub fn assign(&mut self, s: &str) -> bool {
+        let bytes = encoding::encode(U, s);
+        if bytes.len() / Self::UNIT > Self::CAPACITY {
+            return false;
+        }
+
+        let buffer = self.bytes.as_mut_ptr() as *mut u8;
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer, bytes.len());
+            std::ptr::write_bytes(buffer.add(bytes.len()), 0, Self::UNIT);
+        }
+        self.inner.set_heap_ptr(buffer);
+        self.inner.capacity = Self::CAPACITY;
+        self.inner.length = bytes.len() / Self::UNIT;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.assign("");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encoding, DLBasicString, DLCodedString, DLInplaceStr, DLString, DLStringError};
+
+    #[test]
+    fn inline_strings() {
+        let string = DLBasicString::inline("regul").unwrap();
+        assert_eq!(string.to_string(), "regul");
+        assert_eq!(string.capacity, 7);
+        assert!(DLBasicString::inline("regulation").is_none());
+
+        let mut string = DLString::default();
+        string.assign("data0:").unwrap();
+        string.push('/').unwrap();
+        assert!(string == "data0:/");
+        assert_eq!(string.push_str("a"), Err(DLStringError::NoAllocator));
+        assert_eq!(string.to_string(), "data0:/");
+        string.clear();
+        assert!(string.is_empty());
+    }
+
+    #[test]
+    fn encodings() {
+        for (encoding, s) in [
+            (encoding::UTF8, "héllo"),
+            (encoding::UTF16, "héllo"),
+            (encoding::UTF32, "h€"),
+            (encoding::ISO_8859, "héllo"),
+            (encoding::SJIS, "hello"),
+        ] {
+            let bytes = encoding::encode(encoding, s);
+            assert_eq!(encoding::decode(encoding, &bytes), s);
+        }
+        assert_eq!(encoding::encode(encoding::SJIS, "あa"), b"?a");
+
+        let mut string = DLCodedString::<{ encoding::UTF8 }> {
+            inner: Default::default(),
+        };
+        assert!(string.assign_in_place("fifteen bytes!!"));
+        assert_eq!(string.to_string(), "fifteen bytes!!");
+        assert!(!string.assign_in_place("sixteen bytes!!!"));
+    }
+
+    #[test]
+    fn inplace_strings() {
+        let mut string = DLInplaceStr::<{ encoding::UTF16 }, 16> {
+            vftable: 0,
+            inner: Default::default(),
+            bytes: [0; 16],
+        };
+        assert_eq!(DLInplaceStr::<{ encoding::UTF16 }, 16>::CAPACITY, 15);
+        assert!(string.assign("Tarnished"));
+        assert_eq!(string.to_string(), "Tarnished");
+        assert_eq!(string.inner.to_string(), "Tarnished");
+        assert!(!string.assign("Tarnished of Limgrave"));
+        string.clear();
+        assert_eq!(string.to_string(), "");
     }
 }
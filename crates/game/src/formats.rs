@@ -0,0 +1,354 @@
+//! Offline readers and writers for the file formats the game loads from its archives.
+
+pub mod bhd5;
+pub mod bnd4;
+pub mod emevd;
+pub mod esd;
+pub mod fxr;
+pub mod gparam;
+pub mod save;
+pub mod tae;
+
+use std::{cell::Cell, fmt::Write};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("Unexpected end of data reading {length} bytes at {offset:#x}")]
+    UnexpectedEof { offset: usize, length: usize },
+    #[error("Invalid magic {found:?}, expected {expected:?}")]
+    InvalidMagic {
+        found: Vec<u8>,
+        expected: &'static [u8],
+    },
+    #[error("Unsupported version {0:#x}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid data at {offset:#x}: {reason}")]
+    InvalidData { offset: usize, reason: String },
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Little-endian cursor over a byte slice. All offsets are absolute from the start of the slice.
+#[derive(Clone)]
+pub(crate) struct BinaryReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+macro_rules! read_primitive {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $name(&mut self) -> Result<$ty, FormatError> {
+                let bytes = self.bytes(size_of::<$ty>())?;
+                Ok(<$ty>::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        )*
+    };
+}
+
+impl<'a> BinaryReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Creates a second reader positioned at `position`, leaving this one untouched.
+    pub fn at(&self, position: usize) -> Self {
+        Self {
+            data: self.data,
+            position,
+        }
+    }
+
+    pub fn skip(&mut self, count: usize) {
+        self.position += count;
+    }
+
+    pub fn bytes(&mut self, length: usize) -> Result<&'a [u8], FormatError> {
+        let slice = self
+            .position
+            .checked_add(length)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or(FormatError::UnexpectedEof {
+                offset: self.position,
+                length,
+            })?;
+
+        self.position += length;
+        Ok(slice)
+    }
+
+    read_primitive!(
+        u8: u8,
+        i8: i8,
+        u16: u16,
+        i16: i16,
+        u32: u32,
+        i32: i32,
+        u64: u64,
+        i64: i64,
+        f32: f32,
+    );
+
+    /// Reads a `repr(C)` structure straight from the data.
+    ///
+    /// # Safety
+    /// Every bit pattern has to be a valid `T`, which rules out pointers, references and enums.
+    pub unsafe fn pod<T>(&mut self) -> Result<T, FormatError> {
+        let bytes = self.bytes(size_of::<T>())?;
+        Ok(std::ptr::read_unaligned(bytes.as_ptr().cast()))
+    }
+
+    pub fn magic(&mut self, expected: &'static [u8]) -> Result<(), FormatError> {
+        let found = self.bytes(expected.len())?;
+        if found != expected {
+            return Err(FormatError::InvalidMagic {
+                found: found.to_vec(),
+                expected,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reads a 64-bit offset or count and checks that it is not negative.
+    pub fn offset(&mut self) -> Result<usize, FormatError> {
+        let position = self.position;
+        usize::try_from(self.i64()?).map_err(|_| self.invalid(position, "negative offset"))
+    }
+
+    /// Reads a 32-bit offset or count and checks that it is not negative.
+    pub fn offset32(&mut self) -> Result<usize, FormatError> {
+        let position = self.position;
+        usize::try_from(self.i32()?).map_err(|_| self.invalid(position, "negative offset"))
+    }
+
+    /// Sums offsets read from the data, failing instead of overflowing.
+    pub fn add_offsets(&self, offsets: &[usize]) -> Result<usize, FormatError> {
+        offsets
+            .iter()
+            .try_fold(0usize, |sum, offset| sum.checked_add(*offset))
+            .ok_or_else(|| self.invalid(self.position, "offset out of range"))
+    }
+
+    /// Reads a null-terminated UTF-16 string starting at the current position.
+    pub fn utf16(&mut self) -> Result<String, FormatError> {
+        let mut units = vec![];
+        loop {
+            match self.u16()? {
+                0 => break,
+                unit => units.push(unit),
+            }
+        }
+
+        Ok(String::from_utf16_lossy(&units))
+    }
+
+    /// Reads a null-terminated single byte string starting at the current position.
+    pub fn cstr(&mut self) -> Result<String, FormatError> {
+        let remaining = self.data.get(self.position..).unwrap_or_default();
+        let length = remaining
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(FormatError::UnexpectedEof {
+                offset: self.position,
+                length: remaining.len() + 1,
+            })?;
+
+        let string = String::from_utf8_lossy(&remaining[..length]).into_owned();
+        self.position += length + 1;
+        Ok(string)
+    }
+
+    pub fn invalid(&self, offset: usize, reason: impl Into<String>) -> FormatError {
+        FormatError::InvalidData {
+            offset,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Little-endian byte buffer with support for patching offsets once they are known.
+#[derive(Default)]
+pub(crate) struct BinaryWriter {
+    data: Vec<u8>,
+}
+
+macro_rules! write_primitive {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $name(&mut self, value: $ty) {
+                self.data.extend(value.to_le_bytes());
+            }
+        )*
+    };
+}
+
+impl BinaryWriter {
+    pub fn position(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    write_primitive!(
+        u8: u8,
+        i8: i8,
+        u16: u16,
+        i16: i16,
+        u32: u32,
+        i32: i32,
+        u64: u64,
+        i64: i64,
+        f32: f32,
+    );
+
+    /// Writes a null-terminated UTF-16 string.
+    pub fn utf16(&mut self, value: &str) {
+        for unit in value.encode_utf16().chain([0]) {
+            self.u16(unit);
+        }
+    }
+
+    /// Pads with zeroes until the position is a multiple of `alignment`.
+    pub fn pad(&mut self, alignment: usize) {
+        self.data
+            .resize(self.data.len().next_multiple_of(alignment), 0);
+    }
+
+    /// Overwrites a previously written i32, used to fill in offsets after the fact.
+    pub fn patch_i32(&mut self, position: usize, value: i32) {
+        self.data[position..position + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn patch_i64(&mut self, position: usize, value: i64) {
+        self.data[position..position + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Caps how much a parser reads from a file whose nodes refer to each other through offsets.
+/// Limiting the nesting depth isn't enough, as a node can list the same child, or one of its
+/// parents, several times and make the tree exponentially larger than the file.
+pub(crate) struct ReadBudget(Cell<usize>);
+
+impl ReadBudget {
+    /// Allows one item per byte of `data`. Every node and field takes up several bytes, so a file
+    /// that doesn't repeat offsets stays well below this.
+    pub fn new(data: &[u8]) -> Self {
+        Self(Cell::new(data.len()))
+    }
+
+    /// Accounts for `count` items about to be read at the reader's position.
+    pub fn take(&self, reader: &BinaryReader, count: usize) -> Result<(), FormatError> {
+        let remaining = self.0.get().checked_sub(count).ok_or_else(|| {
+            reader.invalid(reader.position(), "file refers to more data than it holds")
+        })?;
+
+        self.0.set(remaining);
+        Ok(())
+    }
+}
+
+/// Views a `repr(C)` structure as its raw bytes.
+///
+/// # Safety
+/// `T` can't contain padding, as reading uninitialized padding bytes is undefined behavior.
+pub(crate) unsafe fn pod_bytes<T>(value: &T) -> &[u8] {
+    std::slice::from_raw_parts((value as *const T).cast(), size_of::<T>())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Primitive types used to describe argument layouts in definition files.
+pub enum ArgumentType {
+    U8,
+    S8,
+    U16,
+    S16,
+    U32,
+    S32,
+    F32,
+}
+
+impl ArgumentType {
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U8 | Self::S8 => 1,
+            Self::U16 | Self::S16 => 2,
+            Self::U32 | Self::S32 | Self::F32 => 4,
+        }
+    }
+
+    pub(crate) fn format_into(&self, bytes: &[u8], output: &mut String) {
+        match self {
+            Self::U8 => write!(output, "{}", bytes[0]),
+            Self::S8 => write!(output, "{}", bytes[0] as i8),
+            Self::U16 => write!(output, "{}", u16::from_le_bytes([bytes[0], bytes[1]])),
+            Self::S16 => write!(output, "{}", i16::from_le_bytes([bytes[0], bytes[1]])),
+            Self::U32 => write!(output, "{}", u32::from_le_bytes(bytes.try_into().unwrap())),
+            Self::S32 => write!(output, "{}", i32::from_le_bytes(bytes.try_into().unwrap())),
+            Self::F32 => write!(
+                output,
+                "{:?}",
+                f32::from_le_bytes(bytes.try_into().unwrap())
+            ),
+        }
+        .unwrap()
+    }
+}
+
+impl TryFrom<&str> for ArgumentType {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
+            "u8" => Self::U8,
+            "s8" | "i8" => Self::S8,
+            "u16" => Self::U16,
+            "s16" | "i16" => Self::S16,
+            "u32" => Self::U32,
+            "s32" | "i32" => Self::S32,
+            "f32" => Self::F32,
+            _ => return Err(format!("unknown argument type {value}")),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArgumentDefinition {
+    pub name: Option<String>,
+    pub ty: ArgumentType,
+}
+
+impl ArgumentDefinition {
+    /// Parses an argument written as either a bare type or `name:type`.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let (name, ty) = match source.split_once(':') {
+            Some((name, ty)) => (Some(name.to_string()), ty),
+            None => (None, source),
+        };
+
+        Ok(Self {
+            name,
+            ty: ArgumentType::try_from(ty)?,
+        })
+    }
+}
@@ -0,0 +1,448 @@
+//! BHD5 archive headers (`Data0.bhd`) and the BDT files (`Data0.bdt`) they index. Headers are
+//! encrypted with the archive's RSA key, entries are found by hashing their path and may have
+//! parts of their data encrypted with AES-128-ECB.
+use std::{
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+use aes::{
+    cipher::{BlockDecrypt, KeyInit},
+    Aes128,
+};
+use base64::Engine;
+use num_bigint::BigUint;
+
+use super::{BinaryReader, FormatError};
+
+const MAGIC: &[u8] = b"BHD5";
+const PATH_HASH_PRIME: u64 = 0x85;
+
+/// Public key an archive's header is encrypted with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bhd5Key {
+    modulus: BigUint,
+    exponent: BigUint,
+}
+
+impl Bhd5Key {
+    /// Creates a key from its big-endian modulus and exponent.
+    pub fn new(modulus: &[u8], exponent: &[u8]) -> Self {
+        Self {
+            modulus: BigUint::from_bytes_be(modulus),
+            exponent: BigUint::from_bytes_be(exponent),
+        }
+    }
+
+    /// Reads a PKCS#1 key (`-----BEGIN RSA PUBLIC KEY-----`), the form the archive keys are
+    /// usually shared in.
+    pub fn from_pem(pem: &str) -> Result<Self, FormatError> {
+        let base64 = pem
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.starts_with("-----"))
+            .collect::<String>();
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(base64)
+            .map_err(|e| FormatError::InvalidData {
+                offset: 0,
+                reason: format!("invalid key encoding: {e}"),
+            })?;
+
+        let mut reader = BinaryReader::new(&der);
+        let sequence = der_element(&mut reader, 0x30)?;
+        let mut reader = BinaryReader::new(sequence);
+        let modulus = der_element(&mut reader, 0x02)?;
+        let exponent = der_element(&mut reader, 0x02)?;
+
+        Ok(Self::new(modulus, exponent))
+    }
+
+    /// Decrypts a header. Every block of the key's size decrypts to one byte less.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, FormatError> {
+        let bits = self.modulus.bits() as usize;
+        let input_size = bits.div_ceil(8);
+        let output_size = bits.saturating_sub(1) / 8;
+        if output_size == 0 {
+            return Err(FormatError::InvalidData {
+                offset: 0,
+                reason: "key is too small".to_string(),
+            });
+        }
+
+        if !data.len().is_multiple_of(input_size) {
+            return Err(FormatError::InvalidData {
+                offset: 0,
+                reason: format!("header is not a multiple of the {input_size} byte key size"),
+            });
+        }
+
+        let mut decrypted = Vec::with_capacity(data.len() / input_size * output_size);
+        for (i, block) in data.chunks_exact(input_size).enumerate() {
+            let block = BigUint::from_bytes_be(block).modpow(&self.exponent, &self.modulus);
+            let block = block.to_bytes_be();
+            if block.len() > output_size {
+                return Err(FormatError::InvalidData {
+                    offset: i * input_size,
+                    reason: "block does not decrypt with this key".to_string(),
+                });
+            }
+
+            decrypted.resize(decrypted.len() + output_size - block.len(), 0);
+            decrypted.extend(block);
+        }
+
+        Ok(decrypted)
+    }
+}
+
+/// Reads a DER element with a short or long form length, returning its contents.
+fn der_element<'a>(reader: &mut BinaryReader<'a>, tag: u8) -> Result<&'a [u8], FormatError> {
+    let position = reader.position();
+    if reader.u8()? != tag {
+        return Err(reader.invalid(position, "unexpected key structure"));
+    }
+
+    let length = match reader.u8()? {
+        length @ 0..0x80 => length as usize,
+        long => (0..long & 0x7F).try_fold(0usize, |length, _| {
+            Ok::<_, FormatError>(length << 8 | reader.u8()? as usize)
+        })?,
+    };
+
+    reader.bytes(length)
+}
+
+/// Hashes a path the way the game does to look up archive entries. Paths are case-insensitive,
+/// may use either separator and get a leading slash if they don't have one.
+pub fn path_hash(path: &str) -> u64 {
+    let path = path.replace('\\', "/").to_lowercase();
+    let prefix = (!path.starts_with('/')).then_some('/');
+
+    prefix.into_iter().chain(path.chars()).fold(0, |hash, c| {
+        hash.wrapping_mul(PATH_HASH_PRIME).wrapping_add(c as u64)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bhd5 {
+    pub salt: String,
+    /// Entries grouped by the remainder of their path hash divided by the bucket count.
+    pub buckets: Vec<Vec<Bhd5Entry>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bhd5Entry {
+    pub path_hash: u64,
+    pub padded_size: u32,
+    /// Size without the AES padding, 0 if the entry isn't padded.
+    pub unpadded_size: u32,
+    /// Position of the entry's data in the BDT.
+    pub offset: u64,
+    pub sha_hash: Option<Bhd5ShaHash>,
+    pub aes_key: Option<Bhd5AesKey>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bhd5ShaHash {
+    pub hash: [u8; 32],
+    /// Parts of the data covered by the hash.
+    pub ranges: Vec<Range<u64>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bhd5AesKey {
+    pub key: [u8; 16],
+    /// Parts of the data that are encrypted.
+    pub ranges: Vec<Range<u64>>,
+}
+
+impl Bhd5 {
+    /// Reads a decrypted header.
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = BinaryReader::new(data);
+        reader.magic(MAGIC)?;
+        if reader.u8()? != 0xFF {
+            return Err(reader.invalid(0x4, "big endian headers are not supported"));
+        }
+        reader.skip(3);
+
+        let version = reader.u32()?;
+        if version != 1 {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        reader.skip(4);
+        let bucket_count = reader.u32()? as usize;
+        let buckets_offset = reader.u32()? as usize;
+        let salt_length = reader.u32()? as usize;
+        let salt = String::from_utf8_lossy(reader.bytes(salt_length)?).into_owned();
+
+        let buckets = (0..bucket_count)
+            .map(|i| {
+                let mut reader = reader.at(buckets_offset + i * 8);
+                let entry_count = reader.u32()? as usize;
+                let entries_offset = reader.u32()? as usize;
+
+                let mut reader = reader.at(entries_offset);
+                (0..entry_count)
+                    .map(|_| Bhd5Entry::read(&mut reader))
+                    .collect::<Result<_, _>>()
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { salt, buckets })
+    }
+
+    /// Decrypts and reads a header as stored on disk.
+    pub fn parse_encrypted(data: &[u8], key: &Bhd5Key) -> Result<Self, FormatError> {
+        Self::parse(&key.decrypt(data)?)
+    }
+
+    /// Looks up the entry for a path like `/map/m60/m60_44_36_00.mapbnd.dcx`.
+    pub fn entry(&self, path: &str) -> Option<&Bhd5Entry> {
+        self.entry_by_hash(path_hash(path))
+    }
+
+    pub fn entry_by_hash(&self, hash: u64) -> Option<&Bhd5Entry> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+
+        self.buckets[(hash % self.buckets.len() as u64) as usize]
+            .iter()
+            .find(|e| e.path_hash == hash)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Bhd5Entry> {
+        self.buckets.iter().flatten()
+    }
+}
+
+impl Bhd5Entry {
+    fn read(reader: &mut BinaryReader) -> Result<Self, FormatError> {
+        let path_hash = reader.u64()?;
+        let padded_size = reader.u32()?;
+        let unpadded_size = reader.u32()?;
+        let offset = reader.u64()?;
+        let sha_hash_offset = reader.offset()?;
+        let aes_key_offset = reader.offset()?;
+
+        let sha_hash = match sha_hash_offset {
+            0 => None,
+            offset => {
+                let mut reader = reader.at(offset);
+                Some(Bhd5ShaHash {
+                    hash: reader.bytes(32)?.try_into().unwrap(),
+                    ranges: read_ranges(&mut reader)?,
+                })
+            }
+        };
+
+        let aes_key = match aes_key_offset {
+            0 => None,
+            offset => {
+                let mut reader = reader.at(offset);
+                Some(Bhd5AesKey {
+                    key: reader.bytes(16)?.try_into().unwrap(),
+                    ranges: read_ranges(&mut reader)?,
+                })
+            }
+        };
+
+        Ok(Self {
+            path_hash,
+            padded_size,
+            unpadded_size,
+            offset,
+            sha_hash,
+            aes_key,
+        })
+    }
+
+    /// Reads the entry's data from its BDT and decrypts it. The result is usually still DCX
+    /// compressed.
+    pub fn extract(&self, bdt: &mut (impl Read + Seek)) -> Result<Vec<u8>, FormatError> {
+        bdt.seek(SeekFrom::Start(self.offset))?;
+        let mut data = vec![0; self.padded_size as usize];
+        bdt.read_exact(&mut data)?;
+
+        if let Some(aes_key) = &self.aes_key {
+            aes_key.decrypt(&mut data)?;
+        }
+
+        if self.unpadded_size != 0 {
+            data.truncate(self.unpadded_size as usize);
+        }
+
+        Ok(data)
+    }
+}
+
+impl Bhd5AesKey {
+    fn decrypt(&self, data: &mut [u8]) -> Result<(), FormatError> {
+        let cipher = Aes128::new(&self.key.into());
+        for range in self.ranges.iter() {
+            let (start, end) = (range.start as usize, range.end as usize);
+            if end > data.len() || !(end - start).is_multiple_of(16) {
+                return Err(FormatError::InvalidData {
+                    offset: start,
+                    reason: format!("invalid encrypted range {range:?}"),
+                });
+            }
+
+            for block in data[start..end].chunks_exact_mut(16) {
+                cipher.decrypt_block(block.into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a count followed by start and end offsets. Ranges starting at -1 are unused.
+fn read_ranges(reader: &mut BinaryReader) -> Result<Vec<Range<u64>>, FormatError> {
+    let count = reader.u32()? as usize;
+    let ranges = (0..count)
+        .map(|_| Ok((reader.i64()?, reader.i64()?)))
+        .collect::<Result<Vec<_>, FormatError>>()?;
+
+    Ok(ranges
+        .into_iter()
+        .filter(|(start, end)| *start >= 0 && end > start)
+        .map(|(start, end)| start as u64..end as u64)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use aes::{
+        cipher::{BlockEncrypt, KeyInit},
+        Aes128,
+    };
+    use num_bigint::BigUint;
+
+    use super::{path_hash, Bhd5, Bhd5Key};
+    use crate::formats::BinaryWriter;
+
+    const REGULATION: &str = "/regulation.bin";
+    const MAP: &str = "/map/m60/m60_44_36_00/m60_44_36_00.mapbnd.dcx";
+    const AES_KEY: [u8; 16] = [7; 16];
+
+    /// Builds a header with two buckets and the matching BDT.
+    fn archive() -> (Vec<u8>, Vec<u8>) {
+        let mut bdt = b"BDF4".to_vec();
+        bdt.resize(0x10, 0);
+
+        let regulation_offset = bdt.len() as u64;
+        bdt.extend(b"regulation");
+        let map_offset = bdt.len() as u64;
+        let mut map = [0x11; 48];
+        for block in map[..32].chunks_exact_mut(16) {
+            Aes128::new(&AES_KEY.into()).encrypt_block(block.into());
+        }
+        bdt.extend(map);
+
+        let mut writer = BinaryWriter::default();
+        writer.bytes(b"BHD5");
+        writer.bytes(&[0xFF, 0, 0, 0]);
+        writer.u32(1);
+        writer.u32(0);
+        writer.u32(2);
+        writer.u32(0x20);
+        writer.u32(4);
+        writer.bytes(b"salt");
+
+        let entries = [
+            (REGULATION, regulation_offset, 10, false),
+            (MAP, map_offset, 48, true),
+        ];
+        let mut buckets = [vec![], vec![]];
+        for entry in entries {
+            buckets[(path_hash(entry.0) % 2) as usize].push(entry);
+        }
+
+        // Buckets, then entries, then the AES key.
+        let entries_offset = 0x20 + 2 * 8;
+        let aes_key_offset = entries_offset + entries.len() * 40;
+        let mut first_entry = 0;
+        for bucket in buckets.iter() {
+            writer.u32(bucket.len() as u32);
+            writer.u32((entries_offset + first_entry * 40) as u32);
+            first_entry += bucket.len();
+        }
+
+        for (path, offset, size, encrypted) in buckets.iter().flatten() {
+            writer.u64(path_hash(path));
+            writer.u32(*size);
+            writer.u32(0);
+            writer.u64(*offset);
+            writer.i64(0);
+            writer.i64(if *encrypted { aes_key_offset as i64 } else { 0 });
+        }
+
+        writer.bytes(&AES_KEY);
+        writer.u32(2);
+        writer.i64(0);
+        writer.i64(32);
+        writer.i64(-1);
+        writer.i64(-1);
+
+        (writer.into_inner(), bdt)
+    }
+
+    #[test]
+    fn extracts_entries() {
+        let (header, bdt) = archive();
+        let bhd = Bhd5::parse(&header).unwrap();
+        assert_eq!(bhd.salt, "salt");
+        assert_eq!(bhd.entries().count(), 2);
+        assert!(bhd.entry("/regulation.bin.dcx").is_none());
+
+        let mut bdt = Cursor::new(bdt);
+        let regulation = bhd.entry("REGULATION.BIN").unwrap();
+        assert_eq!(regulation.extract(&mut bdt).unwrap(), b"regulation");
+
+        let map = bhd
+            .entry(r"\map\m60\m60_44_36_00\m60_44_36_00.mapbnd.dcx")
+            .unwrap();
+        let ranges = &map.aes_key.as_ref().unwrap().ranges;
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0], 0..32);
+        assert_eq!(map.extract(&mut bdt).unwrap(), [0x11; 48]);
+    }
+
+    #[test]
+    fn decrypts_header() {
+        // Textbook RSA key with n = 61 * 53, small enough for one byte per block.
+        let key = Bhd5Key::from_pem(
+            "-----BEGIN RSA PUBLIC KEY-----\nMAcCAgyhAgER\n-----END RSA PUBLIC KEY-----",
+        )
+        .unwrap();
+        assert_eq!(key, Bhd5Key::new(&[0x0C, 0xA1], &[0x11]));
+
+        let plaintext = b"BHD5\xFF";
+        let private_exponent = BigUint::from(2753u32);
+        let modulus = BigUint::from(3233u32);
+        let encrypted = plaintext
+            .iter()
+            .flat_map(|byte| {
+                let block = BigUint::from(*byte).modpow(&private_exponent, &modulus);
+                let block = block.to_bytes_be();
+                [vec![0; 2 - block.len()], block].concat()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(key.decrypt(&encrypted).unwrap(), plaintext);
+        assert!(key.decrypt(&encrypted[1..]).is_err());
+    }
+
+    #[test]
+    fn hashes_paths() {
+        assert_eq!(path_hash("/a"), 0x2F * 0x85 + 0x61);
+        assert_eq!(path_hash("a"), path_hash("/A"));
+        assert_eq!(path_hash(r"\map\m60"), path_hash("/map/m60"));
+    }
+}
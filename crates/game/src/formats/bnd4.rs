@@ -0,0 +1,326 @@
+//! BND4 archives. Bundle several files into one, used for save files, `.parambnd`s, `.anibnd`s and
+//! most other `*bnd` files once they've been taken out of their DCX compression.
+use super::{BinaryReader, BinaryWriter, FormatError};
+
+const MAGIC: &[u8] = b"BND4";
+
+/// Bits of [`Bnd4::format`] describing which fields the entry headers contain.
+pub mod format {
+    pub const IDS: u8 = 0b0000_0010;
+    pub const NAMES1: u8 = 0b0000_0100;
+    pub const NAMES2: u8 = 0b0000_1000;
+    pub const LONG_OFFSETS: u8 = 0b0001_0000;
+    pub const COMPRESSION: u8 = 0b0010_0000;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bnd4 {
+    /// Eight byte version string, usually a timestamp like `07D7R6`.
+    pub version: [u8; 8],
+    /// Decoded entry header format, see [`format`].
+    pub format: u8,
+    /// Whether entry names are stored as UTF-16 instead of Shift-JIS.
+    pub unicode: bool,
+    pub entries: Vec<Bnd4Entry>,
+    unk04: u8,
+    unk05: u8,
+    bit_big_endian: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bnd4Entry {
+    pub flags: u8,
+    pub id: i32,
+    pub name: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl Bnd4 {
+    pub fn new(format: u8, unicode: bool) -> Self {
+        Self {
+            version: *b"00000001",
+            format,
+            unicode,
+            entries: vec![],
+            unk04: 0,
+            unk05: 0,
+            bit_big_endian: false,
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        Ok(Self::parse_with_offsets(data)?.0)
+    }
+
+    /// Parses the archive and returns where each entry's data starts in `data`.
+    pub(crate) fn parse_with_offsets(data: &[u8]) -> Result<(Self, Vec<usize>), FormatError> {
+        let mut reader = BinaryReader::new(data);
+        reader.magic(MAGIC)?;
+
+        let unk04 = reader.u8()?;
+        let unk05 = reader.u8()?;
+        reader.skip(3);
+        if reader.u8()? != 0 {
+            return Err(reader.invalid(0x9, "big endian archives are not supported"));
+        }
+        // Stored inverted, little endian archives set it.
+        let bit_big_endian = reader.u8()? == 0;
+        reader.skip(1);
+
+        let entry_count = reader.offset32()?;
+        reader.skip(8);
+        let version = reader.bytes(8)?.try_into().unwrap();
+        let entry_header_size = reader.offset()?;
+        reader.skip(8);
+        let unicode = reader.u8()? != 0;
+        let format = decode_format(reader.u8()?, bit_big_endian);
+
+        let (entries, offsets) = (0..entry_count)
+            .map(|i| {
+                let header_offset = i
+                    .checked_mul(entry_header_size)
+                    .ok_or_else(|| reader.invalid(0x20, "entry headers out of range"))?;
+                let mut reader = reader.at(reader.add_offsets(&[0x40, header_offset])?);
+                let flags = reader.u8()?;
+                reader.skip(7);
+                let size = reader.offset()?;
+                if format & format::COMPRESSION != 0 && reader.offset()? != size {
+                    return Err(reader.invalid(reader.position(), "compressed entry"));
+                }
+
+                let data_offset = match format & format::LONG_OFFSETS {
+                    0 => reader.u32()? as usize,
+                    _ => reader.offset()?,
+                };
+
+                let mut id = match format & format::IDS {
+                    0 => -1,
+                    _ => reader.i32()?,
+                };
+
+                let name = match format & (format::NAMES1 | format::NAMES2) {
+                    0 => None,
+                    _ => {
+                        let name_offset = reader.u32()? as usize;
+                        let mut reader = reader.at(name_offset);
+                        Some(match unicode {
+                            true => reader.utf16()?,
+                            false => reader.cstr()?,
+                        })
+                    }
+                };
+
+                // Archives with only the first name bit set store the ID after the name.
+                if format == format::NAMES1 {
+                    id = reader.i32()?;
+                }
+
+                let entry = Bnd4Entry {
+                    flags,
+                    id,
+                    name,
+                    data: reader.at(data_offset).bytes(size)?.to_vec(),
+                };
+                Ok((entry, data_offset))
+            })
+            .collect::<Result<(Vec<_>, Vec<_>), FormatError>>()?;
+
+        let archive = Self {
+            version,
+            format,
+            unicode,
+            entries,
+            unk04,
+            unk05,
+            bit_big_endian,
+        };
+        Ok((archive, offsets))
+    }
+
+    /// Serializes the archive. Entry data is aligned to 16 bytes and no name hash table is
+    /// written.
+    pub fn write(&self) -> Vec<u8> {
+        let mut writer = BinaryWriter::default();
+        writer.bytes(MAGIC);
+        writer.u8(self.unk04);
+        writer.u8(self.unk05);
+        writer.bytes(&[0; 3]);
+        // Big endian, then bit big endian inverted.
+        writer.u8(0);
+        writer.u8(!self.bit_big_endian as u8);
+        writer.u8(0);
+        writer.i32(self.entries.len() as i32);
+        writer.i64(0x40);
+        writer.bytes(&self.version);
+        writer.i64(self.entry_header_size() as i64);
+        let headers_end_position = writer.position();
+        writer.i64(0);
+        writer.u8(self.unicode as u8);
+        writer.u8(encode_format(self.format, self.bit_big_endian));
+        writer.u8(0);
+        writer.u8(0);
+        writer.i32(0);
+        writer.i64(0);
+
+        let mut name_placeholders = vec![];
+        let mut data_placeholders = vec![];
+        for entry in self.entries.iter() {
+            writer.u8(entry.flags);
+            writer.bytes(&[0; 3]);
+            writer.i32(-1);
+            writer.i64(entry.data.len() as i64);
+            if self.format & format::COMPRESSION != 0 {
+                writer.i64(entry.data.len() as i64);
+            }
+
+            data_placeholders.push(writer.position());
+            match self.format & format::LONG_OFFSETS {
+                0 => writer.u32(0),
+                _ => writer.i64(0),
+            }
+
+            if self.format & format::IDS != 0 {
+                writer.i32(entry.id);
+            }
+
+            if self.format & (format::NAMES1 | format::NAMES2) != 0 {
+                name_placeholders.push(writer.position());
+                writer.u32(0);
+            }
+
+            if self.format == format::NAMES1 {
+                writer.i32(entry.id);
+                writer.i32(0);
+            }
+        }
+
+        for (entry, placeholder) in self.entries.iter().zip(name_placeholders) {
+            writer.patch_i32(placeholder, writer.position() as i32);
+            let name = entry.name.as_deref().unwrap_or_default();
+            match self.unicode {
+                true => writer.utf16(name),
+                false => {
+                    writer.bytes(name.as_bytes());
+                    writer.u8(0);
+                }
+            }
+        }
+
+        let headers_end = writer.position() as i64;
+        writer.patch_i64(headers_end_position, headers_end);
+
+        for (entry, placeholder) in self.entries.iter().zip(data_placeholders) {
+            writer.pad(0x10);
+            let position = writer.position();
+            match self.format & format::LONG_OFFSETS {
+                0 => writer.patch_i32(placeholder, position as i32),
+                _ => writer.patch_i64(placeholder, position as i64),
+            }
+            writer.bytes(&entry.data);
+        }
+
+        writer.into_inner()
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&Bnd4Entry> {
+        self.entries
+            .iter()
+            .find(|e| e.name.as_deref() == Some(name))
+    }
+
+    pub fn entry_mut(&mut self, name: &str) -> Option<&mut Bnd4Entry> {
+        self.entries
+            .iter_mut()
+            .find(|e| e.name.as_deref() == Some(name))
+    }
+
+    fn entry_header_size(&self) -> usize {
+        let mut size = 0x10;
+        if self.format & format::COMPRESSION != 0 {
+            size += 8;
+        }
+        size += match self.format & format::LONG_OFFSETS {
+            0 => 4,
+            _ => 8,
+        };
+        if self.format & format::IDS != 0 {
+            size += 4;
+        }
+        if self.format & (format::NAMES1 | format::NAMES2) != 0 {
+            size += 4;
+        }
+        if self.format == format::NAMES1 {
+            size += 8;
+        }
+        size
+    }
+}
+
+/// The format byte is stored bit-reversed unless the archive says otherwise.
+fn decode_format(raw: u8, bit_big_endian: bool) -> u8 {
+    match bit_big_endian || (raw & 1 != 0 && raw & 0x80 == 0) {
+        true => raw,
+        false => raw.reverse_bits(),
+    }
+}
+
+fn encode_format(format: u8, bit_big_endian: bool) -> u8 {
+    match bit_big_endian || decode_format(format.reverse_bits(), false) != format {
+        true => format,
+        false => format.reverse_bits(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format, Bnd4, Bnd4Entry};
+
+    #[test]
+    fn round_trip() {
+        for format in [
+            format::IDS | format::NAMES1 | format::NAMES2 | format::COMPRESSION,
+            format::IDS | format::NAMES2 | format::LONG_OFFSETS,
+            format::NAMES1,
+        ] {
+            let mut bnd = Bnd4::new(format, true);
+            bnd.entries = vec![
+                Bnd4Entry {
+                    flags: 0x40,
+                    id: 0,
+                    name: Some("USER_DATA000".to_string()),
+                    data: vec![1, 2, 3],
+                },
+                Bnd4Entry {
+                    flags: 0x40,
+                    id: 1,
+                    name: Some("USER_DATA001".to_string()),
+                    data: vec![4; 0x21],
+                },
+            ];
+
+            let bytes = bnd.write();
+            let parsed = Bnd4::parse(&bytes).unwrap();
+            assert_eq!(parsed, bnd);
+            assert_eq!(parsed.write(), bytes);
+            assert_eq!(parsed.entry("USER_DATA001").unwrap().data.len(), 0x21);
+        }
+    }
+
+    #[test]
+    fn writes_game_header() {
+        let bnd = Bnd4::new(
+            format::IDS | format::NAMES1 | format::NAMES2 | format::COMPRESSION,
+            true,
+        );
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            b'B', b'N', b'D', b'4', 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0,
+            0x40, 0, 0, 0, 0, 0, 0, 0, b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'1',
+            0x24, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0, 0,
+            1, 0x74, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(bnd.write(), expected);
+        assert_eq!(Bnd4::parse(expected).unwrap(), bnd);
+    }
+}
@@ -0,0 +1,528 @@
+//! EMEVD event scripts. Every map, plus `common` and `common_func`, ships one of these. Each
+//! event is a list of instructions identified by a bank and an index, with their arguments
+//! packed into a shared argument blob. Event parameters are patched into instruction arguments
+//! when an event is initialized through `2000[00]`.
+use std::{collections::HashMap, fmt::Write};
+
+use super::{ArgumentDefinition, ArgumentType, BinaryReader, FormatError};
+
+const EVENT_SIZE: usize = 0x30;
+const INSTRUCTION_SIZE: usize = 0x20;
+const PARAMETER_SIZE: usize = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What happens with an event once it has ran to completion.
+pub enum RestBehavior {
+    /// Event ends and does not run again until the map is reloaded.
+    Default,
+    /// Event restarts from the top.
+    Restart,
+    /// Event ends and will not run again until the flag of the same ID is cleared.
+    End,
+    Unknown(u32),
+}
+
+impl From<u32> for RestBehavior {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Default,
+            1 => Self::Restart,
+            2 => Self::End,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Emevd {
+    pub events: Vec<Event>,
+    /// Other EMEVD files whose events can be referenced from this one, usually `common_func`.
+    pub linked_files: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: i64,
+    pub rest_behavior: RestBehavior,
+    pub instructions: Vec<Instruction>,
+    pub parameters: Vec<Parameter>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub bank: i32,
+    pub index: i32,
+    /// Raw argument bytes laid out as the instruction expects them.
+    pub args: Vec<u8>,
+    /// Bitmask of map layers this instruction is restricted to, if any.
+    pub layer: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Describes a copy from the event's initialization arguments into an instruction's arguments.
+pub struct Parameter {
+    /// Index of the instruction within the event that receives the value.
+    pub instruction_index: usize,
+    /// Byte offset within the instruction's arguments to write to.
+    pub target_start_byte: usize,
+    /// Byte offset within the event's initialization arguments to read from.
+    pub source_start_byte: usize,
+    pub byte_count: usize,
+    unk1c: i32,
+}
+
+impl Emevd {
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = BinaryReader::new(data);
+        reader.magic(b"EVD\0")?;
+
+        let big_endian = reader.u8()?;
+        let is_64_bit = reader.i8()?;
+        reader.skip(2);
+        if big_endian != 0 || is_64_bit != -1 {
+            return Err(reader.invalid(4, "only little endian 64-bit EMEVD is supported"));
+        }
+
+        let version = reader.u32()?;
+        if version != 0xCD {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        let _file_size = reader.u32()?;
+        let event_count = reader.offset()?;
+        let events_offset = reader.offset()?;
+        let _instruction_count = reader.offset()?;
+        let instructions_offset = reader.offset()?;
+        reader.skip(0x10);
+        let _layer_count = reader.offset()?;
+        let layers_offset = reader.offset()?;
+        let _parameter_count = reader.offset()?;
+        let parameters_offset = reader.offset()?;
+        let linked_file_count = reader.offset()?;
+        let linked_files_offset = reader.offset()?;
+        let _arguments_length = reader.offset()?;
+        let arguments_offset = reader.offset()?;
+        let _strings_length = reader.offset()?;
+        let strings_offset = reader.offset()?;
+
+        let events = (0..event_count)
+            .map(|i| {
+                let mut reader = reader.at(events_offset + i * EVENT_SIZE);
+                let id = reader.i64()?;
+                let instruction_count = reader.offset()?;
+                let instruction_offset = reader.offset()?;
+                let parameter_count = reader.offset()?;
+                let parameter_offset = reader.i64()?;
+                let rest_behavior = RestBehavior::from(reader.u32()?);
+
+                let instructions = (0..instruction_count)
+                    .map(|i| {
+                        let position = relative_offset(
+                            &reader,
+                            instructions_offset,
+                            instruction_offset + i * INSTRUCTION_SIZE,
+                        )?;
+                        let mut reader = reader.at(position);
+                        let bank = reader.i32()?;
+                        let index = reader.i32()?;
+                        let args_length = reader.offset()?;
+                        let args_position = reader.position();
+                        let args_offset = usize::try_from(reader.i32()?)
+                            .map_err(|_| reader.invalid(args_position, "negative offset"))?;
+                        reader.skip(4);
+                        let layer_offset = reader.i64()?;
+
+                        let args = reader
+                            .at(relative_offset(&reader, arguments_offset, args_offset)?)
+                            .bytes(args_length)?
+                            .to_vec();
+
+                        let layer = match usize::try_from(layer_offset) {
+                            Ok(offset) => {
+                                let mut reader = reader.at(layers_offset + offset);
+                                reader.skip(4);
+                                Some(reader.u32()?)
+                            }
+                            Err(_) => None,
+                        };
+
+                        Ok(Instruction {
+                            bank,
+                            index,
+                            args,
+                            layer,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, FormatError>>()?;
+
+                let parameters = match usize::try_from(parameter_offset) {
+                    Ok(offset) => (0..parameter_count)
+                        .map(|i| {
+                            let mut reader =
+                                reader.at(parameters_offset + offset + i * PARAMETER_SIZE);
+                            Ok(Parameter {
+                                instruction_index: reader.offset()?,
+                                target_start_byte: reader.offset()?,
+                                source_start_byte: reader.offset()?,
+                                byte_count: reader.i32()? as usize,
+                                unk1c: reader.i32()?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, FormatError>>()?,
+                    Err(_) => vec![],
+                };
+
+                Ok(Event {
+                    id,
+                    rest_behavior,
+                    instructions,
+                    parameters,
+                })
+            })
+            .collect::<Result<Vec<_>, FormatError>>()?;
+
+        let linked_files = (0..linked_file_count)
+            .map(|i| {
+                let offset = reader.at(linked_files_offset + i * 8).offset()?;
+                reader.at(strings_offset + offset).utf16()
+            })
+            .collect::<Result<Vec<_>, FormatError>>()?;
+
+        Ok(Self {
+            events,
+            linked_files,
+        })
+    }
+
+    /// Renders every event as an instruction listing, naming instructions and decoding their
+    /// arguments wherever `definitions` knows about them.
+    pub fn disassemble(&self, definitions: &EmevdDefinitions) -> String {
+        let mut output = String::new();
+        for file in self.linked_files.iter() {
+            writeln!(output, "// Linked: {file}").unwrap();
+        }
+
+        for event in self.events.iter() {
+            event.disassemble_into(definitions, &mut output);
+        }
+
+        output
+    }
+}
+
+/// Position of an entry at `offset` into the table starting at `table`.
+fn relative_offset(
+    reader: &BinaryReader,
+    table: usize,
+    offset: usize,
+) -> Result<usize, FormatError> {
+    table
+        .checked_add(offset)
+        .ok_or_else(|| reader.invalid(reader.position(), "offset out of range"))
+}
+
+impl Event {
+    fn disassemble_into(&self, definitions: &EmevdDefinitions, output: &mut String) {
+        writeln!(output, "Event {} ({:?}) {{", self.id, self.rest_behavior).unwrap();
+
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let parameters = self
+                .parameters
+                .iter()
+                .filter(|p| p.instruction_index == index)
+                .collect::<Vec<_>>();
+
+            write!(output, "    {:0>4}  ", index).unwrap();
+            instruction.disassemble_into(definitions, &parameters, output);
+
+            if let Some(layer) = instruction.layer {
+                write!(output, " // layers {layer:#010x}").unwrap();
+            }
+            output.push('\n');
+        }
+
+        output.push_str("}\n");
+    }
+}
+
+impl Instruction {
+    fn disassemble_into(
+        &self,
+        definitions: &EmevdDefinitions,
+        parameters: &[&Parameter],
+        output: &mut String,
+    ) {
+        let definition = definitions.instruction(self.bank, self.index);
+        match definition {
+            Some(definition) => output.push_str(&definition.name),
+            None => write!(output, "{}[{:0>2}]", self.bank, self.index).unwrap(),
+        }
+        output.push('(');
+
+        // Falls back to one u32 per four bytes when the layout of the arguments is unknown.
+        let fallback;
+        let args = match definition {
+            Some(definition) => definition.args.as_slice(),
+            None => {
+                fallback = (0..self.args.len() / 4)
+                    .map(|_| ArgumentDefinition {
+                        name: None,
+                        ty: ArgumentType::U32,
+                    })
+                    .collect::<Vec<_>>();
+                fallback.as_slice()
+            }
+        };
+
+        let mut offset = 0usize;
+        for (i, arg) in args.iter().enumerate() {
+            let size = arg.ty.size();
+            offset = offset.next_multiple_of(size);
+
+            if i != 0 {
+                output.push_str(", ");
+            }
+            if let Some(name) = &arg.name {
+                write!(output, "{name} = ").unwrap();
+            }
+
+            // Parameterized arguments are printed as X{source}_{size} for the event argument
+            // they will be replaced with.
+            if let Some(parameter) = parameters.iter().find(|p| p.target_start_byte == offset) {
+                write!(
+                    output,
+                    "X{}_{}",
+                    parameter.source_start_byte, parameter.byte_count
+                )
+                .unwrap();
+            } else {
+                match self.args.get(offset..offset + size) {
+                    Some(bytes) => arg.ty.format_into(bytes, output),
+                    None => output.push('?'),
+                }
+            }
+
+            offset += size;
+        }
+
+        let consumed = offset.next_multiple_of(4);
+        if consumed < self.args.len() {
+            if !args.is_empty() {
+                output.push_str(", ");
+            }
+            output.push_str("...");
+            for byte in &self.args[consumed..] {
+                write!(output, " {byte:02x}").unwrap();
+            }
+        }
+
+        output.push(')');
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstructionDefinition {
+    pub name: String,
+    pub args: Vec<ArgumentDefinition>,
+}
+
+#[derive(Debug, Default, Clone)]
+/// Names and argument layouts for instructions, keyed by bank and index.
+///
+/// Definitions are read from a line based text file. Every line names one instruction followed
+/// by its arguments in order, each argument either a bare type or `name:type`. Empty lines and
+/// lines starting with `#` are ignored:
+///
+/// ```text
+/// # bank[index] Name args...
+/// 2000[00] InitializeEvent slot:s32 event_id:u32
+/// 2003[66] SetEventFlag target_type:u8 flag:u32 state:u8
+/// ```
+pub struct EmevdDefinitions {
+    instructions: HashMap<(i32, i32), InstructionDefinition>,
+}
+
+impl EmevdDefinitions {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut instructions = HashMap::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: &str| format!("line {}: {reason}", line_number + 1);
+            let mut parts = line.split_whitespace();
+
+            let (bank, index) = parts
+                .next()
+                .and_then(|id| id.strip_suffix(']')?.split_once('['))
+                .and_then(|(bank, index)| Some((bank.parse().ok()?, index.parse().ok()?)))
+                .ok_or_else(|| error("expected bank[index]"))?;
+
+            let name = parts
+                .next()
+                .ok_or_else(|| error("missing instruction name"))?
+                .to_string();
+
+            let args = parts
+                .map(|arg| ArgumentDefinition::parse(arg).map_err(|e| error(&e)))
+                .collect::<Result<Vec<_>, String>>()?;
+
+            instructions.insert((bank, index), InstructionDefinition { name, args });
+        }
+
+        Ok(Self { instructions })
+    }
+
+    pub fn instruction(&self, bank: i32, index: i32) -> Option<&InstructionDefinition> {
+        self.instructions.get(&(bank, index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Emevd, EmevdDefinitions, RestBehavior};
+    use crate::formats::FormatError;
+
+    /// Builds a single event with one parameterized SetEventFlag and one unknown instruction.
+    fn sample() -> Vec<u8> {
+        let events = 0x90;
+        let instructions = events + 0x30;
+        let layers = instructions + 0x40;
+        let parameters = layers + 0x20;
+        let linked_files = parameters + 0x20;
+        let arguments = linked_files + 0x8;
+        let strings = arguments + 0x10;
+
+        let mut data = vec![];
+        data.extend(b"EVD\0");
+        data.extend([0, 0xFF, 0, 0xFF]);
+        data.extend(0xCDu32.to_le_bytes());
+        data.extend(0u32.to_le_bytes());
+        for value in [
+            1,
+            events,
+            2,
+            instructions,
+            0,
+            0,
+            1,
+            layers,
+            1,
+            parameters,
+            1,
+            linked_files,
+            0x10,
+            arguments,
+            0x10,
+            strings,
+        ] {
+            data.extend((value as i64).to_le_bytes());
+        }
+        assert_eq!(data.len(), events);
+
+        data.extend(1234i64.to_le_bytes());
+        data.extend(2i64.to_le_bytes());
+        data.extend(0i64.to_le_bytes());
+        data.extend(1i64.to_le_bytes());
+        data.extend(0i64.to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.extend(0u32.to_le_bytes());
+
+        data.extend(2003i32.to_le_bytes());
+        data.extend(66i32.to_le_bytes());
+        data.extend(12i64.to_le_bytes());
+        data.extend(0i32.to_le_bytes());
+        data.extend(0i32.to_le_bytes());
+        data.extend((-1i64).to_le_bytes());
+
+        data.extend(1000i32.to_le_bytes());
+        data.extend(3i32.to_le_bytes());
+        data.extend(4i64.to_le_bytes());
+        data.extend(12i32.to_le_bytes());
+        data.extend(0i32.to_le_bytes());
+        data.extend(0i64.to_le_bytes());
+
+        data.extend(2i32.to_le_bytes());
+        data.extend(0x4u32.to_le_bytes());
+        data.extend(0i64.to_le_bytes());
+        data.extend((-1i64).to_le_bytes());
+        data.extend(1i64.to_le_bytes());
+
+        data.extend(0i64.to_le_bytes());
+        data.extend(4i64.to_le_bytes());
+        data.extend(0i64.to_le_bytes());
+        data.extend(4i32.to_le_bytes());
+        data.extend(0i32.to_le_bytes());
+
+        data.extend(0i64.to_le_bytes());
+
+        data.extend([0, 0, 0, 0]);
+        data.extend(0u32.to_le_bytes());
+        data.extend([1, 0, 0, 0]);
+        data.extend(7u32.to_le_bytes());
+
+        for unit in "common_func".encode_utf16().chain([0]) {
+            data.extend(unit.to_le_bytes());
+        }
+
+        data
+    }
+
+    #[test]
+    fn parses_events() {
+        let emevd = Emevd::parse(&sample()).unwrap();
+
+        assert_eq!(emevd.linked_files, ["common_func"]);
+        assert_eq!(emevd.events.len(), 1);
+
+        let event = &emevd.events[0];
+        assert_eq!(event.id, 1234);
+        assert_eq!(event.rest_behavior, RestBehavior::Restart);
+        assert_eq!(event.instructions.len(), 2);
+        assert_eq!(event.instructions[0].args.len(), 12);
+        assert_eq!(event.instructions[0].layer, None);
+        assert_eq!(event.instructions[1].args, [7, 0, 0, 0]);
+        assert_eq!(event.instructions[1].layer, Some(0x4));
+        assert_eq!(event.parameters.len(), 1);
+        assert_eq!(event.parameters[0].target_start_byte, 4);
+    }
+
+    #[test]
+    fn disassembles_with_definitions() {
+        let emevd = Emevd::parse(&sample()).unwrap();
+        let definitions = EmevdDefinitions::parse(
+            "# comment\n2003[66] SetEventFlag target_type:u8 flag:u32 state:u8\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            emevd.disassemble(&definitions),
+            "// Linked: common_func\n\
+             Event 1234 (Restart) {\n    \
+             0000  SetEventFlag(target_type = 0, flag = X0_4, state = 1)\n    \
+             0001  1000[03](7) // layers 0x00000004\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_definitions() {
+        assert!(EmevdDefinitions::parse("2003 SetEventFlag").is_err());
+        assert!(EmevdDefinitions::parse("2003[66] SetEventFlag flag:u64").is_err());
+    }
+
+    #[test]
+    fn rejects_negative_argument_offset() {
+        let mut data = sample();
+        // Argument offset of the first instruction.
+        data[0xD0..0xD4].copy_from_slice(&(-1i32).to_le_bytes());
+
+        assert!(matches!(
+            Emevd::parse(&data),
+            Err(FormatError::InvalidData { offset: 0xD0, .. })
+        ));
+    }
+}
@@ -0,0 +1,687 @@
+//! EzState descriptions (ESD). Drive NPC talk scripts and a large part of the character logic.
+//! An ESD is a set of state machines, each state running commands on entry, exit and every
+//! frame it is active. Conditions and command arguments are stored as a small stack based
+//! bytecode that is evaluated by the EzState VM. Commands dispatched by the VM arrive at
+//! [`EzStateEventVmt`](crate::cs::EzStateEventVmt) with the command id as event id.
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use super::{BinaryReader, FormatError, ReadBudget};
+
+/// Offsets in the file are relative to the data section following the header.
+const DATA_START: usize = 0x6C;
+const STATE_GROUP_SIZE: usize = 0x20;
+const STATE_SIZE: usize = 0x48;
+const COMMAND_CALL_SIZE: usize = 0x18;
+const COMMAND_ARG_SIZE: usize = 0x10;
+/// Conditions can reference each other as subconditions. Bail on files nesting deeper than
+/// this to keep the recursion from overflowing the stack. Cyclic offsets are caught by the
+/// [`ReadBudget`].
+const MAX_CONDITION_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Esd {
+    pub name: Option<String>,
+    /// State machines keyed by their ID.
+    pub state_groups: BTreeMap<i64, StateGroup>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StateGroup {
+    pub id: i64,
+    pub states: Vec<State>,
+}
+
+#[derive(Debug, Clone)]
+pub struct State {
+    pub id: i64,
+    /// Transitions out of the state, evaluated in order every frame.
+    pub conditions: Vec<Condition>,
+    pub entry_commands: Vec<CommandCall>,
+    pub exit_commands: Vec<CommandCall>,
+    pub while_commands: Vec<CommandCall>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Condition {
+    /// State to transition to if the condition evaluates to true and none of the
+    /// subconditions do.
+    pub target_state: Option<i64>,
+    /// Commands ran when the condition passes.
+    pub pass_commands: Vec<CommandCall>,
+    pub subconditions: Vec<Condition>,
+    /// Bytecode deciding if this condition passes.
+    pub evaluator: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandCall {
+    pub bank: i32,
+    pub id: i32,
+    /// Bytecode for every argument.
+    pub args: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    LessOrEqual,
+    GreaterOrEqual,
+    Less,
+    Greater,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    fn from_opcode(opcode: u8) -> Option<Self> {
+        Some(match opcode {
+            0x8C => Self::Add,
+            0x8D => Self::Subtract,
+            0x8E => Self::Multiply,
+            0x8F => Self::Divide,
+            0x90 => Self::LessOrEqual,
+            0x91 => Self::GreaterOrEqual,
+            0x92 => Self::Less,
+            0x93 => Self::Greater,
+            0x94 => Self::Equal,
+            0x95 => Self::NotEqual,
+            0x98 => Self::And,
+            0x99 => Self::Or,
+            _ => return None,
+        })
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::LessOrEqual => "<=",
+            Self::GreaterOrEqual => ">=",
+            Self::Less => "<",
+            Self::Greater => ">",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::And => "&&",
+            Self::Or => "||",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single decoded bytecode instruction.
+pub enum Op {
+    PushInt(i32),
+    PushFloat(f32),
+    PushDouble(f64),
+    PushString(String),
+    /// Pops the arguments and then the function ID, pushes the function's return value.
+    Call {
+        arg_count: u8,
+    },
+    Binary(BinaryOperator),
+    Not,
+    /// Aborts evaluation if the popped value is false.
+    StopIfFalse,
+    SetRegister(u8),
+    GetRegister(u8),
+    Unknown(u8),
+}
+
+impl Op {
+    /// Decodes an expression up until its terminating `0xA1`.
+    pub fn decode(bytecode: &[u8]) -> Result<Vec<Op>, FormatError> {
+        let mut reader = BinaryReader::new(bytecode);
+        let mut ops = vec![];
+
+        loop {
+            let opcode = reader.u8()?;
+            ops.push(match opcode {
+                0x00..=0x7F => Op::PushInt(opcode as i32 - 64),
+                0x80 => Op::PushFloat(reader.f32()?),
+                0x81 => Op::PushDouble(f64::from_bits(reader.u64()?)),
+                0x82 => Op::PushInt(reader.i32()?),
+                0x84..=0x8A => Op::Call {
+                    arg_count: opcode - 0x84,
+                },
+                0x9A => Op::Not,
+                0xA1 => break,
+                0xA5 => Op::PushString(reader.utf16()?),
+                0xA6 => Op::StopIfFalse,
+                0xA7..=0xAE => Op::SetRegister(opcode - 0xA7),
+                0xAF..=0xB6 => Op::GetRegister(opcode - 0xAF),
+                _ => BinaryOperator::from_opcode(opcode)
+                    .map(Op::Binary)
+                    .unwrap_or(Op::Unknown(opcode)),
+            });
+        }
+
+        Ok(ops)
+    }
+}
+
+/// Renders an expression as infix text, naming function calls wherever `definitions` knows the
+/// function's ID. Falls back to listing the raw ops if the bytecode doesn't form a single
+/// expression.
+pub fn disassemble_expression(bytecode: &[u8], definitions: &EsdDefinitions) -> String {
+    let ops = match Op::decode(bytecode) {
+        Ok(ops) => ops,
+        Err(e) => return format!("<{e}>"),
+    };
+
+    let mut stack: Vec<String> = vec![];
+    for op in ops.iter() {
+        let value = match op {
+            Op::PushInt(value) => value.to_string(),
+            Op::PushFloat(value) => format!("{value:?}"),
+            Op::PushDouble(value) => format!("{value:?}"),
+            Op::PushString(value) => format!("{value:?}"),
+            Op::Call { arg_count } => {
+                let Some(split) = stack.len().checked_sub(*arg_count as usize + 1) else {
+                    return format!("{ops:?}");
+                };
+
+                let args = stack.split_off(split + 1);
+                let id = stack.pop().unwrap();
+                let name = id
+                    .parse()
+                    .ok()
+                    .and_then(|id| definitions.function(id))
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("f{id}"));
+
+                format!("{name}({})", args.join(", "))
+            }
+            Op::Binary(operator) => {
+                let (Some(right), Some(left)) = (stack.pop(), stack.pop()) else {
+                    return format!("{ops:?}");
+                };
+
+                format!("({left} {} {right})", operator.symbol())
+            }
+            Op::Not => match stack.pop() {
+                Some(value) => format!("!{value}"),
+                None => return format!("{ops:?}"),
+            },
+            Op::StopIfFalse => match stack.pop() {
+                Some(value) => format!("{value} ~"),
+                None => return format!("{ops:?}"),
+            },
+            Op::SetRegister(register) => match stack.pop() {
+                Some(value) => format!("SetREG{register}({value})"),
+                None => return format!("{ops:?}"),
+            },
+            Op::GetRegister(register) => format!("GetREG{register}()"),
+            Op::Unknown(opcode) => format!("<{opcode:#04x}>"),
+        };
+
+        stack.push(value);
+    }
+
+    match stack.len() {
+        1 => stack.pop().unwrap(),
+        _ => format!("{ops:?}"),
+    }
+}
+
+impl Esd {
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = BinaryReader::new(data);
+        reader.magic(b"fsSL")?;
+
+        let version = reader.at(0x8).u32()?;
+        if !(1..=3).contains(&version) {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        let mut reader = reader.at(DATA_START + 0x1C);
+        let state_groups_offset = reader.offset()?;
+        let state_group_count = reader.offset()?;
+        let name_offset = reader.offset()?;
+        let name_length = reader.offset()?;
+
+        let name = match name_length {
+            0 => None,
+            _ => Some(reader.at(DATA_START + name_offset).utf16()?),
+        };
+
+        let budget = ReadBudget::new(data);
+        let mut state_groups = BTreeMap::new();
+        for i in 0..state_group_count {
+            let mut reader = reader.at(DATA_START + state_groups_offset + i * STATE_GROUP_SIZE);
+            let id = reader.i64()?;
+            let states_offset = reader.offset()?;
+            let state_count = reader.offset()?;
+
+            // Conditions refer to their target by offset, so map those back to IDs first.
+            let state_ids = (0..state_count)
+                .map(|i| {
+                    let offset = states_offset + i * STATE_SIZE;
+                    Ok((offset, reader.at(DATA_START + offset).i64()?))
+                })
+                .collect::<Result<HashMap<_, _>, FormatError>>()?;
+
+            let states = (0..state_count)
+                .map(|i| {
+                    Self::read_state(&reader, states_offset + i * STATE_SIZE, &state_ids, &budget)
+                })
+                .collect::<Result<Vec<_>, FormatError>>()?;
+
+            state_groups.insert(id, StateGroup { id, states });
+        }
+
+        Ok(Self { name, state_groups })
+    }
+
+    fn read_state(
+        reader: &BinaryReader,
+        offset: usize,
+        state_ids: &HashMap<usize, i64>,
+        budget: &ReadBudget,
+    ) -> Result<State, FormatError> {
+        let mut reader = reader.at(DATA_START + offset);
+        let id = reader.i64()?;
+        let condition_offsets_offset = reader.offset()?;
+        let condition_count = reader.offset()?;
+        let entry_commands = Self::read_commands(&mut reader)?;
+        let exit_commands = Self::read_commands(&mut reader)?;
+        let while_commands = Self::read_commands(&mut reader)?;
+        let conditions = Self::read_conditions(
+            &reader,
+            condition_offsets_offset,
+            condition_count,
+            state_ids,
+            budget,
+            0,
+        )?;
+
+        Ok(State {
+            id,
+            conditions,
+            entry_commands,
+            exit_commands,
+            while_commands,
+        })
+    }
+
+    fn read_conditions(
+        reader: &BinaryReader,
+        offsets_offset: usize,
+        count: usize,
+        state_ids: &HashMap<usize, i64>,
+        budget: &ReadBudget,
+        depth: usize,
+    ) -> Result<Vec<Condition>, FormatError> {
+        if depth > MAX_CONDITION_DEPTH {
+            return Err(reader.invalid(DATA_START + offsets_offset, "condition nesting too deep"));
+        }
+
+        (0..count)
+            .map(|i| {
+                let offset = reader.at(DATA_START + offsets_offset + i * 8).offset()?;
+                let mut reader = reader.at(DATA_START + offset);
+                budget.take(&reader, 1)?;
+
+                let target_state_offset = reader.i64()?;
+                let pass_commands = Self::read_commands(&mut reader)?;
+                let subcondition_offsets_offset = reader.offset()?;
+                let subcondition_count = reader.offset()?;
+                let evaluator_offset = reader.offset()?;
+                let evaluator_length = reader.offset()?;
+
+                let target_state = match usize::try_from(target_state_offset) {
+                    Ok(offset) => Some(*state_ids.get(&offset).ok_or_else(|| {
+                        reader.invalid(DATA_START + offset, "condition targets unknown state")
+                    })?),
+                    Err(_) => None,
+                };
+
+                Ok(Condition {
+                    target_state,
+                    pass_commands,
+                    subconditions: Self::read_conditions(
+                        &reader,
+                        subcondition_offsets_offset,
+                        subcondition_count,
+                        state_ids,
+                        budget,
+                        depth + 1,
+                    )?,
+                    evaluator: reader
+                        .at(DATA_START + evaluator_offset)
+                        .bytes(evaluator_length)?
+                        .to_vec(),
+                })
+            })
+            .collect()
+    }
+
+    /// Reads an offset and count pair pointing at command calls.
+    fn read_commands(reader: &mut BinaryReader) -> Result<Vec<CommandCall>, FormatError> {
+        let offset = reader.offset()?;
+        let count = reader.offset()?;
+
+        (0..count)
+            .map(|i| {
+                let mut reader = reader.at(DATA_START + offset + i * COMMAND_CALL_SIZE);
+                let bank = reader.i32()?;
+                let id = reader.i32()?;
+                let args_offset = reader.offset()?;
+                let arg_count = reader.offset()?;
+
+                let args = (0..arg_count)
+                    .map(|i| {
+                        let mut reader = reader.at(DATA_START + args_offset + i * COMMAND_ARG_SIZE);
+                        let bytecode_offset = reader.offset()?;
+                        let bytecode_length = reader.offset()?;
+                        Ok(reader
+                            .at(DATA_START + bytecode_offset)
+                            .bytes(bytecode_length)?
+                            .to_vec())
+                    })
+                    .collect::<Result<Vec<_>, FormatError>>()?;
+
+                Ok(CommandCall { bank, id, args })
+            })
+            .collect()
+    }
+
+    /// Renders all state machines as text, naming commands and functions wherever
+    /// `definitions` knows about them.
+    pub fn disassemble(&self, definitions: &EsdDefinitions) -> String {
+        let mut output = String::new();
+        if let Some(name) = &self.name {
+            writeln!(output, "// {name}").unwrap();
+        }
+
+        for group in self.state_groups.values() {
+            writeln!(output, "StateGroup {} {{", group.id).unwrap();
+            for state in group.states.iter() {
+                writeln!(output, "    State {} {{", state.id).unwrap();
+                for (label, commands) in [
+                    ("entry", &state.entry_commands),
+                    ("exit", &state.exit_commands),
+                    ("while", &state.while_commands),
+                ] {
+                    for command in commands.iter() {
+                        writeln!(
+                            output,
+                            "        {label}: {}",
+                            command.disassemble(definitions)
+                        )
+                        .unwrap();
+                    }
+                }
+
+                for condition in state.conditions.iter() {
+                    condition.disassemble_into(definitions, 2, &mut output);
+                }
+                writeln!(output, "    }}").unwrap();
+            }
+            writeln!(output, "}}").unwrap();
+        }
+
+        output
+    }
+}
+
+impl Condition {
+    fn disassemble_into(&self, definitions: &EsdDefinitions, depth: usize, output: &mut String) {
+        let indent = "    ".repeat(depth);
+        write!(
+            output,
+            "{indent}if {}",
+            disassemble_expression(&self.evaluator, definitions)
+        )
+        .unwrap();
+
+        match self.target_state {
+            Some(state) => writeln!(output, " => State {state}").unwrap(),
+            None => output.push('\n'),
+        }
+
+        for command in self.pass_commands.iter() {
+            writeln!(
+                output,
+                "{indent}    pass: {}",
+                command.disassemble(definitions)
+            )
+            .unwrap();
+        }
+
+        for subcondition in self.subconditions.iter() {
+            subcondition.disassemble_into(definitions, depth + 1, output);
+        }
+    }
+}
+
+impl CommandCall {
+    pub fn disassemble(&self, definitions: &EsdDefinitions) -> String {
+        let name = definitions
+            .command(self.bank, self.id)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("c{}_{}", self.bank, self.id));
+
+        let args = self
+            .args
+            .iter()
+            .map(|arg| disassemble_expression(arg, definitions))
+            .collect::<Vec<_>>();
+
+        format!("{name}({})", args.join(", "))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Names for commands and expression functions.
+///
+/// Definitions are read from a line based text file. Commands are written as `bank[id] Name`,
+/// functions as `id Name`. Empty lines and lines starting with `#` are ignored:
+///
+/// ```text
+/// 1[1] TalkToPlayer
+/// 1[10] OpenShop
+/// 5 GetDistanceToPlayer
+/// ```
+pub struct EsdDefinitions {
+    commands: HashMap<(i32, i32), String>,
+    functions: HashMap<i32, String>,
+}
+
+impl EsdDefinitions {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut result = Self::default();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = || {
+                format!(
+                    "line {}: expected `bank[id] Name` or `id Name`",
+                    line_number + 1
+                )
+            };
+            let (id, name) = line.split_once(char::is_whitespace).ok_or_else(error)?;
+            let name = name.trim().to_string();
+
+            match id.strip_suffix(']').and_then(|id| id.split_once('[')) {
+                Some((bank, id)) => {
+                    let bank = bank.parse().map_err(|_| error())?;
+                    let id = id.parse().map_err(|_| error())?;
+                    result.commands.insert((bank, id), name);
+                }
+                None => {
+                    result
+                        .functions
+                        .insert(id.parse().map_err(|_| error())?, name);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn command(&self, bank: i32, id: i32) -> Option<&str> {
+        self.commands.get(&(bank, id)).map(String::as_str)
+    }
+
+    pub fn function(&self, id: i32) -> Option<&str> {
+        self.functions.get(&id).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{disassemble_expression, BinaryOperator, Esd, EsdDefinitions, Op, DATA_START};
+    use crate::formats::FormatError;
+
+    /// `GetDistanceToPlayer() < 2.5`
+    const EVALUATOR: &[u8] = &[0x45, 0x84, 0x80, 0x00, 0x00, 0x20, 0x40, 0x92, 0xA1];
+
+    /// One state group with two states, the first talking to the player and transitioning to
+    /// the second once the player is close enough.
+    fn sample() -> Vec<u8> {
+        let mut data = vec![0u8; 0x6C];
+        data[0..4].copy_from_slice(b"fsSL");
+        data[8..12].copy_from_slice(&3u32.to_le_bytes());
+
+        let mut section = vec![0u8; 0x1C];
+        let varints = |values: &[i64]| {
+            values
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<_>>()
+        };
+
+        let state_groups = 0x4C;
+        let states = state_groups + 0x20;
+        let conditions = states + 2 * 0x48;
+        let condition_offsets = conditions + 0x38;
+        let commands = condition_offsets + 8;
+        let args = commands + 0x18;
+        let bytecode = args + 0x10;
+        let name = bytecode + 0x10;
+
+        section.extend(varints(&[state_groups, 1, name, 4, -1, -1]));
+        section.extend(varints(&[0x7FFFFFFF, states, 2, states]));
+        section.extend(varints(&[0, condition_offsets, 1, commands, 1, 0, 0, 0, 0]));
+        section.extend(varints(&[1, 0, 0, 0, 0, 0, 0, 0, 0]));
+        section.extend(varints(&[
+            states + 0x48,
+            0,
+            0,
+            0,
+            0,
+            bytecode,
+            EVALUATOR.len() as i64,
+        ]));
+        section.extend(varints(&[conditions]));
+        section.extend(1i32.to_le_bytes());
+        section.extend(1i32.to_le_bytes());
+        section.extend(varints(&[args, 1]));
+        section.extend(varints(&[bytecode + 0x9, 6]));
+        section.extend(EVALUATOR);
+        section.extend([0x82, 0x10, 0x27, 0x00, 0x00, 0xA1, 0x00]);
+        assert_eq!(section.len(), name as usize);
+        for unit in "t000".encode_utf16().chain([0]) {
+            section.extend(unit.to_le_bytes());
+        }
+
+        data.extend(section);
+        data
+    }
+
+    #[test]
+    fn decodes_bytecode() {
+        assert_eq!(
+            Op::decode(EVALUATOR).unwrap(),
+            [
+                Op::PushInt(5),
+                Op::Call { arg_count: 0 },
+                Op::PushFloat(2.5),
+                Op::Binary(BinaryOperator::Less),
+            ]
+        );
+        assert!(Op::decode(&[0x82, 0x00]).is_err());
+    }
+
+    #[test]
+    fn disassembles_expressions() {
+        let definitions = EsdDefinitions::parse("5 GetDistanceToPlayer").unwrap();
+        assert_eq!(
+            disassemble_expression(EVALUATOR, &definitions),
+            "(GetDistanceToPlayer() < 2.5)"
+        );
+        assert_eq!(
+            disassemble_expression(EVALUATOR, &EsdDefinitions::default()),
+            "(f5() < 2.5)"
+        );
+    }
+
+    #[test]
+    fn parses_and_disassembles_state_groups() {
+        let esd = Esd::parse(&sample()).unwrap();
+        assert_eq!(esd.name.as_deref(), Some("t000"));
+
+        let group = &esd.state_groups[&0x7FFFFFFF];
+        assert_eq!(group.states.len(), 2);
+        assert_eq!(group.states[0].conditions[0].target_state, Some(1));
+        assert_eq!(group.states[0].entry_commands[0].args.len(), 1);
+
+        let definitions =
+            EsdDefinitions::parse("1[1] TalkToPlayer\n5 GetDistanceToPlayer").unwrap();
+        assert_eq!(
+            esd.disassemble(&definitions),
+            "// t000\n\
+             StateGroup 2147483647 {\n    \
+             State 0 {\n        \
+             entry: TalkToPlayer(10000)\n        \
+             if (GetDistanceToPlayer() < 2.5) => State 1\n    \
+             }\n    \
+             State 1 {\n    \
+             }\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn rejects_condition_fan_out() {
+        let mut data = sample();
+        // A chain of conditions that each list the next one twice. The tree they describe is
+        // 2^40 conditions large and never nests deep enough to trip the depth limit.
+        let chain = 40;
+        let size = 0x38 + 0x10;
+        let start = data.len() - DATA_START;
+        for i in 0..chain {
+            let list = (start + i * size + 0x38) as i64;
+            let next = (start + (i + 1) * size) as i64;
+            let count = if i + 1 < chain { 2 } else { 0 };
+            for value in [-1, 0, 0, list, count, 0, 0, next, next] {
+                data.extend(value.to_le_bytes());
+            }
+        }
+
+        // The first state's condition starts the chain.
+        let condition = DATA_START + 0x4C + 0x20 + 2 * 0x48;
+        let list = (start + 0x38) as i64;
+        data[condition + 0x18..condition + 0x20].copy_from_slice(&list.to_le_bytes());
+        data[condition + 0x20..condition + 0x28].copy_from_slice(&2i64.to_le_bytes());
+
+        assert!(matches!(
+            Esd::parse(&data),
+            Err(FormatError::InvalidData { .. })
+        ));
+    }
+}
@@ -0,0 +1,639 @@
+//! FXR effect files, the effects loaded by [`crate::gxffx::GXFfxGraphicsResourceManager`].
+//!
+//! An effect is a tree of containers. Each container holds effects, which in turn hold actions.
+//! Actions do the actual work (emitting particles, drawing sprites, playing sounds) and are
+//! configured through a table of raw fields and a list of animated properties. What the fields of
+//! an action mean depends on its type, so finding the textures and child effects an FXR references
+//! needs [`ActionDefinitions`].
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{BinaryReader, FormatError, ReadBudget};
+
+const MAGIC: &[u8] = b"FXR\0";
+/// Containers nest arbitrarily deep in theory. Real files stay well below this, it only keeps
+/// the recursion from overflowing the stack. Cyclic offsets are caught by the [`ReadBudget`].
+const MAX_CONTAINER_DEPTH: usize = 64;
+/// Same as [`MAX_CONTAINER_DEPTH`] for properties and the modifiers nested in them.
+const MAX_PROPERTY_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Fxr {
+    /// 4 for Dark Souls 3, 5 for Sekiro and Elden Ring.
+    pub version: u16,
+    pub id: i32,
+    pub root: Container,
+    /// IDs of other effects this effect spawns. Only present on version 5 files.
+    pub referenced_effects: Vec<i32>,
+    pub unk_section13: Vec<i32>,
+    pub unk_section14: Vec<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Container {
+    pub container_type: i16,
+    pub children: Vec<Container>,
+    pub effects: Vec<Effect>,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Effect {
+    pub effect_type: i16,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub action_type: i16,
+    pub unk02: u8,
+    pub unk03: u8,
+    pub unk04: i32,
+    /// Both field tables of the action, the first one followed by the second one.
+    pub fields: Vec<Field>,
+    pub field_groups: Vec<Vec<Field>>,
+    pub properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Property {
+    /// Number of components, 1 for scalars up to 4 for colors.
+    pub components: u8,
+    /// How the property is animated, e.g. constant, stepped or linearly interpolated.
+    pub function: u8,
+    pub fields: Vec<Field>,
+    pub modifiers: Vec<Modifier>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Modifier {
+    pub modifier_type: u16,
+    pub unk04: i32,
+    pub fields: Vec<Field>,
+    pub properties: Vec<Property>,
+}
+
+/// A raw 4-byte field. The file doesn't say whether a field holds an int or a float.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field(pub u32);
+
+impl Field {
+    pub fn as_i32(&self) -> i32 {
+        self.0 as i32
+    }
+
+    pub fn as_f32(&self) -> f32 {
+        f32::from_bits(self.0)
+    }
+}
+
+impl Fxr {
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = BinaryReader::new(data);
+        reader.magic(MAGIC)?;
+        reader.skip(2);
+
+        let version = reader.u16()?;
+        if version != 4 && version != 5 {
+            return Err(FormatError::UnsupportedVersion(version as u32));
+        }
+
+        reader.skip(4);
+        let id = reader.i32()?;
+
+        // Sections 1 through 11 as (offset, count) pairs. Only the containers are needed to walk
+        // the tree, everything below them is reached through the containers' own offsets.
+        let mut sections = [(0usize, 0usize); 11];
+        for section in sections.iter_mut() {
+            *section = (reader.offset32()?, reader.offset32()?);
+        }
+        reader.skip(8);
+
+        let (containers_offset, containers_count) = sections[3];
+        if containers_count == 0 {
+            return Err(reader.invalid(0x28, "effect has no containers"));
+        }
+        let budget = ReadBudget::new(data);
+        let root = Container::read(&mut reader.at(containers_offset), &budget, 0)?;
+
+        let (referenced_effects, unk_section13, unk_section14) = if version == 5 {
+            let mut lists = [vec![], vec![], vec![]];
+            for list in lists.iter_mut() {
+                let offset = reader.offset32()?;
+                let count = reader.offset32()?;
+                *list = read_list(&mut reader.at(offset), count, |r| r.i32())?;
+            }
+
+            let [a, b, c] = lists;
+            (a, b, c)
+        } else {
+            (vec![], vec![], vec![])
+        };
+
+        Ok(Self {
+            version,
+            id,
+            root,
+            referenced_effects,
+            unk_section13,
+            unk_section14,
+        })
+    }
+
+    /// Every action in the effect, in tree order.
+    pub fn actions(&self) -> Vec<&Action> {
+        let mut actions = vec![];
+        self.root.collect_actions(&mut actions);
+        actions
+    }
+
+    /// Collects the textures, models and child effects referenced by this effect's actions. Actions
+    /// without a definition are skipped.
+    pub fn references(&self, definitions: &ActionDefinitions) -> References {
+        let mut references = References {
+            effects: self.referenced_effects.iter().copied().collect(),
+            ..Default::default()
+        };
+
+        for action in self.actions() {
+            let Some(definition) = definitions.actions.get(&action.action_type) else {
+                continue;
+            };
+
+            for (index, kind) in definition.references.iter() {
+                let Some(field) = action.fields.get(*index) else {
+                    continue;
+                };
+
+                // Unset references are stored as 0.
+                let id = field.as_i32();
+                if id == 0 {
+                    continue;
+                }
+
+                match kind {
+                    ReferenceKind::Texture => references.textures.insert(id),
+                    ReferenceKind::Model => references.models.insert(id),
+                    ReferenceKind::Effect => references.effects.insert(id),
+                };
+            }
+        }
+
+        references
+    }
+}
+
+impl Container {
+    fn read(
+        reader: &mut BinaryReader,
+        budget: &ReadBudget,
+        depth: usize,
+    ) -> Result<Self, FormatError> {
+        if depth > MAX_CONTAINER_DEPTH {
+            return Err(reader.invalid(reader.position(), "containers nested too deep"));
+        }
+        budget.take(reader, 1)?;
+
+        let container_type = reader.i16()?;
+        reader.skip(6);
+        let effect_count = reader.offset32()?;
+        let action_count = reader.offset32()?;
+        let child_count = reader.offset32()?;
+        reader.skip(4);
+        let effects_offset = reader.offset()?;
+        let actions_offset = reader.offset()?;
+        let children_offset = reader.offset()?;
+
+        let mut children_reader = reader.at(children_offset);
+        Ok(Self {
+            container_type,
+            children: (0..child_count)
+                .map(|_| Self::read(&mut children_reader, budget, depth + 1))
+                .collect::<Result<_, _>>()?,
+            effects: read_list(&mut reader.at(effects_offset), effect_count, |reader| {
+                Effect::read(reader, budget)
+            })?,
+            actions: read_list(&mut reader.at(actions_offset), action_count, |reader| {
+                Action::read(reader, budget)
+            })?,
+        })
+    }
+
+    fn collect_actions<'a>(&'a self, actions: &mut Vec<&'a Action>) {
+        actions.extend(self.actions.iter());
+        for effect in self.effects.iter() {
+            actions.extend(effect.actions.iter());
+        }
+        for child in self.children.iter() {
+            child.collect_actions(actions);
+        }
+    }
+}
+
+impl Effect {
+    fn read(reader: &mut BinaryReader, budget: &ReadBudget) -> Result<Self, FormatError> {
+        budget.take(reader, 1)?;
+        let effect_type = reader.i16()?;
+        reader.skip(10);
+        let action_count = reader.offset32()?;
+        let actions_offset = reader.offset()?;
+        reader.skip(8);
+
+        Ok(Self {
+            effect_type,
+            actions: read_list(&mut reader.at(actions_offset), action_count, |reader| {
+                Action::read(reader, budget)
+            })?,
+        })
+    }
+}
+
+impl Action {
+    fn read(reader: &mut BinaryReader, budget: &ReadBudget) -> Result<Self, FormatError> {
+        budget.take(reader, 1)?;
+        let action_type = reader.i16()?;
+        let unk02 = reader.u8()?;
+        let unk03 = reader.u8()?;
+        let unk04 = reader.i32()?;
+        let field_count1 = reader.offset32()?;
+        let field_group_count = reader.offset32()?;
+        let property_count1 = reader.offset32()?;
+        let field_count2 = reader.offset32()?;
+        reader.skip(4);
+        let property_count2 = reader.offset32()?;
+        let field_count = reader.add_offsets(&[field_count1, field_count2])?;
+        let property_count = reader.add_offsets(&[property_count1, property_count2])?;
+        let fields_offset = reader.offset()?;
+        let field_groups_offset = reader.offset()?;
+        let properties_offset = reader.offset()?;
+        reader.skip(8);
+
+        let field_groups = read_list(
+            &mut reader.at(field_groups_offset),
+            field_group_count,
+            |reader| {
+                let offset = reader.offset()?;
+                let count = reader.offset32()?;
+                reader.skip(4);
+                read_fields(&mut reader.at(offset), budget, count)
+            },
+        )?;
+
+        Ok(Self {
+            action_type,
+            unk02,
+            unk03,
+            unk04,
+            fields: read_fields(&mut reader.at(fields_offset), budget, field_count)?,
+            field_groups,
+            properties: read_list(
+                &mut reader.at(properties_offset),
+                property_count,
+                |reader| Property::read(reader, budget, 0),
+            )?,
+        })
+    }
+}
+
+impl Property {
+    fn read(
+        reader: &mut BinaryReader,
+        budget: &ReadBudget,
+        depth: usize,
+    ) -> Result<Self, FormatError> {
+        if depth > MAX_PROPERTY_DEPTH {
+            return Err(reader.invalid(reader.position(), "properties nested too deep"));
+        }
+        budget.take(reader, 1)?;
+
+        let type_enum = reader.u16()?;
+        reader.skip(6);
+        let field_count = reader.offset32()?;
+        reader.skip(4);
+        let fields_offset = reader.offset()?;
+        reader.skip(8);
+        let modifiers_offset = reader.offset()?;
+        let modifier_count = reader.offset32()?;
+        reader.skip(4);
+
+        Ok(Self {
+            components: (type_enum & 0b11) as u8 + 1,
+            function: (type_enum >> 4) as u8,
+            fields: read_fields(&mut reader.at(fields_offset), budget, field_count)?,
+            modifiers: read_list(&mut reader.at(modifiers_offset), modifier_count, |reader| {
+                Modifier::read(reader, budget, depth + 1)
+            })?,
+        })
+    }
+}
+
+impl Modifier {
+    fn read(
+        reader: &mut BinaryReader,
+        budget: &ReadBudget,
+        depth: usize,
+    ) -> Result<Self, FormatError> {
+        budget.take(reader, 1)?;
+        let modifier_type = reader.u16()?;
+        reader.skip(2);
+        let unk04 = reader.i32()?;
+        let field_count = reader.offset32()?;
+        let property_count = reader.offset32()?;
+        let fields_offset = reader.offset()?;
+        reader.skip(8);
+        let properties_offset = reader.offset()?;
+        reader.skip(8);
+
+        Ok(Self {
+            modifier_type,
+            unk04,
+            fields: read_fields(&mut reader.at(fields_offset), budget, field_count)?,
+            properties: read_list(
+                &mut reader.at(properties_offset),
+                property_count,
+                |reader| Property::read(reader, budget, depth + 1),
+            )?,
+        })
+    }
+}
+
+fn read_list<'a, T>(
+    reader: &mut BinaryReader<'a>,
+    count: usize,
+    read: impl Fn(&mut BinaryReader<'a>) -> Result<T, FormatError>,
+) -> Result<Vec<T>, FormatError> {
+    (0..count).map(|_| read(reader)).collect()
+}
+
+fn read_fields(
+    reader: &mut BinaryReader,
+    budget: &ReadBudget,
+    count: usize,
+) -> Result<Vec<Field>, FormatError> {
+    budget.take(reader, count)?;
+    read_list(reader, count, |r| Ok(Field(r.u32()?)))
+}
+
+/// Resources referenced by an effect.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct References {
+    pub textures: BTreeSet<i32>,
+    pub models: BTreeSet<i32>,
+    pub effects: BTreeSet<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Texture,
+    Model,
+    Effect,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActionDefinition {
+    pub name: String,
+    /// Indices into [`Action::fields`] that hold resource IDs.
+    pub references: Vec<(usize, ReferenceKind)>,
+}
+
+/// Names for action types and the fields within them that reference other resources.
+#[derive(Debug, Default, Clone)]
+pub struct ActionDefinitions {
+    pub actions: BTreeMap<i16, ActionDefinition>,
+}
+
+impl ActionDefinitions {
+    /// Parses definitions written one per line as `type Name index:kind...`, where kind is one of
+    /// `texture`, `model` or `effect`. Empty lines and lines starting with `#` are skipped.
+    ///
+    /// ```text
+    /// 600 PointSprite 0:texture
+    /// 609 Model 0:model
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut actions = BTreeMap::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: String| format!("line {}: {reason}", line_number + 1);
+            let mut parts = line.split_whitespace();
+            let action_type = parts
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| error("expected action type".to_string()))?;
+            let name = parts
+                .next()
+                .ok_or_else(|| error("expected action name".to_string()))?
+                .to_string();
+
+            let references = parts
+                .map(|part| {
+                    let invalid = || error(format!("invalid reference {part}"));
+                    let (index, kind) = part.split_once(':').ok_or_else(invalid)?;
+                    let index = index.parse().map_err(|_| invalid())?;
+                    let kind = match kind {
+                        "texture" => ReferenceKind::Texture,
+                        "model" => ReferenceKind::Model,
+                        "effect" => ReferenceKind::Effect,
+                        _ => return Err(invalid()),
+                    };
+
+                    Ok((index, kind))
+                })
+                .collect::<Result<_, _>>()?;
+
+            actions.insert(action_type, ActionDefinition { name, references });
+        }
+
+        Ok(Self { actions })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ActionDefinitions, Fxr};
+    use crate::formats::{BinaryWriter, FormatError};
+
+    /// Writes an effect with a root container holding one child container, whose effect has a
+    /// single sprite action with an animated color property.
+    fn sample() -> Vec<u8> {
+        fn seek(writer: &mut BinaryWriter, position: usize) {
+            writer.bytes(&vec![0; position - writer.position()]);
+        }
+
+        let mut writer = BinaryWriter::default();
+        writer.bytes(b"FXR\0");
+        writer.i16(0);
+        writer.u16(5);
+        writer.i32(1);
+        writer.i32(302_100);
+        // Section table, only the container section is used.
+        for (offset, count) in [(0, 1), (0, 0), (0, 0), (0xA0, 2)] {
+            writer.i32(offset);
+            writer.i32(count);
+        }
+        for _ in 4..11 {
+            writer.i32(0);
+            writer.i32(0);
+        }
+        writer.i32(1);
+        writer.i32(0);
+        // Sections 12 to 14.
+        writer.i32(0x240);
+        writer.i32(1);
+        writer.i32(0);
+        writer.i32(0);
+        writer.i32(0);
+        writer.i32(0);
+        writer.i32(0);
+        writer.i32(0);
+        seek(&mut writer, 0xA0);
+
+        let container = |writer: &mut BinaryWriter, effects: (i32, i64), children: (i32, i64)| {
+            writer.i16(2000);
+            writer.bytes(&[0, 1, 0, 0, 0, 0]);
+            writer.i32(effects.0);
+            writer.i32(0);
+            writer.i32(children.0);
+            writer.i32(0);
+            writer.i64(effects.1);
+            writer.i64(0);
+            writer.i64(children.1);
+        };
+
+        // Root at 0xA0 and its child at 0xD0.
+        container(&mut writer, (0, 0), (1, 0xD0));
+        container(&mut writer, (1, 0x100), (0, 0));
+
+        // Effect at 0x100.
+        writer.i16(1002);
+        writer.bytes(&[0; 10]);
+        writer.i32(1);
+        writer.i64(0x120);
+        writer.i64(0);
+
+        // Action at 0x120.
+        writer.i16(600);
+        writer.u8(0);
+        writer.u8(1);
+        writer.i32(0);
+        writer.i32(2);
+        writer.i32(0);
+        writer.i32(1);
+        writer.i32(1);
+        writer.i32(0);
+        writer.i32(0);
+        writer.i64(0x180);
+        writer.i64(0);
+        writer.i64(0x1A0);
+        writer.i64(0);
+
+        // Fields at 0x180: texture 12345, a float and the second table's value.
+        seek(&mut writer, 0x180);
+        writer.i32(12345);
+        writer.f32(0.5);
+        writer.i32(7);
+
+        // Property at 0x1A0, a linear RGBA color with two keyframes.
+        seek(&mut writer, 0x1A0);
+        writer.u16(4 << 4 | 3);
+        writer.bytes(&[0; 6]);
+        writer.i32(2);
+        writer.i32(0);
+        writer.i64(0x200);
+        writer.i64(0);
+        writer.i64(0);
+        writer.i32(0);
+        writer.i32(0);
+        seek(&mut writer, 0x200);
+        writer.f32(0.0);
+        writer.f32(1.0);
+
+        // Referenced effects.
+        seek(&mut writer, 0x240);
+        writer.i32(302_101);
+
+        writer.into_inner()
+    }
+
+    #[test]
+    fn parses_tree() {
+        let fxr = Fxr::parse(&sample()).unwrap();
+        assert_eq!(fxr.id, 302_100);
+        assert_eq!(fxr.root.children.len(), 1);
+
+        let actions = fxr.actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].action_type, 600);
+        assert_eq!(actions[0].fields.len(), 3);
+        assert_eq!(actions[0].fields[1].as_f32(), 0.5);
+
+        let property = &actions[0].properties[0];
+        assert_eq!((property.components, property.function), (4, 4));
+        assert_eq!(property.fields[1].as_f32(), 1.0);
+    }
+
+    #[test]
+    fn collects_references() {
+        let fxr = Fxr::parse(&sample()).unwrap();
+        let definitions = ActionDefinitions::parse("# sprites\n600 PointSprite 0:texture").unwrap();
+
+        let references = fxr.references(&definitions);
+        assert_eq!(references.textures.into_iter().collect::<Vec<_>>(), [12345]);
+        assert_eq!(
+            references.effects.into_iter().collect::<Vec<_>>(),
+            [302_101]
+        );
+        assert!(references.models.is_empty());
+    }
+
+    #[test]
+    fn rejects_cyclic_modifiers() {
+        fn write(data: &mut [u8], offset: usize, bytes: &[u8]) {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        let mut data = sample();
+        // The property at 0x1A0 gets a modifier at 0x210 that refers back to the property.
+        write(&mut data, 0x1C0, &0x210i64.to_le_bytes());
+        write(&mut data, 0x1C8, &1i32.to_le_bytes());
+        write(&mut data, 0x21C, &1i32.to_le_bytes());
+        write(&mut data, 0x230, &0x1A0i64.to_le_bytes());
+
+        assert!(matches!(
+            Fxr::parse(&data),
+            Err(FormatError::InvalidData { .. })
+        ));
+
+        // A chain of properties that each have two modifiers pointing at the next property. The
+        // tree they describe is 2^30 properties large and stays within the depth limit.
+        let chain = 30;
+        let start = data.len();
+        for i in 0..chain {
+            let modifiers = (start + i * 0x90 + 0x30) as i64;
+            let next = (start + (i + 1) * 0x90) as i64;
+            let count: i32 = if i + 1 < chain { 2 } else { 0 };
+
+            data.extend([0; 0x20]);
+            data.extend(modifiers.to_le_bytes());
+            data.extend(count.to_le_bytes());
+            data.extend(0i32.to_le_bytes());
+            for _ in 0..2 {
+                data.extend([0; 0xC]);
+                data.extend(1i32.to_le_bytes());
+                data.extend([0; 0x10]);
+                data.extend(next.to_le_bytes());
+                data.extend([0; 8]);
+            }
+        }
+        write(&mut data, 0x1C0, &((start + 0x30) as i64).to_le_bytes());
+        write(&mut data, 0x1C8, &2i32.to_le_bytes());
+        assert!(matches!(
+            Fxr::parse(&data),
+            Err(FormatError::InvalidData { .. })
+        ));
+    }
+}
@@ -0,0 +1,1006 @@
+//! Graphics parameter (GPARAM) files. Hold the lighting, fog, color grading and other scene draw
+//! settings that [`CSWorldSceneDrawParamManager`](crate::cs::CSWorldSceneDrawParamManager)
+//! blends between. Every field holds one or more keyframes, keyed by an ID and the time of day
+//! at which it applies.
+//!
+//! Besides the binary format this module reads and writes a line based text representation so
+//! that files can be edited by hand and rebuilt:
+//!
+//! ```text
+//! gparam 5 unk0d=false unk14=0 unk18=0x54 unk50=0.0
+//! group "LightSet ver2" "LightSet"
+//! comment "Sunset"
+//! field "Directional Light Color" "DirLightColor" color
+//! key 0 18.5 255 200 180 255
+//! ```
+use std::{fmt::Write, str::FromStr};
+
+use super::{BinaryReader, BinaryWriter, FormatError};
+
+const MAGIC: &[u8] = b"f\0i\0l\0t\0";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gparam {
+    /// 3 for Dark Souls 3, 5 for Sekiro and Elden Ring.
+    pub version: u32,
+    pub groups: Vec<Group>,
+    unk0d: bool,
+    unk14: i32,
+    unk18: i32,
+    unk2: Vec<u8>,
+    unk3: Vec<Unk3>,
+    unk50: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name1: String,
+    pub name2: String,
+    pub fields: Vec<Field>,
+    /// Describes the keyframes of the group, usually names for the time of day.
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name1: String,
+    pub name2: String,
+    pub value_type: ValueType,
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    pub id: i32,
+    /// Hour of the day at which this keyframe applies. Always 0 on version 3 files.
+    pub time_of_day: f32,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Unk3 {
+    group_index: i32,
+    unk0c: i32,
+    value_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ValueType {
+    Byte = 0x1,
+    Short = 0x2,
+    IntA = 0x3,
+    BoolA = 0x5,
+    IntB = 0x7,
+    Float = 0x9,
+    BoolB = 0xB,
+    Float2 = 0xC,
+    Float3 = 0xD,
+    Float4 = 0xE,
+    Color = 0xF,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Byte(i8),
+    Short(i16),
+    IntA(i32),
+    BoolA(bool),
+    IntB(i32),
+    Float(f32),
+    BoolB(bool),
+    Float2([f32; 2]),
+    Float3([f32; 3]),
+    Float4([f32; 4]),
+    /// RGBA color.
+    Color([u8; 4]),
+}
+
+impl ValueType {
+    const ALL: [ValueType; 11] = [
+        Self::Byte,
+        Self::Short,
+        Self::IntA,
+        Self::BoolA,
+        Self::IntB,
+        Self::Float,
+        Self::BoolB,
+        Self::Float2,
+        Self::Float3,
+        Self::Float4,
+        Self::Color,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Byte => "byte",
+            Self::Short => "short",
+            Self::IntA => "int_a",
+            Self::BoolA => "bool_a",
+            Self::IntB => "int_b",
+            Self::Float => "float",
+            Self::BoolB => "bool_b",
+            Self::Float2 => "float2",
+            Self::Float3 => "float3",
+            Self::Float4 => "float4",
+            Self::Color => "color",
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| *t as u8 == value)
+    }
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Self::Byte(_) => ValueType::Byte,
+            Self::Short(_) => ValueType::Short,
+            Self::IntA(_) => ValueType::IntA,
+            Self::BoolA(_) => ValueType::BoolA,
+            Self::IntB(_) => ValueType::IntB,
+            Self::Float(_) => ValueType::Float,
+            Self::BoolB(_) => ValueType::BoolB,
+            Self::Float2(_) => ValueType::Float2,
+            Self::Float3(_) => ValueType::Float3,
+            Self::Float4(_) => ValueType::Float4,
+            Self::Color(_) => ValueType::Color,
+        }
+    }
+
+    fn read(reader: &mut BinaryReader, value_type: ValueType) -> Result<Self, FormatError> {
+        Ok(match value_type {
+            ValueType::Byte => Self::Byte(reader.i8()?),
+            ValueType::Short => Self::Short(reader.i16()?),
+            ValueType::IntA => Self::IntA(reader.i32()?),
+            ValueType::BoolA => Self::BoolA(reader.u8()? != 0),
+            ValueType::IntB => Self::IntB(reader.i32()?),
+            ValueType::Float => Self::Float(reader.f32()?),
+            ValueType::BoolB => Self::BoolB(reader.u8()? != 0),
+            ValueType::Float2 => Self::Float2([reader.f32()?, reader.f32()?]),
+            ValueType::Float3 => Self::Float3([reader.f32()?, reader.f32()?, reader.f32()?]),
+            ValueType::Float4 => {
+                Self::Float4([reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?])
+            }
+            ValueType::Color => Self::Color(reader.bytes(4)?.try_into().unwrap()),
+        })
+    }
+
+    fn write(&self, writer: &mut BinaryWriter) {
+        match self {
+            Self::Byte(v) => writer.i8(*v),
+            Self::Short(v) => writer.i16(*v),
+            Self::IntA(v) | Self::IntB(v) => writer.i32(*v),
+            Self::BoolA(v) | Self::BoolB(v) => writer.u8(*v as u8),
+            Self::Float(v) => writer.f32(*v),
+            Self::Float2(v) => v.iter().for_each(|f| writer.f32(*f)),
+            Self::Float3(v) => v.iter().for_each(|f| writer.f32(*f)),
+            Self::Float4(v) => v.iter().for_each(|f| writer.f32(*f)),
+            Self::Color(v) => writer.bytes(v),
+        }
+    }
+
+    fn parse(value_type: ValueType, tokens: &[String]) -> Result<Self, String> {
+        fn parse<T: FromStr>(tokens: &[String], count: usize) -> Result<Vec<T>, String> {
+            if tokens.len() != count {
+                return Err(format!("expected {count} values, found {}", tokens.len()));
+            }
+
+            tokens
+                .iter()
+                .map(|t| t.parse().map_err(|_| format!("invalid value {t}")))
+                .collect()
+        }
+
+        Ok(match value_type {
+            ValueType::Byte => Self::Byte(parse(tokens, 1)?[0]),
+            ValueType::Short => Self::Short(parse(tokens, 1)?[0]),
+            ValueType::IntA => Self::IntA(parse(tokens, 1)?[0]),
+            ValueType::BoolA => Self::BoolA(parse(tokens, 1)?[0]),
+            ValueType::IntB => Self::IntB(parse(tokens, 1)?[0]),
+            ValueType::Float => Self::Float(parse(tokens, 1)?[0]),
+            ValueType::BoolB => Self::BoolB(parse(tokens, 1)?[0]),
+            ValueType::Float2 => Self::Float2(parse(tokens, 2)?.try_into().unwrap()),
+            ValueType::Float3 => Self::Float3(parse(tokens, 3)?.try_into().unwrap()),
+            ValueType::Float4 => Self::Float4(parse(tokens, 4)?.try_into().unwrap()),
+            ValueType::Color => Self::Color(parse(tokens, 4)?.try_into().unwrap()),
+        })
+    }
+
+    fn format_into(&self, output: &mut String) {
+        fn floats(output: &mut String, values: &[f32]) {
+            for (i, value) in values.iter().enumerate() {
+                if i != 0 {
+                    output.push(' ');
+                }
+                write!(output, "{value:?}").unwrap();
+            }
+        }
+
+        match self {
+            Self::Byte(v) => write!(output, "{v}").unwrap(),
+            Self::Short(v) => write!(output, "{v}").unwrap(),
+            Self::IntA(v) | Self::IntB(v) => write!(output, "{v}").unwrap(),
+            Self::BoolA(v) | Self::BoolB(v) => write!(output, "{v}").unwrap(),
+            Self::Float(v) => write!(output, "{v:?}").unwrap(),
+            Self::Float2(v) => floats(output, v),
+            Self::Float3(v) => floats(output, v),
+            Self::Float4(v) => floats(output, v),
+            Self::Color([r, g, b, a]) => write!(output, "{r} {g} {b} {a}").unwrap(),
+        }
+    }
+}
+
+impl Gparam {
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = BinaryReader::new(data);
+        reader.magic(MAGIC)?;
+
+        let version = reader.u32()?;
+        if version != 3 && version != 5 {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        reader.skip(1);
+        let unk0d = reader.u8()? != 0;
+        reader.skip(2);
+        let group_count = reader.offset32()?;
+        let unk14 = reader.i32()?;
+        let unk18 = reader.i32()?;
+
+        let group_header_offsets = reader.offset32()?;
+        let group_headers = reader.offset32()?;
+        let param_header_offsets = reader.offset32()?;
+        let param_headers = reader.offset32()?;
+        let values = reader.offset32()?;
+        let value_ids = reader.offset32()?;
+        let unk2_offset = reader.offset32()?;
+        let unk3_count = reader.offset32()?;
+        let unk3_offset = reader.offset32()?;
+        let unk3_value_ids = reader.offset32()?;
+        reader.skip(4);
+        let comment_offsets_offsets = reader.offset32()?;
+        let comment_offsets = reader.offset32()?;
+        let comments_position = reader.position();
+        let comments = reader.offset32()?;
+        let unk50 = match version {
+            5 => reader.f32()?,
+            _ => 0.0,
+        };
+
+        let value_id_size = if version == 5 { 8 } else { 4 };
+        let unk3_size = if version == 5 { 0x10 } else { 0xC };
+
+        let mut groups = (0..group_count)
+            .map(|i| {
+                let offset = reader
+                    .at(reader.add_offsets(&[group_header_offsets, i * 4])?)
+                    .offset32()?;
+                let mut reader = reader.at(reader.add_offsets(&[group_headers, offset])?);
+                let field_count = reader.offset32()?;
+                let field_offsets_offset = reader.offset32()?;
+                let name1 = reader.utf16()?;
+                let name2 = reader.utf16()?;
+
+                let fields = (0..field_count)
+                    .map(|i| {
+                        let offset = reader
+                            .at(reader.add_offsets(&[
+                                param_header_offsets,
+                                field_offsets_offset,
+                                i * 4,
+                            ])?)
+                            .offset32()?;
+
+                        let mut reader = reader.at(reader.add_offsets(&[param_headers, offset])?);
+                        let values_offset = reader.offset32()?;
+                        let value_ids_offset = reader.offset32()?;
+                        let type_position = reader.position();
+                        let value_type = ValueType::from_u8(reader.u8()?)
+                            .ok_or_else(|| reader.invalid(type_position, "unknown value type"))?;
+                        let count = reader.u8()? as usize;
+                        reader.skip(2);
+                        let name1 = reader.utf16()?;
+                        let name2 = reader.utf16()?;
+
+                        let mut values_reader =
+                            reader.at(reader.add_offsets(&[values, values_offset])?);
+                        let keyframes = (0..count)
+                            .map(|i| {
+                                let mut reader = reader.at(reader.add_offsets(&[
+                                    value_ids,
+                                    value_ids_offset,
+                                    i * value_id_size,
+                                ])?);
+                                let id = reader.i32()?;
+                                let time_of_day = match version {
+                                    5 => reader.f32()?,
+                                    _ => 0.0,
+                                };
+
+                                Ok(Keyframe {
+                                    id,
+                                    time_of_day,
+                                    value: Value::read(&mut values_reader, value_type)?,
+                                })
+                            })
+                            .collect::<Result<Vec<_>, FormatError>>()?;
+
+                        Ok(Field {
+                            name1,
+                            name2,
+                            value_type,
+                            keyframes,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, FormatError>>()?;
+
+                Ok(Group {
+                    name1,
+                    name2,
+                    fields,
+                    comments: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>, FormatError>>()?;
+
+        // Comment counts aren't stored, they follow from the distance between the offsets.
+        let comments_length = comments
+            .checked_sub(comment_offsets)
+            .ok_or_else(|| reader.invalid(comments_position, "comments precede their offsets"))?;
+        let comment_starts = (0..group_count)
+            .map(|i| {
+                reader
+                    .at(reader.add_offsets(&[comment_offsets_offsets, i * 4])?)
+                    .offset32()
+            })
+            .chain([Ok(comments_length)])
+            .collect::<Result<Vec<_>, FormatError>>()?;
+        for (i, group) in groups.iter_mut().enumerate() {
+            let count = comment_starts[i + 1].saturating_sub(comment_starts[i]) / 4;
+            group.comments = (0..count)
+                .map(|j| {
+                    let offset = reader
+                        .at(reader.add_offsets(&[comment_offsets, comment_starts[i], j * 4])?)
+                        .offset32()?;
+                    reader.at(reader.add_offsets(&[comments, offset])?).utf16()
+                })
+                .collect::<Result<Vec<_>, FormatError>>()?;
+        }
+
+        let unk3 = (0..unk3_count)
+            .map(|i| {
+                let mut reader = reader.at(reader.add_offsets(&[unk3_offset, i * unk3_size])?);
+                let group_index = reader.i32()?;
+                let count = reader.offset32()?;
+                let ids_offset = reader.offset32()?;
+                let unk0c = match version {
+                    5 => reader.i32()?,
+                    _ => 0,
+                };
+
+                let mut reader = reader.at(reader.add_offsets(&[unk3_value_ids, ids_offset])?);
+                Ok(Unk3 {
+                    group_index,
+                    unk0c,
+                    value_ids: (0..count)
+                        .map(|_| reader.i32())
+                        .collect::<Result<Vec<_>, FormatError>>()?,
+                })
+            })
+            .collect::<Result<Vec<_>, FormatError>>()?;
+
+        let unk2 = reader
+            .at(unk2_offset)
+            .bytes(unk3_offset.saturating_sub(unk2_offset))?
+            .to_vec();
+
+        Ok(Self {
+            version,
+            groups,
+            unk0d,
+            unk14,
+            unk18,
+            unk2,
+            unk3,
+            unk50,
+        })
+    }
+
+    /// Serializes the GPARAM back into its binary form. Fails if a field holds more than 255
+    /// keyframes or keyframes that don't match the field's value type.
+    pub fn write(&self) -> Result<Vec<u8>, FormatError> {
+        let mut writer = BinaryWriter::default();
+        writer.bytes(MAGIC);
+        writer.u32(self.version);
+        writer.u8(0);
+        writer.u8(self.unk0d as u8);
+        writer.i16(0);
+        writer.i32(self.groups.len() as i32);
+        writer.i32(self.unk14);
+        writer.i32(self.unk18);
+        // Section offsets, filled in as the sections get written.
+        for _ in 0..14 {
+            writer.i32(0);
+        }
+        if self.version == 5 {
+            writer.f32(self.unk50);
+        }
+
+        let fields = || self.groups.iter().flat_map(|g| g.fields.iter());
+        for field in fields() {
+            if field.keyframes.len() > u8::MAX as usize {
+                return Err(FormatError::InvalidData {
+                    offset: writer.position(),
+                    reason: format!("field {} has too many keyframes", field.name1),
+                });
+            }
+
+            if let Some(k) = field
+                .keyframes
+                .iter()
+                .find(|k| k.value.value_type() != field.value_type)
+            {
+                return Err(FormatError::InvalidData {
+                    offset: writer.position(),
+                    reason: format!(
+                        "keyframe {} of field {} is not a {}",
+                        k.id,
+                        field.name1,
+                        field.value_type.name()
+                    ),
+                });
+            }
+        }
+
+        let group_header_offsets = writer.position();
+        for _ in self.groups.iter() {
+            writer.i32(0);
+        }
+
+        let group_headers = writer.position();
+        let mut field_offsets_placeholders = vec![];
+        for (i, group) in self.groups.iter().enumerate() {
+            writer.patch_i32(
+                group_header_offsets + i * 4,
+                (writer.position() - group_headers) as i32,
+            );
+            writer.i32(group.fields.len() as i32);
+            field_offsets_placeholders.push(writer.position());
+            writer.i32(0);
+            writer.utf16(&group.name1);
+            writer.utf16(&group.name2);
+            writer.pad(4);
+        }
+
+        let param_header_offsets = writer.position();
+        for (group, placeholder) in self.groups.iter().zip(field_offsets_placeholders) {
+            writer.patch_i32(
+                placeholder,
+                (writer.position() - param_header_offsets) as i32,
+            );
+            for _ in group.fields.iter() {
+                writer.i32(0);
+            }
+        }
+
+        let param_headers = writer.position();
+        let mut field_placeholders = vec![];
+        for (i, field) in fields().enumerate() {
+            writer.patch_i32(
+                param_header_offsets + i * 4,
+                (writer.position() - param_headers) as i32,
+            );
+            field_placeholders.push(writer.position());
+            writer.i32(0);
+            writer.i32(0);
+            writer.u8(field.value_type as u8);
+            writer.u8(field.keyframes.len() as u8);
+            writer.i16(0);
+            writer.utf16(&field.name1);
+            writer.utf16(&field.name2);
+            writer.pad(4);
+        }
+
+        let values = writer.position();
+        for (field, placeholder) in fields().zip(field_placeholders.iter()) {
+            writer.patch_i32(*placeholder, (writer.position() - values) as i32);
+            for keyframe in field.keyframes.iter() {
+                keyframe.value.write(&mut writer);
+            }
+            writer.pad(4);
+        }
+
+        let value_ids = writer.position();
+        for (field, placeholder) in fields().zip(field_placeholders.iter()) {
+            writer.patch_i32(placeholder + 4, (writer.position() - value_ids) as i32);
+            for keyframe in field.keyframes.iter() {
+                writer.i32(keyframe.id);
+                if self.version == 5 {
+                    writer.f32(keyframe.time_of_day);
+                }
+            }
+        }
+
+        let unk2 = writer.position();
+        writer.bytes(&self.unk2);
+
+        let unk3 = writer.position();
+        let mut unk3_placeholders = vec![];
+        for entry in self.unk3.iter() {
+            writer.i32(entry.group_index);
+            writer.i32(entry.value_ids.len() as i32);
+            unk3_placeholders.push(writer.position());
+            writer.i32(0);
+            if self.version == 5 {
+                writer.i32(entry.unk0c);
+            }
+        }
+
+        let unk3_value_ids = writer.position();
+        for (entry, placeholder) in self.unk3.iter().zip(unk3_placeholders) {
+            writer.patch_i32(placeholder, (writer.position() - unk3_value_ids) as i32);
+            for id in entry.value_ids.iter() {
+                writer.i32(*id);
+            }
+        }
+
+        let comment_offsets_offsets = writer.position();
+        for _ in self.groups.iter() {
+            writer.i32(0);
+        }
+
+        let comment_offsets = writer.position();
+        for (i, group) in self.groups.iter().enumerate() {
+            writer.patch_i32(
+                comment_offsets_offsets + i * 4,
+                (writer.position() - comment_offsets) as i32,
+            );
+            for _ in group.comments.iter() {
+                writer.i32(0);
+            }
+        }
+
+        let comments = writer.position();
+        for (i, comment) in self
+            .groups
+            .iter()
+            .flat_map(|g| g.comments.iter())
+            .enumerate()
+        {
+            writer.patch_i32(
+                comment_offsets + i * 4,
+                (writer.position() - comments) as i32,
+            );
+            writer.utf16(comment);
+        }
+
+        for (i, offset) in [
+            group_header_offsets,
+            group_headers,
+            param_header_offsets,
+            param_headers,
+            values,
+            value_ids,
+            unk2,
+            self.unk3.len(),
+            unk3,
+            unk3_value_ids,
+            0,
+            comment_offsets_offsets,
+            comment_offsets,
+            comments,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            writer.patch_i32(0x1C + i * 4, offset as i32);
+        }
+
+        Ok(writer.into_inner())
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups
+            .iter()
+            .find(|g| g.name1 == name || g.name2 == name)
+    }
+
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut Group> {
+        self.groups
+            .iter_mut()
+            .find(|g| g.name1 == name || g.name2 == name)
+    }
+
+    /// Renders the GPARAM in its text representation.
+    pub fn to_text(&self) -> String {
+        let mut output = String::new();
+        writeln!(
+            output,
+            "gparam {} unk0d={} unk14={} unk18={:#x} unk50={:?}",
+            self.version, self.unk0d, self.unk14, self.unk18 as u32, self.unk50
+        )
+        .unwrap();
+
+        for group in self.groups.iter() {
+            writeln!(output, "group {:?} {:?}", group.name1, group.name2).unwrap();
+            for comment in group.comments.iter() {
+                writeln!(output, "comment {comment:?}").unwrap();
+            }
+
+            for field in group.fields.iter() {
+                writeln!(
+                    output,
+                    "field {:?} {:?} {}",
+                    field.name1,
+                    field.name2,
+                    field.value_type.name()
+                )
+                .unwrap();
+
+                for keyframe in field.keyframes.iter() {
+                    write!(output, "key {} {:?} ", keyframe.id, keyframe.time_of_day).unwrap();
+                    keyframe.value.format_into(&mut output);
+                    output.push('\n');
+                }
+            }
+        }
+
+        for entry in self.unk3.iter() {
+            write!(output, "unk3 {} {}", entry.group_index, entry.unk0c).unwrap();
+            for id in entry.value_ids.iter() {
+                write!(output, " {id}").unwrap();
+            }
+            output.push('\n');
+        }
+
+        if !self.unk2.is_empty() {
+            output.push_str("unk2");
+            for byte in self.unk2.iter() {
+                write!(output, " {byte:02x}").unwrap();
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /// Parses the text representation produced by [`Gparam::to_text`].
+    pub fn from_text(source: &str) -> Result<Self, String> {
+        let mut result: Option<Self> = None;
+
+        for (line_number, line) in source.lines().enumerate() {
+            let error = |reason: String| format!("line {}: {reason}", line_number + 1);
+            let tokens = tokenize(line).map_err(error)?;
+            let Some((keyword, args)) = tokens.split_first() else {
+                continue;
+            };
+
+            if keyword == "gparam" {
+                let version = args
+                    .first()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| error("expected version".to_string()))?;
+
+                let mut gparam = Self {
+                    version,
+                    groups: vec![],
+                    unk0d: false,
+                    unk14: 0,
+                    unk18: 0,
+                    unk2: vec![],
+                    unk3: vec![],
+                    unk50: 0.0,
+                };
+
+                for arg in args.iter().skip(1) {
+                    let invalid = || error(format!("invalid header value {arg}"));
+                    let (key, value) = arg.split_once('=').ok_or_else(invalid)?;
+                    match key {
+                        "unk0d" => gparam.unk0d = value.parse().map_err(|_| invalid())?,
+                        "unk14" => gparam.unk14 = value.parse().map_err(|_| invalid())?,
+                        "unk18" => {
+                            gparam.unk18 = u32::from_str_radix(value.trim_start_matches("0x"), 16)
+                                .map_err(|_| invalid())?
+                                as i32
+                        }
+                        "unk50" => gparam.unk50 = value.parse().map_err(|_| invalid())?,
+                        _ => return Err(invalid()),
+                    }
+                }
+
+                result = Some(gparam);
+                continue;
+            }
+
+            let gparam = result
+                .as_mut()
+                .ok_or_else(|| error("expected gparam header first".to_string()))?;
+
+            match keyword.as_str() {
+                "group" => {
+                    let [name1, name2] = args else {
+                        return Err(error("expected group names".to_string()));
+                    };
+
+                    gparam.groups.push(Group {
+                        name1: name1.clone(),
+                        name2: name2.clone(),
+                        fields: vec![],
+                        comments: vec![],
+                    });
+                }
+                "comment" => {
+                    let ([comment], Some(group)) = (args, gparam.groups.last_mut()) else {
+                        return Err(error("expected comment inside a group".to_string()));
+                    };
+
+                    group.comments.push(comment.clone());
+                }
+                "field" => {
+                    let ([name1, name2, value_type], Some(group)) =
+                        (args, gparam.groups.last_mut())
+                    else {
+                        return Err(error("expected field inside a group".to_string()));
+                    };
+
+                    let value_type = ValueType::ALL
+                        .into_iter()
+                        .find(|t| t.name() == value_type)
+                        .ok_or_else(|| error(format!("unknown value type {value_type}")))?;
+
+                    group.fields.push(Field {
+                        name1: name1.clone(),
+                        name2: name2.clone(),
+                        value_type,
+                        keyframes: vec![],
+                    });
+                }
+                "key" => {
+                    let Some(field) = gparam.groups.last_mut().and_then(|g| g.fields.last_mut())
+                    else {
+                        return Err(error("expected key inside a field".to_string()));
+                    };
+
+                    let (Some(id), Some(time_of_day)) = (
+                        args.first().and_then(|v| v.parse().ok()),
+                        args.get(1).and_then(|v| v.parse().ok()),
+                    ) else {
+                        return Err(error("expected key id and time of day".to_string()));
+                    };
+
+                    field.keyframes.push(Keyframe {
+                        id,
+                        time_of_day,
+                        value: Value::parse(field.value_type, &args[2..]).map_err(error)?,
+                    });
+                }
+                "unk3" => {
+                    let values = args
+                        .iter()
+                        .map(|v| v.parse())
+                        .collect::<Result<Vec<i32>, _>>()
+                        .map_err(|_| error("invalid unk3 entry".to_string()))?;
+                    let [group_index, unk0c, value_ids @ ..] = values.as_slice() else {
+                        return Err(error("invalid unk3 entry".to_string()));
+                    };
+
+                    gparam.unk3.push(Unk3 {
+                        group_index: *group_index,
+                        unk0c: *unk0c,
+                        value_ids: value_ids.to_vec(),
+                    });
+                }
+                "unk2" => {
+                    gparam.unk2 = args
+                        .iter()
+                        .map(|v| u8::from_str_radix(v, 16))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| error("invalid unk2 bytes".to_string()))?;
+                }
+                _ => return Err(error(format!("unknown keyword {keyword}"))),
+            }
+        }
+
+        result.ok_or_else(|| "missing gparam header".to_string())
+    }
+}
+
+impl Group {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|f| f.name1 == name || f.name2 == name)
+    }
+
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut Field> {
+        self.fields
+            .iter_mut()
+            .find(|f| f.name1 == name || f.name2 == name)
+    }
+}
+
+/// Splits a line into whitespace separated tokens. Tokens can be quoted with Rust's string
+/// escaping rules, everything following a `#` outside of quotes is a comment.
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => break,
+            c if c.is_whitespace() => continue,
+            '"' => {
+                let mut token = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => token.push('\n'),
+                            Some('t') => token.push('\t'),
+                            Some('r') => token.push('\r'),
+                            Some('0') => token.push('\0'),
+                            Some(c @ ('"' | '\\' | '\'')) => token.push(c),
+                            Some('u') => {
+                                let code = chars
+                                    .by_ref()
+                                    .skip_while(|c| *c == '{')
+                                    .take_while(|c| *c != '}')
+                                    .collect::<String>();
+                                token.push(
+                                    u32::from_str_radix(&code, 16)
+                                        .ok()
+                                        .and_then(char::from_u32)
+                                        .ok_or_else(|| format!("invalid escape \\u{{{code}}}"))?,
+                                );
+                            }
+                            _ => return Err("invalid escape in string".to_string()),
+                        },
+                        Some(c) => token.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                tokens.push(token);
+            }
+            c => {
+                let mut token = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    token.push(c);
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Field, Gparam, Group, Keyframe, Unk3, Value, ValueType};
+
+    fn sample() -> Gparam {
+        Gparam {
+            version: 5,
+            groups: vec![
+                Group {
+                    name1: "Fog".to_string(),
+                    name2: "FogParam".to_string(),
+                    fields: vec![
+                        Field {
+                            name1: "Enabled".to_string(),
+                            name2: "enabled".to_string(),
+                            value_type: ValueType::BoolA,
+                            keyframes: vec![Keyframe {
+                                id: 0,
+                                time_of_day: 0.0,
+                                value: Value::BoolA(true),
+                            }],
+                        },
+                        Field {
+                            name1: "Fog Color".to_string(),
+                            name2: "fogColor".to_string(),
+                            value_type: ValueType::Color,
+                            keyframes: vec![
+                                Keyframe {
+                                    id: 0,
+                                    time_of_day: 6.0,
+                                    value: Value::Color([255, 200, 180, 255]),
+                                },
+                                Keyframe {
+                                    id: 1,
+                                    time_of_day: 18.5,
+                                    value: Value::Color([10, 20, 30, 255]),
+                                },
+                            ],
+                        },
+                    ],
+                    comments: vec!["Morning".to_string(), "Dusk \"late\"".to_string()],
+                },
+                Group {
+                    name1: "Light".to_string(),
+                    name2: "LightSet".to_string(),
+                    fields: vec![Field {
+                        name1: "Direction".to_string(),
+                        name2: "dir".to_string(),
+                        value_type: ValueType::Float3,
+                        keyframes: vec![Keyframe {
+                            id: 0,
+                            time_of_day: 12.0,
+                            value: Value::Float3([0.1, -1.0, 0.25]),
+                        }],
+                    }],
+                    comments: vec![],
+                },
+            ],
+            unk0d: true,
+            unk14: 3,
+            unk18: 0x54,
+            unk2: vec![],
+            unk3: vec![Unk3 {
+                group_index: 1,
+                unk0c: 0,
+                value_ids: vec![0, 1],
+            }],
+            unk50: 1.5,
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let bytes = sample().write().unwrap();
+        let parsed = Gparam::parse(&bytes).unwrap();
+
+        assert_eq!(parsed, sample());
+        assert_eq!(parsed.write().unwrap(), bytes);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let text = sample().to_text();
+        assert_eq!(Gparam::from_text(&text).unwrap(), sample());
+
+        let mut negative = sample();
+        negative.unk18 = -2;
+        let text = negative.to_text();
+        assert!(text.contains(" unk18=0xfffffffe "));
+        assert_eq!(Gparam::from_text(&text).unwrap(), negative);
+    }
+
+    #[test]
+    fn edit_from_text() {
+        let mut text = sample().to_text();
+        text = text.replace("key 1 18.5 10 20 30 255", "key 1 19.0 0 0 0 255 # night");
+
+        let gparam = Gparam::from_text(&text).unwrap();
+        let field = gparam.group("Fog").unwrap().field("fogColor").unwrap();
+        assert_eq!(field.keyframes[1].time_of_day, 19.0);
+        assert_eq!(field.keyframes[1].value, Value::Color([0, 0, 0, 255]));
+
+        assert!(Gparam::from_text(&text.replace("key 0 12.0", "key 0 12.0 1.0")).is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_keyframes() {
+        let mut gparam = sample();
+        gparam.groups[0].fields[0].keyframes[0].value = Value::Float(1.0);
+        assert!(gparam.write().is_err());
+    }
+
+    #[test]
+    fn rejects_negative_offsets() {
+        let mut bytes = sample().write().unwrap();
+        // Group header offsets.
+        bytes[0x1C..0x20].copy_from_slice(&(-1i32).to_le_bytes());
+        assert!(Gparam::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_comments_before_their_offsets() {
+        let mut bytes = sample().write().unwrap();
+        let comment_offsets = i32::from_le_bytes(bytes[0x4C..0x50].try_into().unwrap());
+        bytes[0x50..0x54].copy_from_slice(&(comment_offsets - 4).to_le_bytes());
+        assert!(Gparam::parse(&bytes).is_err());
+    }
+}
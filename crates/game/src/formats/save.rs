@@ -0,0 +1,965 @@
+//! Save files (`ER0000.sl2`). A save is a [`Bnd4`] with one entry per character slot named
+//! `USER_DATA000` through `USER_DATA009`, followed by the profile summary and the regulation.
+//!
+//! Every slot entry starts with the MD5 checksum of the slot data that follows it. PC saves store
+//! entries in the clear, other platforms encrypt each entry with AES-128-CBC and prefix it with
+//! its IV.
+//!
+//! Modified slots are written back over the data they were read from, so sections this module
+//! doesn't decode are preserved byte for byte.
+use std::{
+    mem::{offset_of, size_of},
+    ops::Range,
+};
+
+use aes::cipher::{block_padding::NoPadding, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use md5::{Digest, Md5};
+
+use super::{bnd4::Bnd4, pod_bytes, BinaryReader, FormatError};
+use crate::cs::{
+    CSGaitemImp, ChrAsm, ChrAsmArmStyle, ChrAsmEquipEntries, EquipInventoryData, FaceDataBuffer,
+    FlagBlock, GaitemCategory, GaitemHandle, ItemCategory, ItemId, MapId, PlayerGameData,
+    PlayerGameDataSpEffect,
+};
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+
+pub const SLOT_COUNT: usize = 10;
+
+const CHECKSUM_SIZE: usize = 0x10;
+const IV_SIZE: usize = 0x10;
+
+// Sections of a character slot, in the order the game serializes them. Sections that mirror a
+// game structure take their sizes from it.
+/// Inventory indices of the equipped items.
+const EQUIP_DATA_SIZE: usize = 0x58;
+/// Gaitem handle, quantity and acquisition index.
+const INVENTORY_ENTRY_SIZE: usize = 0xC;
+const EQUIP_MAGIC_DATA_SIZE: usize = 0x74;
+const EQUIP_ITEM_DATA_SIZE: usize = 0x8C;
+const EQUIP_GESTURE_DATA_SIZE: usize = 0x18;
+const EQUIP_PHYSICS_DATA_SIZE: usize = 0xC;
+const FACE_DATA_SIZE: usize = 0x12F;
+const GESTURE_GAME_DATA_SIZE: usize = 0x100;
+/// Everything between the unlocked regions and the event flags: horse, blood stain, menu
+/// profile, trophies, gaitem game data, tutorials and game manager state.
+const PRE_EVENT_FLAGS_SIZE: usize =
+    0x28 + 0x1 + 0x44 + 0x8 + 0x1008 + 0x34 + 0x8 + 0x1B58 * 0x10 + 0x408 + 0x1D;
+const EVENT_FLAG_BLOCK_COUNT: usize = 14667;
+
+pub struct SaveFile {
+    /// One entry per character slot, `None` for slots that don't hold a character.
+    pub slots: Vec<Option<CharacterSlot>>,
+    /// The file as read.
+    data: Vec<u8>,
+    /// Location of each slot's entry within `data`.
+    slot_ranges: Vec<Range<usize>>,
+    key: Option<[u8; 16]>,
+}
+
+impl SaveFile {
+    /// Reads a save with unencrypted entries, as written by the PC version.
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        Self::parse_with_key(data, None)
+    }
+
+    /// Reads a save whose entries are encrypted with `key`.
+    pub fn parse_encrypted(data: &[u8], key: &[u8; 16]) -> Result<Self, FormatError> {
+        Self::parse_with_key(data, Some(*key))
+    }
+
+    fn parse_with_key(data: &[u8], key: Option<[u8; 16]>) -> Result<Self, FormatError> {
+        let (archive, offsets) = Bnd4::parse_with_offsets(data)?;
+        let (slots, slot_ranges) = (0..SLOT_COUNT)
+            .map(|i| {
+                let name = slot_entry_name(i);
+                let index = archive
+                    .entries
+                    .iter()
+                    .position(|e| e.name.as_deref() == Some(name.as_str()))
+                    .ok_or_else(|| FormatError::InvalidData {
+                        offset: 0,
+                        reason: format!("missing {name}"),
+                    })?;
+
+                let entry = &archive.entries[index];
+                let slot = CharacterSlot::parse(&decode_entry(&entry.data, key.as_ref())?)?;
+                Ok((slot, offsets[index]..offsets[index] + entry.data.len()))
+            })
+            .collect::<Result<(Vec<_>, Vec<_>), FormatError>>()?;
+
+        Ok(Self {
+            slots,
+            data: data.to_vec(),
+            slot_ranges,
+            key,
+        })
+    }
+
+    /// Serializes the save, recomputing the checksum of every slot and encrypting it again with
+    /// its original IV. Slots set to `None` are left as they were read.
+    pub fn write(&self) -> Result<Vec<u8>, FormatError> {
+        let mut data = self.data.clone();
+        for (slot, range) in self.slots.iter().zip(self.slot_ranges.iter()) {
+            if let Some(slot) = slot {
+                encode_entry(&slot.write()?, self.key.as_ref(), &mut data[range.clone()]).map_err(
+                    |reason| FormatError::InvalidData {
+                        offset: range.start,
+                        reason,
+                    },
+                )?;
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+fn slot_entry_name(index: usize) -> String {
+    format!("USER_DATA{index:03}")
+}
+
+/// Decrypts an entry if needed and checks its checksum, returning the data after the checksum.
+fn decode_entry(data: &[u8], key: Option<&[u8; 16]>) -> Result<Vec<u8>, FormatError> {
+    let mut data = data.to_vec();
+    if let Some(key) = key {
+        if data.len() < IV_SIZE || !(data.len() - IV_SIZE).is_multiple_of(16) {
+            return Err(FormatError::InvalidData {
+                offset: 0,
+                reason: "encrypted entry is not a whole number of blocks".to_string(),
+            });
+        }
+
+        let mut plaintext = data.split_off(IV_SIZE);
+        Aes128CbcDec::new(key.into(), data.as_slice().into())
+            .decrypt_padded_mut::<NoPadding>(&mut plaintext)
+            .unwrap();
+        data = plaintext;
+    }
+
+    if data.len() < CHECKSUM_SIZE {
+        return Err(FormatError::UnexpectedEof {
+            offset: 0,
+            length: CHECKSUM_SIZE,
+        });
+    }
+
+    let (checksum, contents) = data.split_at(CHECKSUM_SIZE);
+    if Md5::digest(contents).as_slice() != checksum {
+        return Err(FormatError::InvalidData {
+            offset: 0,
+            reason: "checksum mismatch".to_string(),
+        });
+    }
+
+    Ok(contents.to_vec())
+}
+
+/// Writes the checksum and contents over an entry, which must keep its size.
+fn encode_entry(contents: &[u8], key: Option<&[u8; 16]>, entry: &mut [u8]) -> Result<(), String> {
+    let (iv, data) = entry.split_at_mut(key.map_or(0, |_| IV_SIZE));
+    if data.len() != CHECKSUM_SIZE + contents.len() {
+        return Err(format!(
+            "slot is {:#x} bytes, expected {:#x}",
+            contents.len(),
+            data.len().saturating_sub(CHECKSUM_SIZE)
+        ));
+    }
+
+    data[..CHECKSUM_SIZE].copy_from_slice(&Md5::digest(contents));
+    data[CHECKSUM_SIZE..].copy_from_slice(contents);
+    if let Some(key) = key {
+        let length = data.len();
+        Aes128CbcEnc::new(key.into(), (&*iv).into())
+            .encrypt_padded_mut::<NoPadding>(data, length)
+            .unwrap();
+    }
+
+    Ok(())
+}
+
+pub struct CharacterSlot {
+    pub version: u32,
+    pub map_id: MapId,
+    gaitems: Vec<SaveGaitem>,
+    pub player_game_data: SavedPlayerGameData,
+    pub sp_effects: [PlayerGameDataSpEffect; PlayerGameData::SP_EFFECT_COUNT],
+    /// Only the part within [`ChrAsm::SAVED_RANGE`] is read and written, the rest is zeroed.
+    pub chr_asm: ChrAsm,
+    pub inventory: SavedInventory,
+    pub equipment: ChrAsmEquipEntries,
+    pub face_data: FaceDataBuffer,
+    pub storage: SavedInventory,
+    unlocked_regions: Vec<u32>,
+    /// Event flag blocks in the order of the game's flag holder, see
+    /// [`EventFlagStore::from_holder`](crate::cs::EventFlagStore::from_holder).
+    pub event_flags: Vec<FlagBlock>,
+    /// Slot data as read, sections that aren't decoded are kept as they are.
+    data: Vec<u8>,
+    offsets: SlotOffsets,
+}
+
+/// Where the writable sections start within [`CharacterSlot::data`].
+struct SlotOffsets {
+    player_game_data: usize,
+    sp_effects: usize,
+    chr_asm: usize,
+    inventory: usize,
+    equipment: usize,
+    face_data: usize,
+    storage: usize,
+    event_flags: usize,
+}
+
+/// Entry of the slot's gaitem table, describing items with per-instance state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveGaitem {
+    pub handle: GaitemHandle,
+    pub item_id: ItemId,
+    /// Ash of war applied to a weapon.
+    pub gem_handle: Option<GaitemHandle>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryItem {
+    /// Position within the inventory's item list.
+    pub slot: usize,
+    pub gaitem_handle: GaitemHandle,
+    /// Item ID behind the handle, resolved through the slot's gaitems. `None` if the handle
+    /// doesn't resolve.
+    pub item_id: Option<ItemId>,
+    pub quantity: u32,
+    /// Used to sort the inventory by acquisition order.
+    pub acquisition_index: u32,
+}
+
+/// Owned counterpart of [`EquipInventoryData`]. The game's inventory points into lists it
+/// allocates and indexes, while saves store each list as a fixed-size array of handles,
+/// quantities and acquisition indices, so the lists are decoded into vectors instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedInventory {
+    pub items: Vec<InventoryItem>,
+    pub key_items: Vec<InventoryItem>,
+    pub next_equip_index: u32,
+    pub next_acquisition_index: u32,
+}
+
+macro_rules! saved_player_fields {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        /// The part of [`PlayerGameData`] that is persisted to save files. Fields live at the same
+        /// offsets as in [`PlayerGameData`], relative to [`PlayerGameData::SAVED_RANGE`].
+        ///
+        /// [`PlayerGameData`] itself can't be held outside of the game as it owns pointers into
+        /// game memory.
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct SavedPlayerGameData {
+            $(pub $field: $ty,)*
+            pub character_name: String,
+        }
+
+        impl SavedPlayerGameData {
+            fn read(data: &[u8]) -> Self {
+                Self {
+                    $($field: <$ty>::from_le_bytes(
+                        data[saved_offset(offset_of!(PlayerGameData, $field))..][..size_of::<$ty>()]
+                            .try_into()
+                            .unwrap(),
+                    ),)*
+                    character_name: read_character_name(data),
+                }
+            }
+
+            fn write(&self, data: &mut [u8]) -> Result<(), FormatError> {
+                $(write_at(
+                    data,
+                    saved_offset(offset_of!(PlayerGameData, $field)),
+                    &self.$field.to_le_bytes(),
+                );)*
+                write_character_name(data, &self.character_name)
+            }
+        }
+    };
+}
+
+saved_player_fields!(
+    current_hp: u32,
+    current_max_hp: u32,
+    base_max_hp: u32,
+    current_fp: u32,
+    current_max_fp: u32,
+    base_max_fp: u32,
+    current_stamina: u32,
+    current_max_stamina: u32,
+    base_max_stamina: u32,
+    vigor: u32,
+    mind: u32,
+    endurance: u32,
+    strength: u32,
+    dexterity: u32,
+    intelligence: u32,
+    faith: u32,
+    arcane: u32,
+    level: u32,
+    rune_count: u32,
+    rune_memory: u32,
+    poison_resist: u32,
+    rot_resist: u32,
+    bleed_resist: u32,
+    death_resist: u32,
+    frost_resist: u32,
+    sleep_resist: u32,
+    madness_resist: u32,
+    gender: u8,
+    archetype: u8,
+    voice_type: u8,
+    starting_gift: u8,
+    unlocked_magic_slots: u8,
+    unlocked_talisman_slots: u8,
+    max_hp_flask: u8,
+    max_fp_flask: u8,
+);
+
+const CHARACTER_NAME_LENGTH: usize = 16;
+
+fn saved_offset(offset: usize) -> usize {
+    offset - PlayerGameData::SAVED_RANGE.start
+}
+
+fn read_character_name(data: &[u8]) -> String {
+    let units = data[saved_offset(PlayerGameData::CHARACTER_NAME_OFFSET)..]
+        .chunks_exact(2)
+        .take(CHARACTER_NAME_LENGTH)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|c| *c != 0)
+        .collect::<Vec<_>>();
+
+    String::from_utf16_lossy(&units)
+}
+
+fn write_character_name(data: &mut [u8], name: &str) -> Result<(), FormatError> {
+    // Whatever follows the terminator is kept if the name didn't change.
+    if read_character_name(data) == name {
+        return Ok(());
+    }
+
+    let offset = saved_offset(PlayerGameData::CHARACTER_NAME_OFFSET);
+    let units = name.encode_utf16().collect::<Vec<_>>();
+    if units.len() > CHARACTER_NAME_LENGTH {
+        return Err(FormatError::InvalidData {
+            offset,
+            reason: format!("character name {name:?} is too long"),
+        });
+    }
+
+    for (i, unit) in units
+        .into_iter()
+        .chain(std::iter::repeat(0))
+        .take(CHARACTER_NAME_LENGTH)
+        .enumerate()
+    {
+        write_at(data, offset + i * 2, &unit.to_le_bytes());
+    }
+
+    Ok(())
+}
+
+fn write_at(data: &mut [u8], offset: usize, bytes: &[u8]) {
+    data[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+impl CharacterSlot {
+    /// Decodes a slot, returning `None` for slots that don't hold a character.
+    pub fn parse(data: &[u8]) -> Result<Option<Self>, FormatError> {
+        let mut reader = BinaryReader::new(data);
+        let version = reader.u32()?;
+        if version == 0 {
+            return Ok(None);
+        }
+
+        let map_id = MapId(reader.i32()?);
+        reader.skip(0x18);
+
+        let gaitems = (0..CSGaitemImp::CAPACITY)
+            .map(|_| SaveGaitem::read(&mut reader))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let player_game_data_offset = reader.position();
+        let player_game_data =
+            SavedPlayerGameData::read(reader.bytes(PlayerGameData::SAVED_RANGE.len())?);
+
+        let sp_effects_offset = reader.position();
+        // Safety: the SP effect entries are plain integers and floats.
+        let sp_effects = unsafe { reader.pod()? };
+        reader.skip(EQUIP_DATA_SIZE);
+        let chr_asm_offset = reader.position();
+        let chr_asm = read_chr_asm(&mut reader)?;
+        let inventory_offset = reader.position();
+        let inventory = SavedInventory::read(
+            &mut reader,
+            EquipInventoryData::INVENTORY_CAPACITY,
+            EquipInventoryData::INVENTORY_KEY_CAPACITY,
+            &gaitems,
+        )?;
+
+        reader.skip(EQUIP_MAGIC_DATA_SIZE + EQUIP_ITEM_DATA_SIZE + EQUIP_GESTURE_DATA_SIZE);
+        let projectile_count = reader.u32()? as usize;
+        reader.skip(projectile_count * 8);
+        let equipment_offset = reader.position();
+        // Safety: the equip entries are all item IDs.
+        let equipment = unsafe { reader.pod()? };
+        reader.skip(EQUIP_PHYSICS_DATA_SIZE);
+
+        let face_data_position = reader.position();
+        let mut face_data_reader = reader.at(face_data_position);
+        reader.skip(FACE_DATA_SIZE);
+        // Safety: the face data buffer is plain bytes and integers.
+        let face_data: FaceDataBuffer = unsafe { face_data_reader.pod()? };
+        if &face_data.magic != b"FACE" {
+            return Err(reader.invalid(face_data_position, "face data not found"));
+        }
+
+        let storage_offset = reader.position();
+        let storage = SavedInventory::read(
+            &mut reader,
+            EquipInventoryData::STORAGE_CAPACITY,
+            EquipInventoryData::STORAGE_KEY_CAPACITY,
+            &gaitems,
+        )?;
+        reader.skip(GESTURE_GAME_DATA_SIZE);
+
+        let region_count = reader.u32()? as usize;
+        let unlocked_regions = (0..region_count)
+            .map(|_| reader.u32())
+            .collect::<Result<_, _>>()?;
+
+        reader.skip(PRE_EVENT_FLAGS_SIZE);
+        let event_flags_offset = reader.position();
+        let event_flags = (0..EVENT_FLAG_BLOCK_COUNT)
+            // Safety: flag blocks are plain bytes.
+            .map(|_| unsafe { reader.pod() })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(Self {
+            version,
+            map_id,
+            gaitems,
+            player_game_data,
+            sp_effects,
+            chr_asm,
+            inventory,
+            equipment,
+            face_data,
+            storage,
+            unlocked_regions,
+            event_flags,
+            data: data.to_vec(),
+            offsets: SlotOffsets {
+                player_game_data: player_game_data_offset,
+                sp_effects: sp_effects_offset,
+                chr_asm: chr_asm_offset,
+                inventory: inventory_offset,
+                equipment: equipment_offset,
+                face_data: face_data_position,
+                storage: storage_offset,
+                event_flags: event_flags_offset,
+            },
+        }))
+    }
+
+    /// Gaitems with per-instance state. Read-only, as the table's layout depends on its contents.
+    pub fn gaitems(&self) -> &[SaveGaitem] {
+        &self.gaitems
+    }
+
+    /// Read-only, as the list isn't of a fixed size.
+    pub fn unlocked_regions(&self) -> &[u32] {
+        &self.unlocked_regions
+    }
+
+    /// Serializes the slot by writing the decoded sections over the data it was read from.
+    pub fn write(&self) -> Result<Vec<u8>, FormatError> {
+        let mut data = self.data.clone();
+        let offsets = &self.offsets;
+        write_at(&mut data, 0, &self.version.to_le_bytes());
+        write_at(&mut data, 4, &self.map_id.0.to_le_bytes());
+
+        self.player_game_data
+            .write(&mut data[offsets.player_game_data..][..PlayerGameData::SAVED_RANGE.len()])?;
+        // Safety: none of these contain padding.
+        unsafe {
+            write_at(&mut data, offsets.sp_effects, pod_bytes(&self.sp_effects));
+            write_at(
+                &mut data,
+                offsets.chr_asm,
+                &pod_bytes(&self.chr_asm)[ChrAsm::SAVED_RANGE],
+            );
+            write_at(&mut data, offsets.equipment, pod_bytes(&self.equipment));
+            write_at(&mut data, offsets.face_data, pod_bytes(&self.face_data));
+        }
+
+        self.inventory.write(
+            &mut data[offsets.inventory..],
+            offsets.inventory,
+            EquipInventoryData::INVENTORY_CAPACITY,
+            EquipInventoryData::INVENTORY_KEY_CAPACITY,
+        )?;
+        self.storage.write(
+            &mut data[offsets.storage..],
+            offsets.storage,
+            EquipInventoryData::STORAGE_CAPACITY,
+            EquipInventoryData::STORAGE_KEY_CAPACITY,
+        )?;
+
+        if self.event_flags.len() != EVENT_FLAG_BLOCK_COUNT {
+            return Err(FormatError::InvalidData {
+                offset: offsets.event_flags,
+                reason: format!(
+                    "{} event flag blocks, expected {EVENT_FLAG_BLOCK_COUNT}",
+                    self.event_flags.len()
+                ),
+            });
+        }
+
+        for (i, block) in self.event_flags.iter().enumerate() {
+            let offset = offsets.event_flags + i * size_of::<FlagBlock>();
+            // Safety: flag blocks are plain bytes.
+            write_at(&mut data, offset, unsafe { pod_bytes(block) });
+        }
+
+        Ok(data)
+    }
+}
+
+impl SaveGaitem {
+    /// Reads a gaitem table entry, the size of which depends on the handle's category. Returns
+    /// `None` for unused entries.
+    fn read(reader: &mut BinaryReader) -> Result<Option<Self>, FormatError> {
+        let handle = GaitemHandle::from(reader.i32()?);
+        let item_id = ItemId::from(reader.i32()?);
+        if !is_valid_handle(&handle) {
+            return Ok(None);
+        }
+
+        let mut gem_handle = None;
+        match handle.category() {
+            Ok(GaitemCategory::Weapon) => {
+                reader.skip(8);
+                gem_handle = Some(GaitemHandle::from(reader.i32()?)).filter(is_valid_handle);
+                reader.skip(1);
+            }
+            Ok(GaitemCategory::Protector) => reader.skip(8),
+            _ => {}
+        }
+
+        Ok(Some(Self {
+            handle,
+            item_id,
+            gem_handle,
+        }))
+    }
+}
+
+fn is_valid_handle(handle: &GaitemHandle) -> bool {
+    !matches!(i32::from(*handle), 0 | -1)
+}
+
+impl SavedInventory {
+    fn read(
+        reader: &mut BinaryReader,
+        capacity: usize,
+        key_capacity: usize,
+        gaitems: &[SaveGaitem],
+    ) -> Result<Self, FormatError> {
+        let mut read_list = |capacity| -> Result<Vec<InventoryItem>, FormatError> {
+            reader.skip(4);
+            let items = (0..capacity)
+                .map(|slot| {
+                    let gaitem_handle = GaitemHandle::from(reader.i32()?);
+                    let quantity = reader.u32()?;
+                    let acquisition_index = reader.u32()?;
+
+                    Ok(is_valid_handle(&gaitem_handle).then(|| InventoryItem {
+                        slot,
+                        gaitem_handle,
+                        item_id: resolve_item_id(gaitem_handle, gaitems),
+                        quantity,
+                        acquisition_index,
+                    }))
+                })
+                .collect::<Result<Vec<_>, FormatError>>()?;
+
+            Ok(items.into_iter().flatten().collect())
+        };
+
+        let items = read_list(capacity)?;
+        let key_items = read_list(key_capacity)?;
+
+        Ok(Self {
+            items,
+            key_items,
+            next_equip_index: reader.u32()?,
+            next_acquisition_index: reader.u32()?,
+        })
+    }
+
+    /// Writes the items to their slots and clears slots that no longer hold an item. `offset` is
+    /// where `data` starts in the slot, for error reporting.
+    fn write(
+        &self,
+        data: &mut [u8],
+        offset: usize,
+        capacity: usize,
+        key_capacity: usize,
+    ) -> Result<(), FormatError> {
+        let mut position = 0;
+        for (items, capacity) in [(&self.items, capacity), (&self.key_items, key_capacity)] {
+            let (count, entries) =
+                data[position..position + 4 + capacity * INVENTORY_ENTRY_SIZE].split_at_mut(4);
+            let occupied = |entries: &[u8]| {
+                entries
+                    .chunks_exact(INVENTORY_ENTRY_SIZE)
+                    .map(|e| GaitemHandle::from(i32::from_le_bytes(e[..4].try_into().unwrap())))
+                    .enumerate()
+                    .filter(|(_, handle)| is_valid_handle(handle))
+                    .map(|(slot, _)| slot)
+                    .collect::<Vec<_>>()
+            };
+
+            // The stored count is adjusted by the change in items rather than recomputed, so an
+            // untouched list keeps whatever the game wrote.
+            let previous = occupied(entries);
+            let stored_count = u32::from_le_bytes((*count).try_into().unwrap());
+            let new_count = (stored_count as usize + items.len()).saturating_sub(previous.len());
+            count.copy_from_slice(&(new_count as u32).to_le_bytes());
+
+            for slot in previous {
+                entries[slot * INVENTORY_ENTRY_SIZE..][..INVENTORY_ENTRY_SIZE].fill(0);
+            }
+
+            let mut written = vec![false; capacity];
+            for item in items.iter() {
+                let entry_offset = offset + position + 4 + item.slot * INVENTORY_ENTRY_SIZE;
+                let entry = entries
+                    .chunks_exact_mut(INVENTORY_ENTRY_SIZE)
+                    .nth(item.slot)
+                    .ok_or_else(|| FormatError::InvalidData {
+                        offset: entry_offset,
+                        reason: format!("inventory slot {} out of range", item.slot),
+                    })?;
+                if std::mem::replace(&mut written[item.slot], true) {
+                    return Err(FormatError::InvalidData {
+                        offset: entry_offset,
+                        reason: format!("inventory slot {} used twice", item.slot),
+                    });
+                }
+
+                write_at(entry, 0, &i32::from(item.gaitem_handle).to_le_bytes());
+                write_at(entry, 4, &item.quantity.to_le_bytes());
+                write_at(entry, 8, &item.acquisition_index.to_le_bytes());
+            }
+
+            position += 4 + capacity * INVENTORY_ENTRY_SIZE;
+        }
+
+        write_at(data, position, &self.next_equip_index.to_le_bytes());
+        write_at(
+            data,
+            position + 4,
+            &self.next_acquisition_index.to_le_bytes(),
+        );
+        Ok(())
+    }
+}
+
+/// Goods and talismans don't carry state, their handles encode the item ID directly. Other
+/// handles refer to an entry in the gaitem table.
+fn resolve_item_id(handle: GaitemHandle, gaitems: &[SaveGaitem]) -> Option<ItemId> {
+    match handle.category().ok()? {
+        GaitemCategory::Goods => Some(ItemId::from_parts(
+            handle.selector() as i32,
+            ItemCategory::Goods,
+        )),
+        GaitemCategory::Accessory => Some(ItemId::from_parts(
+            handle.selector() as i32,
+            ItemCategory::Accessory,
+        )),
+        _ => gaitems
+            .iter()
+            .find(|g| g.handle == handle)
+            .map(|g| g.item_id),
+    }
+}
+
+/// Reads the part of [`ChrAsm`] that is persisted to save files, leaving the rest zeroed.
+fn read_chr_asm(reader: &mut BinaryReader) -> Result<ChrAsm, FormatError> {
+    let position = reader.position();
+    let saved = reader.bytes(ChrAsm::SAVED_RANGE.len())?;
+
+    // The saved range starts with the arm style.
+    let arm_style = u32::from_le_bytes(saved[..4].try_into().unwrap());
+    if arm_style > ChrAsmArmStyle::RightBothHands as u32 {
+        return Err(reader.invalid(position, "unknown arm style"));
+    }
+
+    let mut bytes = [0; size_of::<ChrAsm>()];
+    bytes[ChrAsm::SAVED_RANGE].copy_from_slice(saved);
+    // Safety: the arm style was checked above, everything else is plain integers.
+    unsafe { BinaryReader::new(&bytes).pod() }
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem::{offset_of, size_of};
+
+    use aes::cipher::{block_padding::NoPadding, BlockEncryptMut, KeyIvInit};
+    use md5::{Digest, Md5};
+
+    use super::*;
+    use crate::cs::ChrAsmEquipmentSlots;
+    use crate::formats::{
+        bnd4::{format, Bnd4Entry},
+        BinaryWriter,
+    };
+
+    const WEAPON_HANDLE: i32 = 0x80800001u32 as i32;
+    const GEM_HANDLE: i32 = 0xC0800002u32 as i32;
+    const GOODS_HANDLE: i32 = 0xB0000064u32 as i32;
+
+    /// Builds a slot holding a single weapon with an ash of war and some flasks.
+    fn slot() -> Vec<u8> {
+        let mut writer = BinaryWriter::default();
+        writer.u32(1);
+        writer.i32(0x3C2C2400);
+        writer.bytes(&[0; 0x18]);
+
+        writer.i32(WEAPON_HANDLE);
+        writer.i32(1000000);
+        writer.bytes(&[0; 8]);
+        writer.i32(GEM_HANDLE);
+        writer.u8(0);
+        writer.i32(GEM_HANDLE);
+        writer.i32(0x80002710u32 as i32);
+        writer.bytes(&[0; (CSGaitemImp::CAPACITY - 2) * 8]);
+
+        let mut player = vec![0; PlayerGameData::SAVED_RANGE.len()];
+        let offset = |field| field - PlayerGameData::SAVED_RANGE.start;
+        player[offset(offset_of!(PlayerGameData, vigor))] = 40;
+        player[offset(offset_of!(PlayerGameData, level))] = 120;
+        player[offset(offset_of!(PlayerGameData, archetype))] = 3;
+        for (i, unit) in "Tarnished".encode_utf16().enumerate() {
+            let position = offset(PlayerGameData::CHARACTER_NAME_OFFSET) + i * 2;
+            player[position..position + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        writer.bytes(&player);
+
+        writer.bytes(&[0; PlayerGameData::SP_EFFECT_COUNT * 0x10]);
+        writer.bytes(&[0; EQUIP_DATA_SIZE]);
+        writer.u32(1);
+        writer.bytes(&[0; size_of::<ChrAsmEquipmentSlots>()]);
+        writer.i32(0);
+        writer.i32(WEAPON_HANDLE);
+        writer.bytes(&[0; (ChrAsm::SLOT_COUNT - 2) * 4]);
+        writer.i32(0);
+        writer.i32(1000000);
+        writer.bytes(&[0; (ChrAsm::SLOT_COUNT - 2) * 4]);
+
+        let inventory = |writer: &mut BinaryWriter, capacity, key_capacity| {
+            writer.u32(2);
+            for (handle, quantity, index) in [(WEAPON_HANDLE, 1, 0), (GOODS_HANDLE, 14, 1)] {
+                writer.i32(handle);
+                writer.u32(quantity);
+                writer.u32(index);
+            }
+            writer.bytes(&vec![0; (capacity - 2) * 0xC]);
+            writer.u32(0);
+            writer.bytes(&vec![0; key_capacity * 0xC]);
+            writer.u32(2);
+            writer.u32(2);
+        };
+        inventory(
+            &mut writer,
+            EquipInventoryData::INVENTORY_CAPACITY,
+            EquipInventoryData::INVENTORY_KEY_CAPACITY,
+        );
+
+        writer.bytes(&[0; EQUIP_MAGIC_DATA_SIZE + EQUIP_ITEM_DATA_SIZE + EQUIP_GESTURE_DATA_SIZE]);
+        writer.u32(0);
+        writer.i32(1000000);
+        writer.bytes(&[0; size_of::<ChrAsmEquipEntries>() - 4]);
+        writer.bytes(&[0; EQUIP_PHYSICS_DATA_SIZE]);
+
+        writer.bytes(b"FACE");
+        writer.u32(4);
+        writer.u32(0x120);
+        writer.bytes(&[0; FACE_DATA_SIZE - 12]);
+
+        inventory(
+            &mut writer,
+            EquipInventoryData::STORAGE_CAPACITY,
+            EquipInventoryData::STORAGE_KEY_CAPACITY,
+        );
+        writer.bytes(&[0; GESTURE_GAME_DATA_SIZE]);
+        writer.u32(1);
+        writer.u32(6100000);
+        writer.bytes(&vec![0; PRE_EVENT_FLAGS_SIZE]);
+
+        let mut flags = vec![0; EVENT_FLAG_BLOCK_COUNT * size_of::<FlagBlock>()];
+        flags[0] = 0b1000_0000;
+        writer.bytes(&flags);
+
+        writer.into_inner()
+    }
+
+    fn save(key: Option<&[u8; 16]>) -> Vec<u8> {
+        let mut archive = Bnd4::new(format::IDS | format::NAMES2, true);
+        for i in 0..12 {
+            let mut contents = match i {
+                0 => slot(),
+                _ => vec![0; 4],
+            };
+
+            contents.resize(contents.len().next_multiple_of(16), 0);
+            let mut data = Md5::digest(&contents).to_vec();
+            data.extend(contents);
+            if let Some(key) = key {
+                let iv = [i as u8; 16];
+                let length = data.len();
+                cbc::Encryptor::<aes::Aes128>::new(key.into(), &iv.into())
+                    .encrypt_padded_mut::<NoPadding>(&mut data, length)
+                    .unwrap();
+                data = [iv.as_slice(), &data].concat();
+            }
+
+            archive.entries.push(Bnd4Entry {
+                flags: 0x40,
+                id: i,
+                name: Some(format!("USER_DATA{i:03}")),
+                data,
+            });
+        }
+
+        archive.write()
+    }
+
+    fn check(save: &SaveFile) {
+        assert!(save.slots[1..].iter().all(Option::is_none));
+
+        let slot = save.slots[0].as_ref().unwrap();
+        assert_eq!(slot.map_id, MapId::from_parts(60, 44, 36, 0));
+        assert_eq!(slot.player_game_data.character_name, "Tarnished");
+        assert_eq!(slot.player_game_data.vigor, 40);
+        assert_eq!(slot.player_game_data.level, 120);
+        assert_eq!(slot.player_game_data.archetype, 3);
+
+        assert_eq!(slot.gaitems().len(), 2);
+        assert_eq!(
+            slot.gaitems()[0].gem_handle,
+            Some(GaitemHandle::from(GEM_HANDLE))
+        );
+        assert_eq!(
+            slot.chr_asm.gaitem_handles[1],
+            GaitemHandle::from(WEAPON_HANDLE)
+        );
+        assert_eq!(slot.chr_asm.equipment_param_ids[1], 1000000);
+        assert_eq!(slot.equipment.weapon_primary_left, ItemId::from(1000000));
+
+        let items = &slot.inventory.items;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].item_id, Some(ItemId::from(1000000)));
+        assert_eq!(
+            items[1].item_id,
+            Some(ItemId::from_parts(100, ItemCategory::Goods))
+        );
+        assert_eq!(items[1].quantity, 14);
+        assert_eq!(slot.storage.items.len(), 2);
+
+        assert_eq!(slot.unlocked_regions(), [6100000]);
+        assert!(slot.event_flags[0].get(0.into()));
+        assert!(!slot.event_flags[0].get(1.into()));
+    }
+
+    #[test]
+    fn reads_plain_save() {
+        check(&SaveFile::parse(&save(None)).unwrap());
+    }
+
+    #[test]
+    fn reads_encrypted_save() {
+        let key = [0x42; 16];
+        check(&SaveFile::parse_encrypted(&save(Some(&key)), &key).unwrap());
+        assert!(SaveFile::parse_encrypted(&save(Some(&key)), &[0; 16]).is_err());
+    }
+
+    #[test]
+    fn untouched_save_round_trips() {
+        let plain = save(None);
+        assert_eq!(SaveFile::parse(&plain).unwrap().write().unwrap(), plain);
+
+        let key = [0x42; 16];
+        let encrypted = save(Some(&key));
+        let parsed = SaveFile::parse_encrypted(&encrypted, &key).unwrap();
+        assert_eq!(parsed.write().unwrap(), encrypted);
+    }
+
+    #[test]
+    fn writes_modified_slot() {
+        let key = [0x42; 16];
+        let mut save = SaveFile::parse_encrypted(&save(Some(&key)), &key).unwrap();
+        let slot = save.slots[0].as_mut().unwrap();
+        slot.player_game_data.vigor = 60;
+        slot.player_game_data.level = 140;
+        slot.player_game_data.character_name = "Challenger".to_string();
+
+        slot.inventory.items.remove(1);
+        slot.inventory.items.push(InventoryItem {
+            slot: 5,
+            gaitem_handle: GaitemHandle::from_parts(200, GaitemCategory::Goods),
+            item_id: None,
+            quantity: 3,
+            acquisition_index: 2,
+        });
+        slot.inventory.next_acquisition_index = 3;
+        slot.event_flags[0].set(0.into(), false);
+        slot.event_flags[0].set(1.into(), true);
+
+        let written = SaveFile::parse_encrypted(&save.write().unwrap(), &key).unwrap();
+        let slot = written.slots[0].as_ref().unwrap();
+        assert_eq!(slot.player_game_data.vigor, 60);
+        assert_eq!(slot.player_game_data.level, 140);
+        assert_eq!(slot.player_game_data.character_name, "Challenger");
+
+        let items = &slot.inventory.items;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].slot, 5);
+        assert_eq!(
+            items[1].item_id,
+            Some(ItemId::from_parts(200, ItemCategory::Goods))
+        );
+        assert_eq!(items[1].quantity, 3);
+        assert_eq!(slot.inventory.next_acquisition_index, 3);
+        assert_eq!(slot.storage.items.len(), 2);
+
+        assert!(!slot.event_flags[0].get(0.into()));
+        assert!(slot.event_flags[0].get(1.into()));
+        assert!(written.slots[1..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn rejects_unwritable_changes() {
+        let mut save = SaveFile::parse(&save(None)).unwrap();
+        let slot = save.slots[0].as_mut().unwrap();
+        slot.player_game_data.character_name = "A name that is far too long".to_string();
+        assert!(slot.write().is_err());
+
+        slot.player_game_data.character_name = "Tarnished".to_string();
+        slot.inventory.items[1].slot = EquipInventoryData::INVENTORY_CAPACITY;
+        assert!(slot.write().is_err());
+
+        slot.inventory.items[1].slot = 0;
+        assert!(save.write().is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! TimeAct (TAE) files. Attach timed events to every animation a character can play, hit
+//! boxes, sounds, effects, cancel windows and invincibility frames are all driven by these.
+//! Times are in seconds from the start of the animation, matching the `play_time` in
+//! [`CSChrTimeActModuleAnim`](crate::cs::CSChrTimeActModuleAnim).
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use super::{ArgumentDefinition, BinaryReader, FormatError};
+
+const ANIMATION_HEADER_SIZE: usize = 0x10;
+const EVENT_HEADER_SIZE: usize = 0x18;
+
+#[derive(Debug, Clone)]
+pub struct Tae {
+    pub id: i32,
+    pub skeleton_name: Option<String>,
+    pub sib_name: Option<String>,
+    pub animations: Vec<Animation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// Animation ID within this TAE. The game refers to animations by
+    /// `tae_id * 1_000_000 + id`, so `a10.tae` animation `3000` is played as `10003000`.
+    pub id: i64,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub event_type: i32,
+    pub start_time: f32,
+    pub end_time: f32,
+    unk04: i32,
+    /// Parameter blob for the event. When the event type is unknown the blob runs up to the
+    /// next piece of data in the file so it may contain trailing padding.
+    pub params: Vec<u8>,
+}
+
+impl Tae {
+    pub fn parse(data: &[u8]) -> Result<Self, FormatError> {
+        let mut reader = BinaryReader::new(data);
+        reader.magic(b"TAE ")?;
+
+        let big_endian = reader.u8()?;
+        reader.skip(2);
+        let is_64_bit = reader.u8()?;
+        if big_endian != 0 || is_64_bit != 0xFF {
+            return Err(reader.invalid(4, "only little endian 64-bit TAE is supported"));
+        }
+
+        let version = reader.u32()?;
+        if !(0x1000C..=0x1000D).contains(&version) {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        let mut reader = reader.at(0x50);
+        let id = reader.i32()?;
+        let animation_count = reader.i32()? as usize;
+        let animations_offset = reader.offset()?;
+
+        let mut reader = reader.at(0xB0);
+        let skeleton_name_offset = reader.offset()?;
+        let sib_name_offset = reader.offset()?;
+        let skeleton_name = match skeleton_name_offset {
+            0 => None,
+            offset => Some(reader.at(offset).utf16()?),
+        };
+        let sib_name = match sib_name_offset {
+            0 => None,
+            offset => Some(reader.at(offset).utf16()?),
+        };
+
+        struct RawAnimation {
+            id: i64,
+            event_headers_offset: usize,
+            event_count: usize,
+        }
+
+        // Parameter blobs have no stored length, so every offset the file points at is
+        // collected to find where each blob ends.
+        let mut boundaries = BTreeSet::from([data.len()]);
+        let raw_animations = (0..animation_count)
+            .map(|i| {
+                let mut reader = reader.at(animations_offset + i * ANIMATION_HEADER_SIZE);
+                let id = reader.i64()?;
+                let offset = reader.offset()?;
+
+                let mut reader = reader.at(offset);
+                let event_headers_offset = reader.offset()?;
+                let event_groups_offset = reader.offset()?;
+                let times_offset = reader.offset()?;
+                let _anim_file_offset = reader.offset()?;
+                let event_count = reader.i32()? as usize;
+
+                boundaries.extend([
+                    offset,
+                    event_headers_offset,
+                    event_groups_offset,
+                    times_offset,
+                ]);
+                for i in 0..event_count {
+                    let mut reader = reader.at(event_headers_offset + i * EVENT_HEADER_SIZE);
+                    reader.skip(0x10);
+                    boundaries.insert(reader.offset()?);
+                }
+
+                Ok(RawAnimation {
+                    id,
+                    event_headers_offset,
+                    event_count,
+                })
+            })
+            .collect::<Result<Vec<_>, FormatError>>()?;
+
+        let animations = raw_animations
+            .into_iter()
+            .map(|raw| {
+                let events = (0..raw.event_count)
+                    .map(|i| {
+                        let mut reader =
+                            reader.at(raw.event_headers_offset + i * EVENT_HEADER_SIZE);
+                        let start_time_offset = reader.offset()?;
+                        let end_time_offset = reader.offset()?;
+                        let data_offset = reader.offset()?;
+                        let start_time = reader.at(start_time_offset).f32()?;
+                        let end_time = reader.at(end_time_offset).f32()?;
+
+                        let mut reader = reader.at(data_offset);
+                        let event_type = reader.i32()?;
+                        let unk04 = reader.i32()?;
+                        let params_offset = reader.offset()?;
+
+                        let params_end = boundaries
+                            .range(params_offset + 1..)
+                            .next()
+                            .copied()
+                            .unwrap_or(data.len());
+                        let params_length = params_end.checked_sub(params_offset).ok_or(
+                            FormatError::InvalidData {
+                                offset: params_offset,
+                                reason: "event parameters start past the end of the data"
+                                    .to_string(),
+                            },
+                        )?;
+                        let params = reader.at(params_offset).bytes(params_length)?.to_vec();
+
+                        Ok(Event {
+                            event_type,
+                            start_time,
+                            end_time,
+                            unk04,
+                            params,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, FormatError>>()?;
+
+                Ok(Animation { id: raw.id, events })
+            })
+            .collect::<Result<Vec<_>, FormatError>>()?;
+
+        Ok(Self {
+            id,
+            skeleton_name,
+            sib_name,
+            animations,
+        })
+    }
+
+    pub fn animation(&self, id: i64) -> Option<&Animation> {
+        self.animations.iter().find(|a| a.id == id)
+    }
+}
+
+impl Animation {
+    /// Yields the events that are active at `time` seconds into the animation.
+    pub fn active_events(&self, time: f32) -> impl Iterator<Item = &Event> {
+        self.events.iter().filter(move |e| e.is_active(time))
+    }
+
+    /// Yields the events of a given type, ordered by start time.
+    pub fn events_of_type(&self, event_type: i32) -> impl Iterator<Item = &Event> {
+        let mut events = self
+            .events
+            .iter()
+            .filter(|e| e.event_type == event_type)
+            .collect::<Vec<_>>();
+
+        events.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+        events.into_iter()
+    }
+}
+
+impl Event {
+    pub fn is_active(&self, time: f32) -> bool {
+        time >= self.start_time && time < self.end_time
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventTemplate {
+    pub name: String,
+    pub params: Vec<ArgumentDefinition>,
+}
+
+#[derive(Debug, Default, Clone)]
+/// Names and parameter layouts for event types.
+///
+/// Templates are read from a line based text file. Every line names one event type followed
+/// by its parameters in order, each parameter either a bare type or `name:type`. Empty lines
+/// and lines starting with `#` are ignored:
+///
+/// ```text
+/// # type Name params...
+/// 0 JumpTable jump_table_id:s32 arg_a:s32 arg_b:f32
+/// 128 PlaySound sound_type:s32 sound_id:s32
+/// ```
+pub struct EventTemplates {
+    templates: HashMap<i32, EventTemplate>,
+}
+
+impl EventTemplates {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut templates = HashMap::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: &str| format!("line {}: {reason}", line_number + 1);
+            let mut parts = line.split_whitespace();
+            let event_type = parts
+                .next()
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| error("expected event type"))?;
+            let name = parts
+                .next()
+                .ok_or_else(|| error("missing event name"))?
+                .to_string();
+            let params = parts
+                .map(|p| ArgumentDefinition::parse(p).map_err(|e| error(&e)))
+                .collect::<Result<Vec<_>, String>>()?;
+
+            templates.insert(event_type, EventTemplate { name, params });
+        }
+
+        Ok(Self { templates })
+    }
+
+    pub fn template(&self, event_type: i32) -> Option<&EventTemplate> {
+        self.templates.get(&event_type)
+    }
+
+    /// Renders an event as `Name(param = value, ...) [start - end]`, falling back to the event
+    /// type and raw parameter bytes if there is no template for it.
+    pub fn describe(&self, event: &Event) -> String {
+        let mut output = String::new();
+
+        match self.template(event.event_type) {
+            Some(template) => {
+                write!(output, "{}(", template.name).unwrap();
+
+                let mut offset = 0usize;
+                for (i, param) in template.params.iter().enumerate() {
+                    let size = param.ty.size();
+                    offset = offset.next_multiple_of(size);
+
+                    if i != 0 {
+                        output.push_str(", ");
+                    }
+                    if let Some(name) = &param.name {
+                        write!(output, "{name} = ").unwrap();
+                    }
+                    match event.params.get(offset..offset + size) {
+                        Some(bytes) => param.ty.format_into(bytes, &mut output),
+                        None => output.push('?'),
+                    }
+
+                    offset += size;
+                }
+
+                output.push(')');
+            }
+            None => {
+                write!(output, "Event{}(", event.event_type).unwrap();
+                for (i, byte) in event.params.iter().enumerate() {
+                    if i != 0 {
+                        output.push(' ');
+                    }
+                    write!(output, "{byte:02x}").unwrap();
+                }
+                output.push(')');
+            }
+        }
+
+        write!(output, " [{:?} - {:?}]", event.start_time, event.end_time).unwrap();
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EventTemplates, Tae};
+    use crate::formats::FormatError;
+
+    /// TAE 10 with one animation carrying a sound and an unknown event.
+    fn sample() -> Vec<u8> {
+        let animations = 0xC0;
+        let animation = animations + 0x10;
+        let event_headers = animation + 0x30;
+        let event_data = event_headers + 2 * 0x18;
+        let times = event_data + 0x20 + 0x18;
+
+        let mut data = vec![0u8; animations];
+        data[0..4].copy_from_slice(b"TAE ");
+        data[7] = 0xFF;
+        data[8..12].copy_from_slice(&0x1000Cu32.to_le_bytes());
+        data[0x50..0x54].copy_from_slice(&10i32.to_le_bytes());
+        data[0x54..0x58].copy_from_slice(&1i32.to_le_bytes());
+        data[0x58..0x60].copy_from_slice(&(animations as i64).to_le_bytes());
+
+        let offsets = |values: &[usize]| {
+            values
+                .iter()
+                .flat_map(|v| (*v as i64).to_le_bytes())
+                .collect::<Vec<_>>()
+        };
+
+        data.extend(offsets(&[3000, animation]));
+        data.extend(offsets(&[event_headers, times + 0x10, times, 0]));
+        data.extend(2i32.to_le_bytes());
+        data.extend([0; 0xC]);
+
+        data.extend(offsets(&[times, times + 4, event_data]));
+        data.extend(offsets(&[times + 8, times + 0xC, event_data + 0x20]));
+
+        data.extend(128i32.to_le_bytes());
+        data.extend(0i32.to_le_bytes());
+        data.extend(offsets(&[event_data + 0x10]));
+        data.extend(1i32.to_le_bytes());
+        data.extend(5000i32.to_le_bytes());
+        data.extend([0; 8]);
+
+        data.extend(999i32.to_le_bytes());
+        data.extend(0i32.to_le_bytes());
+        data.extend(offsets(&[event_data + 0x30]));
+        data.extend([0xAA, 0xBB, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(data.len(), times);
+        for time in [0.1f32, 0.3, 0.2, 0.5] {
+            data.extend(time.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn parses_animations() {
+        let tae = Tae::parse(&sample()).unwrap();
+        assert_eq!(tae.id, 10);
+
+        let animation = tae.animation(3000).unwrap();
+        assert_eq!(animation.events.len(), 2);
+        assert_eq!(animation.events[0].event_type, 128);
+        assert_eq!(animation.events[0].start_time, 0.1);
+        assert_eq!(animation.events[0].end_time, 0.3);
+        assert_eq!(animation.events[1].params, [0xAA, 0xBB, 0, 0, 0, 0, 0, 0]);
+
+        let active = animation
+            .active_events(0.25)
+            .map(|e| e.event_type)
+            .collect::<Vec<_>>();
+        assert_eq!(active, [128, 999]);
+        assert_eq!(animation.active_events(0.4).count(), 1);
+    }
+
+    #[test]
+    fn describes_events() {
+        let tae = Tae::parse(&sample()).unwrap();
+        let templates = EventTemplates::parse("128 PlaySound sound_type:s32 sound_id:s32").unwrap();
+        let animation = tae.animation(3000).unwrap();
+
+        assert_eq!(
+            templates.describe(&animation.events[0]),
+            "PlaySound(sound_type = 1, sound_id = 5000) [0.1 - 0.3]"
+        );
+        assert_eq!(
+            templates.describe(&animation.events[1]),
+            "Event999(aa bb 00 00 00 00 00 00) [0.2 - 0.5]"
+        );
+    }
+
+    #[test]
+    fn rejects_parameters_past_the_end() {
+        let mut data = sample();
+        // Parameter offset of the second event, see `sample`.
+        let params_offset = 0xC0 + 0x10 + 0x30 + 2 * 0x18 + 0x20 + 8;
+        let past_end = data.len() as i64 + 0x100;
+        data[params_offset..params_offset + 8].copy_from_slice(&past_end.to_le_bytes());
+
+        assert!(matches!(
+            Tae::parse(&data),
+            Err(FormatError::InvalidData { .. })
+        ));
+    }
+}
@@ -30,6 +30,16 @@ pub struct GXFfxGraphicsResourceManager {
     pub resource_container: OwnedPtr<FxrResourceContainer>,
 }
 
+impl GXFfxGraphicsResourceManager {
+    /// IDs of every FXR currently loaded into the resource container.
+    pub fn loaded_fxr_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.resource_container
+            .fxr_definitions
+            .iter()
+            .map(|node| node.id)
+    }
+}
+
 #[repr(C)]
 pub struct GXFfxSceneCtrl {
     pub vftable: u64,
@@ -1,11 +1,22 @@
 use std::{
-    collections::VecDeque,
     marker::PhantomData,
+    mem::MaybeUninit,
+    ops::{Bound, RangeBounds},
     ptr::{copy_nonoverlapping, NonNull},
 };
 
+use thiserror::Error;
+
 use crate::{dlkr::DLAllocatorBase, pointer::OwnedPtr};
 
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StlError {
+    #[error("Could not allocate {0} bytes")]
+    AllocationFailed(usize),
+    #[error("Capacity overflow")]
+    CapacityOverflow,
+}
+
 #[repr(C)]
 pub struct DoublyLinkedListNode<T> {
     pub next: NonNull<DoublyLinkedListNode<T>>,
@@ -15,7 +26,7 @@ pub struct DoublyLinkedListNode<T> {
 
 #[repr(C)]
 pub struct DoublyLinkedList<T> {
-    allocator: usize,
+    allocator: NonNull<DLAllocatorBase>,
     pub head: NonNull<DoublyLinkedListNode<T>>,
     pub count: u64,
 }
@@ -36,6 +47,21 @@ impl<T> DoublyLinkedList<T> {
         })
     }
 
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        let mut count = self.count;
+        let mut current = self.head;
+
+        std::iter::from_fn(move || {
+            if count == 0 {
+                return None;
+            }
+
+            count -= 1;
+            current = unsafe { current.as_ref() }.next;
+            Some(unsafe { &mut (*current.as_ptr()).value })
+        })
+    }
+
     pub fn len(&self) -> usize {
         self.count as usize
     }
@@ -45,6 +71,210 @@ impl<T> DoublyLinkedList<T> {
     }
 }
 
+/// Mutation mirrors MSVC's `std::list`: nodes come from the list's own allocator and the head is
+/// a sentinel whose value is never initialized.
+impl<T> DoublyLinkedList<T> {
+    /// Creates an empty list that allocates from `allocator`, which must outlive the list.
+    pub fn new(allocator: &mut DLAllocatorBase) -> Result<Self, StlError> {
+        let mut list = Self {
+            allocator: NonNull::from(allocator),
+            head: NonNull::dangling(),
+            count: 0,
+        };
+
+        let mut head = list.allocate_node()?;
+        unsafe {
+            head.as_mut().next = head;
+            head.as_mut().previous = head;
+        }
+        list.head = head;
+        Ok(list)
+    }
+
+    pub fn allocator(&self) -> NonNull<DLAllocatorBase> {
+        self.allocator
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.iter().next()
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        match self.count {
+            0 => None,
+            _ => Some(unsafe { &self.head.as_ref().previous.as_ref().value }),
+        }
+    }
+
+    pub fn try_push_front(&mut self, value: T) -> Result<(), StlError> {
+        self.cursor_front_mut().try_insert_before(value)
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        self.cursor_front_mut().insert_before(value);
+    }
+
+    pub fn try_push_back(&mut self, value: T) -> Result<(), StlError> {
+        self.cursor_back_mut().try_insert_after(value)
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        self.cursor_back_mut().insert_after(value);
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.cursor_front_mut().remove()
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.cursor_back_mut().remove()
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    /// Cursor on the first element, or on the head if the list is empty.
+    pub fn cursor_front_mut(&mut self) -> DoublyLinkedListCursor<'_, T> {
+        let current = unsafe { self.head.as_ref() }.next;
+        DoublyLinkedListCursor {
+            list: self,
+            current,
+        }
+    }
+
+    /// Cursor on the last element, or on the head if the list is empty.
+    pub fn cursor_back_mut(&mut self) -> DoublyLinkedListCursor<'_, T> {
+        let current = unsafe { self.head.as_ref() }.previous;
+        DoublyLinkedListCursor {
+            list: self,
+            current,
+        }
+    }
+
+    fn allocate_node(&mut self) -> Result<NonNull<DoublyLinkedListNode<T>>, StlError> {
+        let size = size_of::<DoublyLinkedListNode<T>>();
+        let allocation = unsafe { self.allocator.as_mut() }
+            .allocate_aligned(size, align_of::<DoublyLinkedListNode<T>>());
+
+        NonNull::new(allocation as *mut DoublyLinkedListNode<T>)
+            .ok_or(StlError::AllocationFailed(size))
+    }
+
+    /// Links a new node holding `value` in front of `next`. `value` is dropped if the node can't
+    /// be allocated.
+    fn link_before(
+        &mut self,
+        mut next: NonNull<DoublyLinkedListNode<T>>,
+        value: T,
+    ) -> Result<(), StlError> {
+        let node = self.allocate_node()?;
+
+        unsafe {
+            let mut previous = next.as_ref().previous;
+            node.write(DoublyLinkedListNode {
+                next,
+                previous,
+                value,
+            });
+            previous.as_mut().next = node;
+            next.as_mut().previous = node;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Unlinks `node`, which must not be the head, and returns its value.
+    fn unlink(&mut self, node: NonNull<DoublyLinkedListNode<T>>) -> T {
+        unsafe {
+            let DoublyLinkedListNode {
+                mut next,
+                mut previous,
+                value,
+            } = node.read();
+            previous.as_mut().next = next;
+            next.as_mut().previous = previous;
+            self.allocator
+                .as_mut()
+                .deallocate(node.as_ptr() as *const u8);
+            self.count -= 1;
+            value
+        }
+    }
+}
+
+impl<T> Drop for DoublyLinkedList<T> {
+    fn drop(&mut self) {
+        self.clear();
+        unsafe { self.allocator.as_mut() }.deallocate(self.head.as_ptr() as *const u8);
+    }
+}
+
+/// Position within a [`DoublyLinkedList`]. The cursor sits either on an element or on the head,
+/// which lies between the last and the first element.
+pub struct DoublyLinkedListCursor<'a, T> {
+    list: &'a mut DoublyLinkedList<T>,
+    current: NonNull<DoublyLinkedListNode<T>>,
+}
+
+impl<T> DoublyLinkedListCursor<'_, T> {
+    /// The element under the cursor, `None` on the head.
+    pub fn current(&mut self) -> Option<&mut T> {
+        match self.is_head() {
+            true => None,
+            false => Some(unsafe { &mut (*self.current.as_ptr()).value }),
+        }
+    }
+
+    pub fn is_head(&self) -> bool {
+        self.current == self.list.head
+    }
+
+    /// Moves to the next element, wrapping through the head.
+    pub fn move_next(&mut self) {
+        self.current = unsafe { self.current.as_ref() }.next;
+    }
+
+    /// Moves to the previous element, wrapping through the head.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe { self.current.as_ref() }.previous;
+    }
+
+    /// Inserts before the cursor. On the head this appends to the back of the list.
+    pub fn try_insert_before(&mut self, value: T) -> Result<(), StlError> {
+        self.list.link_before(self.current, value)
+    }
+
+    pub fn insert_before(&mut self, value: T) {
+        if let Err(e) = self.try_insert_before(value) {
+            panic!("{e}");
+        }
+    }
+
+    /// Inserts after the cursor. On the head this prepends to the front of the list.
+    pub fn try_insert_after(&mut self, value: T) -> Result<(), StlError> {
+        let next = unsafe { self.current.as_ref() }.next;
+        self.list.link_before(next, value)
+    }
+
+    pub fn insert_after(&mut self, value: T) {
+        if let Err(e) = self.try_insert_after(value) {
+            panic!("{e}");
+        }
+    }
+
+    /// Removes the element under the cursor and moves to the next one. Does nothing on the head.
+    pub fn remove(&mut self) -> Option<T> {
+        if self.is_head() {
+            return None;
+        }
+
+        let node = self.current;
+        self.move_next();
+        Some(self.list.unlink(node))
+    }
+}
+
 #[repr(C)]
 pub struct Vector<T>
 where
@@ -99,6 +329,197 @@ where
     }
 }
 
+/// Mutation mirrors MSVC's `std::vector`: storage comes from the vector's own allocator and grows
+/// by half of the current capacity. Zero-sized elements aren't supported.
+impl<T> Vector<T>
+where
+    T: Sized,
+{
+    /// Creates an empty vector that allocates from `allocator`, which must outlive the vector.
+    pub fn new(allocator: &mut DLAllocatorBase) -> Self {
+        Self {
+            allocator: NonNull::from(allocator),
+            begin: None,
+            end: None,
+            capacity: None,
+        }
+    }
+
+    pub fn allocator(&self) -> NonNull<DLAllocatorBase> {
+        self.allocator
+    }
+
+    /// Amount of elements that fit without reallocating.
+    pub fn capacity(&self) -> usize {
+        match (self.begin, self.capacity) {
+            (Some(begin), Some(capacity)) => {
+                (capacity.as_ptr() as usize - begin.as_ptr() as usize) / size_of::<T>()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Makes room for at least `additional` more elements.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), StlError> {
+        assert!(
+            size_of::<T>() != 0,
+            "Vector doesn't support zero-sized types"
+        );
+
+        let len = self.len();
+        let required = len
+            .checked_add(additional)
+            .ok_or(StlError::CapacityOverflow)?;
+        let capacity = self.capacity();
+        if required <= capacity {
+            return Ok(());
+        }
+
+        let capacity = required.max(capacity + capacity / 2);
+        let size = capacity
+            .checked_mul(size_of::<T>())
+            .ok_or(StlError::CapacityOverflow)?;
+        let allocator = unsafe { self.allocator.as_mut() };
+        let Some(buffer) =
+            NonNull::new(allocator.allocate_aligned(size, align_of::<T>()) as *mut T)
+        else {
+            return Err(StlError::AllocationFailed(size));
+        };
+
+        if let Some(begin) = self.begin {
+            unsafe { copy_nonoverlapping(begin.as_ptr(), buffer.as_ptr(), len) };
+            allocator.deallocate(begin.as_ptr() as *const u8);
+        }
+        self.begin = Some(buffer);
+        self.capacity = Some(unsafe { buffer.add(capacity) });
+        unsafe { self.set_len(len) };
+        Ok(())
+    }
+
+    /// Same as [`Vector::try_reserve`], panics if the allocation fails.
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(e) = self.try_reserve(additional) {
+            panic!("{e}");
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.reserve(1);
+        let len = self.len();
+        unsafe {
+            self.begin.unwrap().add(len).write(value);
+            self.set_len(len + 1);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.len().checked_sub(1)?;
+        unsafe {
+            self.set_len(len);
+            Some(self.begin.unwrap().add(len).read())
+        }
+    }
+
+    /// Inserts an element at `index`, shifting everything after it to the right. Panics if
+    /// `index` is greater than the length.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(
+            index <= len,
+            "insertion index {index} out of bounds ({len})"
+        );
+
+        self.reserve(1);
+        unsafe {
+            let slot = self.begin.unwrap().add(index);
+            std::ptr::copy(slot.as_ptr(), slot.add(1).as_ptr(), len - index);
+            slot.write(value);
+            self.set_len(len + 1);
+        }
+    }
+
+    /// Removes the element at `index`, shifting everything after it to the left. Panics if
+    /// `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index {index} out of bounds ({len})");
+
+        unsafe {
+            let slot = self.begin.unwrap().add(index);
+            let value = slot.read();
+            std::ptr::copy(slot.add(1).as_ptr(), slot.as_ptr(), len - index - 1);
+            self.set_len(len - 1);
+            value
+        }
+    }
+
+    /// Drops the elements past `len`, keeping the storage.
+    pub fn truncate(&mut self, len: usize) {
+        let current = self.len();
+        if len >= current {
+            return;
+        }
+
+        unsafe {
+            self.set_len(len);
+            let tail = self.begin.unwrap().add(len);
+            std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+                tail.as_ptr(),
+                current - len,
+            ));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Keeps the elements for which `keep` returns true, in order.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        let len = self.len();
+        let Some(begin) = self.begin else {
+            return;
+        };
+
+        // Elements are leaked rather than dropped twice if `keep` panics.
+        unsafe { self.set_len(0) };
+        let mut kept = 0;
+        for index in 0..len {
+            unsafe {
+                let current = begin.add(index);
+                if keep(current.as_ref()) {
+                    if index != kept {
+                        copy_nonoverlapping(current.as_ptr(), begin.add(kept).as_ptr(), 1);
+                    }
+                    kept += 1;
+                } else {
+                    std::ptr::drop_in_place(current.as_ptr());
+                }
+            }
+        }
+        unsafe { self.set_len(kept) };
+    }
+
+    /// # Safety
+    ///
+    /// The first `len` elements must be initialized and fit the capacity.
+    unsafe fn set_len(&mut self, len: usize) {
+        self.end = self.begin.map(|begin| unsafe { begin.add(len) });
+    }
+}
+
+impl<T> Drop for Vector<T>
+where
+    T: Sized,
+{
+    fn drop(&mut self) {
+        self.clear();
+        if let Some(begin) = self.begin.take() {
+            unsafe { self.allocator.as_mut() }.deallocate(begin.as_ptr() as *const u8);
+        }
+    }
+}
+
 #[repr(C)]
 pub struct Tree<T> {
     allocator: usize,
@@ -115,33 +536,146 @@ impl<T> Tree<T> {
         self.len() == 0
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &mut T> {
-        let mut pending = VecDeque::new();
+    /// Iterates over the tree's values in key order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.nodes_from(self.first())
+            .map(|n| unsafe { &(*n.as_ptr()).value })
+    }
 
-        let start = unsafe { self.head.as_ref().parent };
-        pending.push_back(start);
+    /// Iterates over the tree's values in key order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.nodes_from(self.first())
+            .map(|n| unsafe { &mut (*n.as_ptr()).value })
+    }
 
-        // TODO: clean up this code
-        std::iter::from_fn(move || {
-            if self.size == 0 {
-                None
-            } else if let Some(mut entry) = pending.pop_front() {
-                let entry = unsafe { entry.as_mut() };
+    /// Looks up the value with the given key. `key_of` extracts the key the tree is ordered by
+    /// from a value.
+    pub fn find<K: Ord>(&self, key: &K, key_of: impl Fn(&T) -> K) -> Option<&T> {
+        let node = self.find_node(key, key_of)?;
+        Some(unsafe { &(*node.as_ptr()).value })
+    }
 
-                if unsafe { entry.left.as_ref() }.is_nil == 0 {
-                    pending.push_back(entry.left);
+    pub fn find_mut<K: Ord>(&mut self, key: &K, key_of: impl Fn(&T) -> K) -> Option<&mut T> {
+        let node = self.find_node(key, key_of)?;
+        Some(unsafe { &mut (*node.as_ptr()).value })
+    }
+
+    /// Iterates in key order, starting at the first value whose key is not less than `key`.
+    pub fn lower_bound<K: Ord>(
+        &self,
+        key: &K,
+        key_of: impl Fn(&T) -> K,
+    ) -> impl Iterator<Item = &T> {
+        self.nodes_from(self.lower_bound_node(key, &key_of))
+            .map(|n| unsafe { &(*n.as_ptr()).value })
+    }
+
+    /// Iterates in key order over the values whose keys fall within `range`.
+    pub fn range<K: Ord, R: RangeBounds<K>>(
+        &self,
+        range: R,
+        key_of: impl Fn(&T) -> K,
+    ) -> impl Iterator<Item = &T> {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.lower_bound_node(key, &key_of),
+            Bound::Excluded(key) => {
+                let mut node = self.lower_bound_node(key, &key_of);
+                while !Self::is_nil(node) && key_of(unsafe { &(*node.as_ptr()).value }) == *key {
+                    node = Self::successor(node);
                 }
+                node
+            }
+            Bound::Unbounded => self.first(),
+        };
+
+        self.nodes_from(start)
+            .map(|n| unsafe { &(*n.as_ptr()).value })
+            .take_while(move |value| match range.end_bound() {
+                Bound::Included(key) => key_of(value) <= *key,
+                Bound::Excluded(key) => key_of(value) < *key,
+                Bound::Unbounded => true,
+            })
+    }
+
+    /// Leftmost node, which MSVC keeps in the head's left link. The head itself for empty trees.
+    fn first(&self) -> NonNull<TreeNode<T>> {
+        match self.size {
+            0 => self.head,
+            _ => unsafe { self.head.as_ref() }.left,
+        }
+    }
+
+    fn root(&self) -> NonNull<TreeNode<T>> {
+        match self.size {
+            0 => self.head,
+            _ => unsafe { self.head.as_ref() }.parent,
+        }
+    }
+
+    fn is_nil(node: NonNull<TreeNode<T>>) -> bool {
+        unsafe { node.as_ref() }.is_nil != 0
+    }
 
-                if unsafe { entry.right.as_ref() }.is_nil == 0 {
-                    pending.push_back(entry.right);
+    /// In-order successor of a node, the head once the walk passes the rightmost node.
+    fn successor(node: NonNull<TreeNode<T>>) -> NonNull<TreeNode<T>> {
+        let mut node = node;
+        let right = unsafe { node.as_ref() }.right;
+        if !Self::is_nil(right) {
+            node = right;
+            loop {
+                let left = unsafe { node.as_ref() }.left;
+                if Self::is_nil(left) {
+                    return node;
                 }
+                node = left;
+            }
+        }
 
-                Some(&mut entry.value)
-            } else {
-                None
+        loop {
+            let parent = unsafe { node.as_ref() }.parent;
+            if Self::is_nil(parent) || unsafe { parent.as_ref() }.right != node {
+                return parent;
             }
+            node = parent;
+        }
+    }
+
+    fn nodes_from(
+        &self,
+        start: NonNull<TreeNode<T>>,
+    ) -> impl Iterator<Item = NonNull<TreeNode<T>>> + '_ {
+        let mut current = start;
+        std::iter::from_fn(move || {
+            if Self::is_nil(current) {
+                return None;
+            }
+
+            let node = current;
+            current = Self::successor(node);
+            Some(node)
         })
     }
+
+    fn lower_bound_node<K: Ord>(&self, key: &K, key_of: &impl Fn(&T) -> K) -> NonNull<TreeNode<T>> {
+        let mut result = self.head;
+        let mut node = self.root();
+        while !Self::is_nil(node) {
+            let current = unsafe { node.as_ref() };
+            if key_of(&current.value) < *key {
+                node = current.right;
+            } else {
+                result = node;
+                node = current.left;
+            }
+        }
+
+        result
+    }
+
+    fn find_node<K: Ord>(&self, key: &K, key_of: impl Fn(&T) -> K) -> Option<NonNull<TreeNode<T>>> {
+        let node = self.lower_bound_node(key, &key_of);
+        (!Self::is_nil(node) && key_of(unsafe { &node.as_ref().value }) == *key).then_some(node)
+    }
 }
 
 #[repr(C)]
@@ -155,11 +689,147 @@ pub struct TreeNode<T> {
     value: T,
 }
 
+/// Hash function used to pick an [`UnorderedMap`] bucket.
+pub trait KeyHasher<K> {
+    fn hash(key: &K) -> u64;
+}
+
+/// MSVC's `std::hash`, which runs FNV-1a over the key's bytes.
+pub struct StdHash;
+
+macro_rules! std_hash_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl KeyHasher<$ty> for StdHash {
+                fn hash(key: &$ty) -> u64 {
+                    fnv1a(&key.to_le_bytes())
+                }
+            }
+        )*
+    };
+}
+
+std_hash_bytes!(u8, i8, u16, i16, u32, i32, u64, i64, usize, isize);
+
+impl<T> KeyHasher<*const T> for StdHash {
+    fn hash(key: &*const T) -> u64 {
+        fnv1a(&(*key as usize).to_le_bytes())
+    }
+}
+
+impl<T> KeyHasher<NonNull<T>> for StdHash {
+    fn hash(key: &NonNull<T>) -> u64 {
+        fnv1a(&(key.as_ptr() as usize).to_le_bytes())
+    }
+}
+
+/// 64-bit FNV-1a, as implemented by MSVC's `_Fnv1a_append_bytes`.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// MSVC's `std::pair<const K, V>`.
+#[repr(C)]
+pub struct UnorderedMapEntry<K, V> {
+    pub key: K,
+    pub value: V,
+}
+
+pub type UnorderedMapNode<K, V> = DoublyLinkedListNode<UnorderedMapEntry<K, V>>;
+
+/// MSVC's `std::unordered_map`. Entries live in a single list where each bucket's entries are
+/// adjacent. The bucket vector holds two nodes per bucket, the first and the last of its range,
+/// with both pointing at the list's head if the bucket is empty.
+#[repr(C)]
+pub struct UnorderedMap<K, V, H = StdHash> {
+    pub max_load_factor: f32,
+    _pad4: u32,
+    pub list: DoublyLinkedList<UnorderedMapEntry<K, V>>,
+    pub buckets: Vector<NonNull<UnorderedMapNode<K, V>>>,
+    /// Bucket count minus one, the bucket count is always a power of two.
+    pub mask: usize,
+    pub bucket_count: usize,
+    hasher: PhantomData<H>,
+}
+
+impl<K, V, H> UnorderedMap<K, V, H> {
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.list.iter().map(|e| (&e.key, &e.value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.list.iter_mut().map(|e| (&e.key, &mut e.value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, H> UnorderedMap<K, V, H>
+where
+    K: PartialEq,
+    H: KeyHasher<K>,
+{
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let node = self.find_node(key)?;
+        Some(unsafe { &(*node.as_ptr()).value.value })
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let node = self.find_node(key)?;
+        Some(unsafe { &mut (*node.as_ptr()).value.value })
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find_node(key).is_some()
+    }
+
+    fn find_node(&self, key: &K) -> Option<NonNull<UnorderedMapNode<K, V>>> {
+        let bucket = (H::hash(key) as usize & self.mask) * 2;
+        let (first, last) = match self.buckets.items().get(bucket..bucket + 2)? {
+            [first, last] => (*first, *last),
+            _ => return None,
+        };
+        if first == self.list.head {
+            return None;
+        }
+
+        let mut node = first;
+        loop {
+            let current = unsafe { node.as_ref() };
+            if current.value.key == *key {
+                return Some(node);
+            }
+            if node == last {
+                return None;
+            }
+            node = current.next;
+        }
+    }
+}
+
+/// Vector with inline storage for up to `N` elements.
+#[repr(C)]
 pub struct DLFixedVector<T, const N: usize>
 where
     T: Sized,
 {
-    elements: [T; N],
+    elements: [MaybeUninit<T>; N],
     // TODO: fact-check this
     unk1: usize,
     count: usize,
@@ -169,11 +839,63 @@ impl<T, const N: usize> DLFixedVector<T, N>
 where
     T: Sized,
 {
+    pub fn items(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.elements.as_ptr().cast(), self.len()) }
+    }
+
+    pub fn items_mut(&mut self) -> &mut [T] {
+        let len = self.len();
+        unsafe { std::slice::from_raw_parts_mut(self.elements.as_mut_ptr().cast(), len) }
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &T> {
-        self.elements[0..self.count].iter()
+        self.items().iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items_mut().iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.count.min(N)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Appends an element, handing it back if the vector is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let len = self.len();
+        if len == N {
+            return Err(value);
+        }
+
+        self.elements[len].write(value);
+        self.count = len + 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.len().checked_sub(1)?;
+        self.count = len;
+        Some(unsafe { self.elements[len].assume_init_read() })
+    }
+
+    pub fn clear(&mut self) {
+        let items: *mut [T] = self.items_mut();
+        self.count = 0;
+        unsafe { std::ptr::drop_in_place(items) };
     }
 }
 
+/// Intrusive list over a fixed pool of entries. Linked entries form a chain through `head`, a
+/// sentinel that is never part of `data`. Entries that aren't linked have no `next` and are free
+/// to be handed out by [`CSFixedList::allocate`]. Unlinking leaves the value in its slot.
 #[repr(C)]
 pub struct CSFixedList<T, const N: usize>
 where
@@ -187,6 +909,102 @@ where
     pub head: CSFixedListEntry<T>,
 }
 
+impl<T, const N: usize> CSFixedList<T, N>
+where
+    T: Sized,
+{
+    /// Linked entries, starting after the head.
+    pub fn entries(&self) -> impl Iterator<Item = &CSFixedListEntry<T>> {
+        self.entry_ptrs().map(|e| unsafe { &*e.as_ptr() })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries().map(|e| &e.data)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entry_ptrs()
+            .map(|e| unsafe { &mut (*e.as_ptr()).data })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entry_ptrs().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry_ptrs().next().is_none()
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Links the first free entry in `data` at the back of the list and returns its value for
+    /// the caller to fill in. `None` if every entry is in use.
+    pub fn allocate(&mut self) -> Option<&mut T> {
+        let index = self.data.iter().position(|e| e.next.is_none())?;
+        let previous = self.head.previous.filter(|_| self.head.next.is_some());
+        let sentinel = NonNull::from(&mut self.head);
+        let last = previous.unwrap_or(sentinel);
+
+        let mut entry = NonNull::from(&mut self.data[index]);
+        unsafe {
+            let current = entry.as_mut();
+            current.next = Some(sentinel);
+            current.previous = Some(last);
+            current.index = index;
+            (*last.as_ptr()).next = Some(entry);
+            (*sentinel.as_ptr()).previous = Some(entry);
+            Some(&mut current.data)
+        }
+    }
+
+    /// Unlinks the entry stored at `data[index]`, freeing it for [`CSFixedList::allocate`].
+    /// Returns false if the entry wasn't linked.
+    pub fn unlink(&mut self, index: usize) -> bool {
+        let Some(entry) = self.data.get_mut(index) else {
+            return false;
+        };
+        let (Some(next), Some(previous)) = (entry.next.take(), entry.previous.take()) else {
+            return false;
+        };
+
+        unsafe {
+            (*previous.as_ptr()).next = Some(next);
+            (*next.as_ptr()).previous = Some(previous);
+        }
+        true
+    }
+
+    /// Unlinks the entries for which `keep` returns false.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        let base = self.data.as_ptr();
+        let remove = self
+            .entry_ptrs()
+            .filter(|e| !keep(unsafe { &e.as_ref().data }))
+            .map(|e| unsafe { e.as_ptr().offset_from(base) } as usize)
+            .collect::<Vec<_>>();
+
+        for index in remove {
+            self.unlink(index);
+        }
+    }
+
+    fn entry_ptrs(&self) -> impl Iterator<Item = NonNull<CSFixedListEntry<T>>> + '_ {
+        let sentinel = NonNull::from(&self.head);
+        let mut current = self.head.next;
+        let mut remaining = N;
+
+        std::iter::from_fn(move || {
+            let entry = current.filter(|e| *e != sentinel)?;
+            // Guards against walking a corrupted chain forever.
+            remaining = remaining.checked_sub(1)?;
+            current = unsafe { entry.as_ref() }.next;
+            Some(entry)
+        })
+    }
+}
+
 #[repr(C)]
 pub struct CSFixedListEntry<T> {
     pub data: T,
@@ -194,3 +1012,428 @@ pub struct CSFixedListEntry<T> {
     pub previous: Option<NonNull<CSFixedListEntry<T>>>,
     index: usize,
 }
+
+impl<T> CSFixedListEntry<T> {
+    /// Position of the entry within the list's `data`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr::NonNull;
+
+    use super::{
+        fnv1a, CSFixedList, CSFixedListEntry, DLFixedVector, DoublyLinkedList,
+        DoublyLinkedListNode, KeyHasher, StdHash, StlError, Tree, TreeNode, UnorderedMap,
+        UnorderedMapEntry, Vector,
+    };
+    use crate::dlkr::RustAllocator;
+
+    #[test]
+    fn vector_mutation() {
+        let mut allocator = RustAllocator::new(0);
+        let mut vector = Vector::new(allocator.as_base());
+        assert_eq!(vector.capacity(), 0);
+        assert_eq!(vector.pop(), None);
+
+        for value in 0..5 {
+            vector.push(value);
+        }
+        assert_eq!(vector.items(), [0, 1, 2, 3, 4]);
+        assert_eq!(vector.capacity(), 6);
+
+        vector.insert(0, 10);
+        vector.insert(6, 11);
+        assert_eq!(vector.remove(3), 2);
+        assert_eq!(vector.items(), [10, 0, 1, 3, 4, 11]);
+
+        vector.retain(|value| value % 2 == 1);
+        assert_eq!(vector.items(), [1, 3, 11]);
+        vector.truncate(1);
+        assert_eq!(vector.pop(), Some(1));
+        assert!(vector.is_empty());
+
+        vector.reserve(20);
+        assert_eq!(vector.capacity(), 20);
+        assert_eq!(allocator.stats().live_allocations, 1);
+        drop(vector);
+        assert_eq!(allocator.stats().live_allocations, 0);
+    }
+
+    #[test]
+    fn vector_drops_elements() {
+        let mut allocator = RustAllocator::new(0);
+        let counter = std::rc::Rc::new(());
+        let mut vector = Vector::new(allocator.as_base());
+        for _ in 0..4 {
+            vector.push(counter.clone());
+        }
+
+        vector.remove(0);
+        vector.retain(|_| false);
+        vector.push(counter.clone());
+        vector.clear();
+        assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn linked_list_mutation() {
+        let mut allocator = RustAllocator::new(0);
+        let mut list = DoublyLinkedList::new(allocator.as_base()).unwrap();
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.back(), None);
+
+        list.push_back(2);
+        list.push_back(3);
+        list.push_front(1);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!((list.front(), list.back()), (Some(&1), Some(&3)));
+
+        list.iter_mut().for_each(|value| *value *= 10);
+        assert_eq!(list.pop_back(), Some(30));
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [20]);
+        assert_eq!(list.len(), 1);
+        assert_eq!(allocator.stats().live_allocations, 2);
+
+        drop(list);
+        assert_eq!(allocator.stats().live_allocations, 0);
+    }
+
+    #[test]
+    fn linked_list_cursor() {
+        let mut allocator = RustAllocator::new(0);
+        let mut list = DoublyLinkedList::new(allocator.as_base()).unwrap();
+        (1..=5).for_each(|value| list.push_back(value));
+
+        let mut cursor = list.cursor_front_mut();
+        while !cursor.is_head() {
+            match cursor.current().copied() {
+                Some(value) if value % 2 == 0 => {
+                    cursor.remove();
+                }
+                Some(value) => {
+                    cursor.insert_after(value * 10);
+                    cursor.move_next();
+                    cursor.move_next();
+                }
+                None => unreachable!(),
+            }
+        }
+        // On the head, inserting before appends and inserting after prepends.
+        assert_eq!(cursor.remove(), None);
+        cursor.insert_before(99);
+        cursor.insert_after(0);
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 99));
+
+        assert_eq!(
+            list.iter().copied().collect::<Vec<_>>(),
+            [0, 1, 10, 3, 30, 5, 50, 99]
+        );
+        assert_eq!(list.len(), 8);
+
+        list.clear();
+        assert!(list.is_empty());
+        assert_eq!(allocator.stats().live_allocations, 1);
+    }
+
+    #[test]
+    fn linked_list_allocation_failure() {
+        let node = size_of::<DoublyLinkedListNode<u64>>();
+        let mut allocator = RustAllocator::with_capacity(0, 2 * node);
+        let mut list = DoublyLinkedList::new(allocator.as_base()).unwrap();
+
+        assert_eq!(list.try_push_back(1u64), Ok(()));
+        assert_eq!(
+            list.try_push_front(0),
+            Err(StlError::AllocationFailed(node))
+        );
+        assert_eq!(
+            list.cursor_front_mut().try_insert_after(2),
+            Err(StlError::AllocationFailed(node))
+        );
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [1]);
+
+        list.pop_back();
+        assert_eq!(list.cursor_back_mut().try_insert_before(3), Ok(()));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn fixed_sizes() {
+        use crate::{cs::SummonMsgData, pointer::OwnedPtr};
+        use std::mem::{offset_of, size_of};
+
+        assert_eq!(0x220, size_of::<CSFixedList<SummonMsgData, 4>>());
+        assert_eq!(0x1b8, offset_of!(CSFixedList<SummonMsgData, 4>, head));
+        assert_eq!(0x50, size_of::<DLFixedVector<OwnedPtr<u8>, 8>>());
+    }
+
+    #[test]
+    fn fixed_vector_push_pop() {
+        let mut vector: DLFixedVector<String, 3> = unsafe { std::mem::zeroed() };
+        assert!(vector.is_empty());
+        assert_eq!(vector.capacity(), 3);
+
+        for value in ["a", "b", "c"] {
+            vector.push(value.to_string()).unwrap();
+        }
+        assert_eq!(vector.push("d".to_string()), Err("d".to_string()));
+        vector.iter_mut().for_each(|value| value.push('!'));
+        assert_eq!(vector.items(), ["a!", "b!", "c!"]);
+
+        assert_eq!(vector.pop().as_deref(), Some("c!"));
+        vector.clear();
+        assert_eq!(vector.pop(), None);
+        assert_eq!(vector.iter().count(), 0);
+    }
+
+    #[test]
+    fn fixed_list_image() {
+        // Image of a list holding data[2] followed by data[0], linked through the head.
+        let mut list = unsafe {
+            let mut image = Box::<CSFixedList<u32, 4>>::new_zeroed();
+            let list = image.as_mut_ptr();
+            (&raw mut (*list).head_ptr)
+                .cast::<*mut CSFixedListEntry<u32>>()
+                .write(&raw mut (*list).head);
+            image.assume_init()
+        };
+        for (index, entry) in list.data.iter_mut().enumerate() {
+            entry.data = index as u32 * 100;
+            entry.index = index;
+        }
+        let head = NonNull::from(&mut list.head);
+        let first = NonNull::from(&mut list.data[2]);
+        let second = NonNull::from(&mut list.data[0]);
+        list.head.next = Some(first);
+        list.head.previous = Some(second);
+        list.data[2].next = Some(second);
+        list.data[2].previous = Some(head);
+        list.data[0].next = Some(head);
+        list.data[0].previous = Some(first);
+
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [200, 0]);
+        assert_eq!(list.len(), 2);
+
+        *list.allocate().unwrap() = 7;
+        *list.allocate().unwrap() = 8;
+        assert_eq!(list.allocate(), None);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [200, 0, 7, 8]);
+        assert_eq!(
+            list.entries().map(|e| e.index()).collect::<Vec<_>>(),
+            [2, 0, 1, 3]
+        );
+
+        assert!(list.unlink(0));
+        assert!(!list.unlink(0));
+        list.retain(|value| *value != 200);
+        list.iter_mut().for_each(|value| *value += 1);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [8, 9]);
+
+        *list.allocate().unwrap() = 1;
+        assert_eq!(list.entries().last().map(|e| e.index()), Some(0));
+        assert!(list.unlink(1) && list.unlink(3) && list.unlink(0));
+        assert!(list.is_empty());
+    }
+
+    /// Lays entries out like MSVC: grouped by bucket in the list, with each bucket's first and
+    /// last node in the bucket vector.
+    fn synthetic_map<H: KeyHasher<u32>>(
+        allocator: &mut RustAllocator,
+        bucket_count: usize,
+        entries: &[(u32, &'static str)],
+    ) -> UnorderedMap<u32, &'static str, H> {
+        let mask = bucket_count - 1;
+        let mut sorted = entries.to_vec();
+        sorted.sort_by_key(|(key, _)| H::hash(key) as usize & mask);
+
+        let mut list = DoublyLinkedList::new(allocator.as_base()).unwrap();
+        for (key, value) in sorted {
+            list.push_back(UnorderedMapEntry { key, value });
+        }
+
+        let mut buckets = Vector::new(allocator.as_base());
+        (0..bucket_count * 2).for_each(|_| buckets.push(list.head));
+        let mut node = unsafe { list.head.as_ref() }.next;
+        while node != list.head {
+            let bucket = (H::hash(&unsafe { node.as_ref() }.value.key) as usize & mask) * 2;
+            let items = buckets.items_mut();
+            if items[bucket] == list.head {
+                items[bucket] = node;
+            }
+            items[bucket + 1] = node;
+            node = unsafe { node.as_ref() }.next;
+        }
+
+        UnorderedMap {
+            max_load_factor: 1.0,
+            _pad4: 0,
+            list,
+            buckets,
+            mask,
+            bucket_count,
+            hasher: std::marker::PhantomData,
+        }
+    }
+
+    #[test]
+    fn unordered_map_layout() {
+        assert_eq!(0x50, std::mem::size_of::<UnorderedMap<u32, u64>>());
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(StdHash::hash(&1u32), fnv1a(&[1, 0, 0, 0]));
+    }
+
+    #[test]
+    fn unordered_map_lookup() {
+        let mut allocator = RustAllocator::new(0);
+        let entries = [(10, "ten"), (20, "twenty"), (30, "thirty"), (40, "forty")];
+        let mut map = synthetic_map::<StdHash>(&mut allocator, 8, &entries);
+
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(&20), Some(&"twenty"));
+        assert_eq!(map.get(&50), None);
+        *map.get_mut(&40).unwrap() = "vierzig";
+        assert!(map.contains_key(&40));
+
+        let mut keys = map.keys().copied().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, [10, 20, 30, 40]);
+        map.iter_mut().for_each(|(_, value)| *value = "x");
+        assert!(map.values().all(|value| *value == "x"));
+    }
+
+    #[test]
+    fn unordered_map_collisions() {
+        struct Identity;
+        impl KeyHasher<u32> for Identity {
+            fn hash(key: &u32) -> u64 {
+                *key as u64
+            }
+        }
+
+        let mut allocator = RustAllocator::new(0);
+        let entries = [(1, "a"), (5, "b"), (9, "c"), (2, "d")];
+        let map = synthetic_map::<Identity>(&mut allocator, 4, &entries);
+
+        for (key, value) in entries {
+            assert_eq!(map.get(&key), Some(&value));
+        }
+        assert_eq!(map.get(&13), None);
+        assert_eq!(map.get(&3), None);
+    }
+
+    fn tree_node<T>(value: T) -> NonNull<TreeNode<T>> {
+        let node = Box::leak(Box::new(TreeNode {
+            left: NonNull::dangling(),
+            parent: NonNull::dangling(),
+            right: NonNull::dangling(),
+            black_red: 0,
+            is_nil: 0,
+            _pad1a: [0; 6],
+            value,
+        }));
+        NonNull::from(node)
+    }
+
+    /// Links sorted values into a balanced tree shaped like MSVC's: leaves point at the head,
+    /// which holds the root, leftmost and rightmost nodes.
+    fn synthetic_tree(values: &[(u32, char)]) -> Tree<(u32, char)> {
+        fn link(
+            values: &[(u32, char)],
+            parent: NonNull<TreeNode<(u32, char)>>,
+            head: NonNull<TreeNode<(u32, char)>>,
+        ) -> NonNull<TreeNode<(u32, char)>> {
+            if values.is_empty() {
+                return head;
+            }
+
+            let middle = values.len() / 2;
+            let mut node = tree_node(values[middle]);
+            let current = unsafe { node.as_mut() };
+            current.parent = parent;
+            current.left = link(&values[..middle], node, head);
+            current.right = link(&values[middle + 1..], node, head);
+            node
+        }
+
+        let mut head = tree_node((0, '-'));
+        unsafe { head.as_mut() }.is_nil = 1;
+        let root = link(values, head, head);
+
+        let mut leftmost = root;
+        let mut rightmost = root;
+        unsafe {
+            while leftmost != head && leftmost.as_ref().left != head {
+                leftmost = leftmost.as_ref().left;
+            }
+            while rightmost != head && rightmost.as_ref().right != head {
+                rightmost = rightmost.as_ref().right;
+            }
+
+            let head = head.as_mut();
+            head.parent = root;
+            head.left = leftmost;
+            head.right = rightmost;
+        }
+
+        Tree {
+            allocator: 0,
+            head,
+            size: values.len(),
+        }
+    }
+
+    #[test]
+    fn tree_iterates_in_order() {
+        let values = (0..13).map(|i| (i * 10, (b'a' + i as u8) as char));
+        let mut tree = synthetic_tree(&values.collect::<Vec<_>>());
+        assert_eq!(tree.len(), 13);
+        assert_eq!(
+            tree.iter().map(|v| v.0).collect::<Vec<_>>(),
+            (0..13).map(|i| i * 10).collect::<Vec<_>>()
+        );
+
+        tree.iter_mut().for_each(|v| v.0 += 1);
+        assert_eq!(tree.iter().next(), Some(&(1, 'a')));
+        assert_eq!(tree.iter().last(), Some(&(121, 'm')));
+
+        let empty = synthetic_tree(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.iter().count(), 0);
+        assert_eq!(empty.find(&0, |v| v.0), None);
+    }
+
+    #[test]
+    fn tree_keyed_lookup() {
+        let values = [(2, 'a'), (3, 'b'), (5, 'c'), (8, 'd'), (13, 'e'), (21, 'f')];
+        let mut tree = synthetic_tree(&values);
+
+        assert_eq!(tree.find(&8, |v| v.0), Some(&(8, 'd')));
+        assert_eq!(tree.find(&9, |v| v.0), None);
+        assert_eq!(tree.find(&1, |v| v.0), None);
+        tree.find_mut(&13, |v| v.0).unwrap().1 = 'z';
+        assert_eq!(tree.find(&13, |v| v.0), Some(&(13, 'z')));
+
+        let keys =
+            |iter: &mut dyn Iterator<Item = &(u32, char)>| iter.map(|v| v.0).collect::<Vec<_>>();
+        assert_eq!(keys(&mut tree.lower_bound(&4, |v| v.0)), [5, 8, 13, 21]);
+        assert_eq!(keys(&mut tree.lower_bound(&5, |v| v.0)), [5, 8, 13, 21]);
+        assert_eq!(keys(&mut tree.lower_bound(&22, |v| v.0)), []);
+        assert_eq!(keys(&mut tree.range(3..13, |v| v.0)), [3, 5, 8]);
+        assert_eq!(keys(&mut tree.range(3..=13, |v| v.0)), [3, 5, 8, 13]);
+        assert_eq!(keys(&mut tree.range(..4, |v| v.0)), [2, 3]);
+        assert_eq!(keys(&mut tree.range(9.., |v| v.0)), [13, 21]);
+        assert_eq!(
+            keys(&mut tree.range(
+                (std::ops::Bound::Excluded(5), std::ops::Bound::Unbounded),
+                |v| v.0
+            )),
+            [8, 13, 21]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use game::cs::{EventFlag, EventFlagSource, FlagBlock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagEvent {
+    Set(EventFlag),
+    Cleared(EventFlag),
+}
+
+impl FlagEvent {
+    pub fn flag(&self) -> EventFlag {
+        match self {
+            FlagEvent::Set(flag) | FlagEvent::Cleared(flag) => *flag,
+        }
+    }
+}
+
+/// Flags and whole flag groups to watch.
+#[derive(Clone, Debug, Default)]
+pub struct FlagWatch {
+    flags: BTreeSet<EventFlag>,
+    groups: BTreeSet<u32>,
+}
+
+impl FlagWatch {
+    pub fn flag(mut self, flag: impl Into<EventFlag>) -> Self {
+        self.flags.insert(flag.into());
+        self
+    }
+
+    pub fn group(mut self, group: u32) -> Self {
+        self.groups.insert(group);
+        self
+    }
+}
+
+/// Remembers the state of the watched flags and reports what changed since the previous update.
+/// Doesn't touch the game so it can be driven by any [`EventFlagSource`].
+pub struct FlagDiff {
+    watch: FlagWatch,
+    flags: BTreeMap<EventFlag, bool>,
+    groups: BTreeMap<u32, FlagBlock>,
+    initialized: bool,
+}
+
+impl FlagDiff {
+    pub fn new(watch: FlagWatch) -> Self {
+        Self {
+            watch,
+            flags: BTreeMap::new(),
+            groups: BTreeMap::new(),
+            initialized: false,
+        }
+    }
+
+    /// Takes a snapshot of the watched flags and returns the changes since the last snapshot,
+    /// ordered by flag. The first update only records the initial state. Flags in groups that
+    /// don't exist are treated as cleared.
+    pub fn update(&mut self, source: &(impl EventFlagSource + ?Sized)) -> Vec<FlagEvent> {
+        let mut events = vec![];
+        let divisor = source.divisor();
+
+        for group in self.watch.groups.iter() {
+            let current = source.group_block(*group).cloned().unwrap_or_default();
+            let previous = self.groups.insert(*group, current.clone());
+            let previous = previous.unwrap_or_default();
+            if !self.initialized || previous == current {
+                continue;
+            }
+
+            // The last group is cut off at u32::MAX.
+            let flags = (0..divisor.min(FlagBlock::FLAG_COUNT))
+                .map_while(|index| Some((index, group.checked_mul(divisor)?.checked_add(index)?)));
+            for (index, flag) in flags {
+                let flag = EventFlag::from(flag);
+                match (previous.get_index(index), current.get_index(index)) {
+                    (false, true) => events.push(FlagEvent::Set(flag)),
+                    (true, false) => events.push(FlagEvent::Cleared(flag)),
+                    _ => {}
+                }
+            }
+        }
+
+        for flag in self.watch.flags.iter() {
+            // Already covered by its group.
+            let group = u32::from(*flag).checked_div(divisor);
+            if group.is_some_and(|group| self.watch.groups.contains(&group)) {
+                continue;
+            }
+
+            let current = source.get_flag(*flag);
+            let previous = self.flags.insert(*flag, current);
+            match (self.initialized, previous, current) {
+                (true, Some(false), true) => events.push(FlagEvent::Set(*flag)),
+                (true, Some(true), false) => events.push(FlagEvent::Cleared(*flag)),
+                _ => {}
+            }
+        }
+
+        self.initialized = true;
+        events.sort_by_key(FlagEvent::flag);
+        events
+    }
+}
+
+type Subscriber = Box<dyn FnMut(&FlagEvent) + Send>;
+
+/// Delivers flag changes to subscribers. Call [`EventFlagWatcher::update`] once per frame, for
+/// example from a recurring task with the `CSEventFlagMan` singleton.
+pub struct EventFlagWatcher {
+    diff: FlagDiff,
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventFlagWatcher {
+    pub fn new(watch: FlagWatch) -> Self {
+        Self {
+            diff: FlagDiff::new(watch),
+            subscribers: vec![],
+        }
+    }
+
+    /// Registers a callback that receives every change of the watched flags.
+    pub fn subscribe(&mut self, subscriber: impl FnMut(&FlagEvent) + Send + 'static) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    pub fn update(&mut self, source: &(impl EventFlagSource + ?Sized)) {
+        for event in self.diff.update(source) {
+            for subscriber in self.subscribers.iter_mut() {
+                subscriber(&event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use game::cs::{EventFlagLayout, EventFlagStore};
+
+    use super::{EventFlagWatcher, FlagDiff, FlagEvent, FlagWatch};
+
+    fn flags() -> EventFlagStore {
+        EventFlagStore::new(EventFlagLayout::parse("divisor 1000\n71 0\n9 1").unwrap())
+    }
+
+    #[test]
+    fn reports_changes() {
+        let mut flags = flags();
+        flags.set(71190, true);
+
+        let mut diff = FlagDiff::new(FlagWatch::default().group(71).flag(9101).flag(71190));
+        assert!(diff.update(&flags).is_empty());
+
+        flags.set(71190, false);
+        flags.set(71801, true);
+        flags.set(9101, true);
+        flags.set(9102, true);
+        assert_eq!(
+            diff.update(&flags),
+            [
+                FlagEvent::Set(9101.into()),
+                FlagEvent::Cleared(71190.into()),
+                FlagEvent::Set(71801.into()),
+            ]
+        );
+        assert!(diff.update(&flags).is_empty());
+    }
+
+    #[test]
+    fn delivers_to_subscribers() {
+        let mut flags = flags();
+        let mut watcher = EventFlagWatcher::new(FlagWatch::default().flag(9101));
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = received.clone();
+        watcher.subscribe(move |event| sink.lock().unwrap().push(*event));
+
+        watcher.update(&flags);
+        flags.set(9101, true);
+        watcher.update(&flags);
+        flags.set(9101, false);
+        watcher.update(&flags);
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            [FlagEvent::Set(9101.into()), FlagEvent::Cleared(9101.into())]
+        );
+    }
+
+    #[test]
+    fn watches_last_group() {
+        let mut flags =
+            EventFlagStore::new(EventFlagLayout::parse("divisor 1000\n4294967 0").unwrap());
+        let mut diff = FlagDiff::new(FlagWatch::default().group(4294967));
+        assert!(diff.update(&flags).is_empty());
+
+        flags.set(u32::MAX, true);
+        assert_eq!(diff.update(&flags), [FlagEvent::Set(u32::MAX.into())]);
+    }
+
+    #[test]
+    fn follows_divisor() {
+        let mut flags =
+            EventFlagStore::new(EventFlagLayout::parse("divisor 100\n7 0\n8 1").unwrap());
+        let mut diff = FlagDiff::new(FlagWatch::default().group(7).flag(750).flag(815));
+        assert!(diff.update(&flags).is_empty());
+
+        for flag in [750, 799, 800, 815] {
+            flags.set(flag, true);
+        }
+        assert_eq!(
+            diff.update(&flags),
+            [
+                FlagEvent::Set(750.into()),
+                FlagEvent::Set(799.into()),
+                FlagEvent::Set(815.into()),
+            ]
+        );
+    }
+}
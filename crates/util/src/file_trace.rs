@@ -0,0 +1,839 @@
+//! Records what the DL file system does with the files it hands out, to find out which files are
+//! read during a hitch.
+use std::{
+    collections::VecDeque,
+    ptr::NonNull,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use game::{
+    dlio::{
+        normalize_path, DLFileDeviceBase, DLFileDeviceDriveType, DLFileDeviceImageSPIBase,
+        DLFileDeviceManager, DLFileDeviceVmt, DLFileOperatorBase, DLFileOperatorContainer,
+        DLFileOperatorVmt, DLFileSeekDirection, DLIOResult, OpenFileMode,
+    },
+    dlkr::{DLAllocatorBase, DLPlainLightMutex},
+    dltx::DLString,
+    dlut::DLDateTime,
+};
+use vtable_rs::VPtr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAccessKind {
+    Open(u32),
+    Read {
+        requested: usize,
+        read: Option<usize>,
+    },
+    Write {
+        requested: usize,
+        written: usize,
+    },
+    Seek {
+        offset: i64,
+        direction: u32,
+    },
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileAccess {
+    /// Normalized path, see [`normalize_path`].
+    pub path: String,
+    pub kind: FileAccessKind,
+    pub result: DLIOResult,
+    pub started: Instant,
+    pub duration: Duration,
+}
+
+impl FileAccess {
+    pub fn failed(&self) -> bool {
+        self.result != DLIOResult::Success
+    }
+
+    pub fn ended(&self) -> Instant {
+        self.started + self.duration
+    }
+}
+
+/// Ring buffer of the most recent file accesses. Shared between every traced operator.
+pub struct FileAccessLog {
+    capacity: usize,
+    accesses: Mutex<VecDeque<FileAccess>>,
+}
+
+impl FileAccessLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            accesses: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Adds an access, dropping the oldest one if the log is full. Failing accesses are also
+    /// reported through `tracing`.
+    pub fn record(&self, access: FileAccess) {
+        if access.failed() {
+            tracing::warn!(
+                path = access.path,
+                kind = ?access.kind,
+                result = ?access.result,
+                "File access failed"
+            );
+        } else {
+            tracing::trace!(
+                path = access.path,
+                kind = ?access.kind,
+                duration = ?access.duration,
+                "File access"
+            );
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+        let mut accesses = self.accesses.lock().unwrap();
+        if accesses.len() == self.capacity {
+            accesses.pop_front();
+        }
+        accesses.push_back(access);
+    }
+
+    /// Accesses of a path, oldest first. The path is compared after normalizing it.
+    pub fn for_path(&self, path: &str) -> Vec<FileAccess> {
+        let path = normalize_path(path);
+        self.filter(|access| access.path == path)
+    }
+
+    /// Accesses that overlap the window, like the frames of a hitch.
+    pub fn between(&self, start: Instant, end: Instant) -> Vec<FileAccess> {
+        self.filter(|access| access.started <= end && access.ended() >= start)
+    }
+
+    pub fn failures(&self) -> Vec<FileAccess> {
+        self.filter(FileAccess::failed)
+    }
+
+    /// Total amount of bytes read per path, largest first.
+    pub fn bytes_read(&self) -> Vec<(String, usize)> {
+        let mut totals = Vec::<(String, usize)>::new();
+        for access in self.accesses.lock().unwrap().iter() {
+            let FileAccessKind::Read {
+                read: Some(read), ..
+            } = access.kind
+            else {
+                continue;
+            };
+
+            match totals.iter_mut().find(|(path, _)| *path == access.path) {
+                Some((_, total)) => *total += read,
+                None => totals.push((access.path.clone(), read)),
+            }
+        }
+        totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+        totals
+    }
+
+    pub fn clear(&self) {
+        self.accesses.lock().unwrap().clear();
+    }
+
+    fn filter(&self, predicate: impl Fn(&FileAccess) -> bool) -> Vec<FileAccess> {
+        let accesses = self.accesses.lock().unwrap();
+        accesses.iter().filter(|a| predicate(a)).cloned().collect()
+    }
+}
+
+/// Calls a method of the wrapped operator and mirrors its result and io state.
+macro_rules! forward {
+    ($self:ident.$method:ident($($arg:expr),*)) => {{
+        let inner = $self.inner.as_ptr();
+        let vftable = unsafe { (*inner).vftable };
+        let result = unsafe { (vftable.$method)(&mut *inner.cast(), $($arg),*) };
+        $self.sync();
+        result
+    }};
+}
+
+/// Same as [`forward!`] for methods taking `&self`.
+macro_rules! forward_ref {
+    ($self:ident.$method:ident($($arg:expr),*)) => {{
+        let inner = $self.inner.as_ptr();
+        let vftable = unsafe { (*inner).vftable };
+        unsafe { (vftable.$method)(&*inner.cast(), $($arg),*) }
+    }};
+}
+
+/// Operator that forwards everything to the operator handed out by the wrapped device and logs
+/// opens, reads, writes, seeks, closes and their results.
+#[repr(C)]
+pub struct TracingFileOperator {
+    pub base: DLFileOperatorBase<Self>,
+    inner: NonNull<DLFileOperatorBase>,
+    path: String,
+    log: Arc<FileAccessLog>,
+    /// Size and start of the async read in flight, logged once its status says it completed.
+    pending_read: Option<(usize, Instant)>,
+}
+
+impl TracingFileOperator {
+    /// # Safety
+    ///
+    /// `inner` must point to a live operator that was allocated by its own allocator, this
+    /// operator takes ownership of it.
+    pub unsafe fn new(
+        inner: NonNull<DLFileOperatorBase>,
+        path: &str,
+        log: Arc<FileAccessLog>,
+    ) -> Self {
+        let operator = unsafe { inner.as_ref() };
+        let mut allocator = operator.allocator;
+        let mut operator_path = DLString::new(unsafe { allocator.as_mut() });
+        if let Err(e) = operator_path.assign_utf16(operator.path.as_utf16()) {
+            tracing::error!("Could not copy operator path {}: {e}", operator.path);
+        }
+
+        Self {
+            base: DLFileOperatorBase {
+                vftable: Default::default(),
+                allocator,
+                result: operator.result,
+                owning_operator_container: operator.owning_operator_container,
+                io_state: operator.io_state,
+                owning_file_device: operator.owning_file_device,
+                path: operator_path,
+            },
+            inner,
+            path: normalize_path(path),
+            log,
+            pending_read: None,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn sync(&mut self) {
+        let inner = unsafe { self.inner.as_ref() };
+        self.base.result = inner.result;
+        self.base.io_state = inner.io_state;
+        if inner.path.as_utf16() != self.base.path.as_utf16() {
+            if let Err(e) = self.base.path.assign_utf16(inner.path.as_utf16()) {
+                tracing::error!("Could not copy operator path {}: {e}", inner.path);
+            }
+        }
+    }
+
+    fn record(&self, kind: FileAccessKind, started: Instant) {
+        self.log.record(FileAccess {
+            path: self.path.clone(),
+            kind,
+            result: self.base.result,
+            started,
+            duration: started.elapsed(),
+        });
+    }
+
+    fn record_read(&self, requested: usize, read: Option<usize>, started: Instant) {
+        self.record(FileAccessKind::Read { requested, read }, started);
+    }
+}
+
+impl DLFileOperatorVmt for TracingFileOperator {
+    extern "C" fn destructor(&mut self) {
+        forward!(self.destructor());
+        let mut allocator = self.base.allocator;
+        unsafe { allocator.as_mut() }.deallocate(self.inner.as_ptr() as *const u8);
+
+        // The game frees this operator's memory without running drop glue.
+        unsafe {
+            std::ptr::drop_in_place(&mut self.base.path);
+            std::ptr::drop_in_place(&mut self.path);
+            std::ptr::drop_in_place(&mut self.log);
+        }
+    }
+
+    extern "C" fn copy_from(&mut self, source: &DLFileOperatorBase) -> bool {
+        forward!(self.copy_from(source))
+    }
+
+    extern "C" fn set_path(&mut self, path: &DLString, param_3: bool, param_4: bool) -> bool {
+        forward!(self.set_path(path, param_3, param_4))
+    }
+
+    extern "C" fn set_path_other_1(
+        &mut self,
+        path: &DLString,
+        param_3: bool,
+        param_4: bool,
+    ) -> bool {
+        forward!(self.set_path_other_1(path, param_3, param_4))
+    }
+
+    extern "C" fn set_path_other_2(
+        &mut self,
+        path: &DLString,
+        param_3: bool,
+        param_4: bool,
+    ) -> bool {
+        forward!(self.set_path_other_2(path, param_3, param_4))
+    }
+
+    extern "C" fn set_state(&mut self, param_2: bool, param_3: bool) -> bool {
+        forward!(self.set_state(param_2, param_3))
+    }
+
+    extern "C" fn clear_file_info(&mut self) -> bool {
+        forward!(self.clear_file_info())
+    }
+
+    extern "C" fn get_virtual_disk_operator(&self) -> *const DLFileOperatorBase {
+        forward_ref!(self.get_virtual_disk_operator())
+    }
+
+    extern "C" fn bind_device_image(
+        &mut self,
+        image_spi: &DLFileDeviceImageSPIBase,
+    ) -> *const DLFileDeviceImageSPIBase {
+        forward!(self.bind_device_image(image_spi))
+    }
+
+    extern "C" fn is_readable(&mut self) -> bool {
+        forward!(self.is_readable())
+    }
+
+    extern "C" fn is_writable(&mut self) -> bool {
+        forward!(self.is_writable())
+    }
+
+    extern "C" fn last_access_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
+        forward_ref!(self.last_access_time(ptr))
+    }
+
+    extern "C" fn last_modify_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
+        forward_ref!(self.last_modify_time(ptr))
+    }
+
+    extern "C" fn file_size(&mut self) -> usize {
+        forward!(self.file_size())
+    }
+
+    extern "C" fn get_read_size(&mut self) -> usize {
+        forward!(self.get_read_size())
+    }
+
+    extern "C" fn get_write_size(&self) -> usize {
+        forward_ref!(self.get_write_size())
+    }
+
+    extern "C" fn set_eof(&mut self) {
+        forward!(self.set_eof())
+    }
+
+    extern "C" fn is_eof(&self) -> bool {
+        forward_ref!(self.is_eof())
+    }
+
+    extern "C" fn is_directory(&self) -> bool {
+        forward_ref!(self.is_directory())
+    }
+
+    extern "C" fn is_open(&self) -> bool {
+        forward_ref!(self.is_open())
+    }
+
+    extern "C" fn open(&mut self, open_mode: OpenFileMode) -> bool {
+        let started = Instant::now();
+        let opened = forward!(self.open(open_mode));
+        self.record(FileAccessKind::Open(open_mode.0), started);
+        opened
+    }
+
+    extern "C" fn close(&mut self) -> bool {
+        let started = Instant::now();
+        let closed = forward!(self.close());
+        self.record(FileAccessKind::Close, started);
+        closed
+    }
+
+    extern "C" fn set_read_only(&mut self, is_open: bool) -> bool {
+        forward!(self.set_read_only(is_open))
+    }
+
+    extern "C" fn seek(
+        &mut self,
+        is_stream: bool,
+        offset: i64,
+        seek_mode: DLFileSeekDirection,
+    ) -> bool {
+        let started = Instant::now();
+        let direction = seek_mode as u32;
+        let moved = forward!(self.seek(is_stream, offset, seek_mode));
+        self.record(FileAccessKind::Seek { offset, direction }, started);
+        moved
+    }
+
+    extern "C" fn cursor_position(&self) -> usize {
+        forward_ref!(self.cursor_position())
+    }
+
+    unsafe extern "C" fn read(&mut self, output: *mut u8, length: usize) -> i32 {
+        let started = Instant::now();
+        let read = forward!(self.read(output, length));
+        self.record_read(length, usize::try_from(read).ok(), started);
+        read
+    }
+
+    extern "C" fn write(&mut self, input: *const u8, length: usize) -> usize {
+        let started = Instant::now();
+        let written = forward!(self.write(input, length));
+        let kind = FileAccessKind::Write {
+            requested: length,
+            written,
+        };
+        self.record(kind, started);
+        written
+    }
+
+    extern "C" fn get_async_block_size(&self) -> usize {
+        forward_ref!(self.get_async_block_size())
+    }
+
+    extern "C" fn get_async_buffer_alignment_size(&self) -> usize {
+        forward_ref!(self.get_async_buffer_alignment_size())
+    }
+
+    unsafe extern "C" fn start_async_read(&mut self, output: *mut u8, length: usize) -> bool {
+        let started = Instant::now();
+        let started_read = forward!(self.start_async_read(output, length));
+        // The amount that was read is only known once the read completes.
+        match started_read {
+            true => self.pending_read = Some((length, started)),
+            false => self.record_read(length, None, started),
+        }
+        started_read
+    }
+
+    extern "C" fn start_async_write(&mut self, input: *const u8, length: usize) -> bool {
+        forward!(self.start_async_write(input, length))
+    }
+
+    extern "C" fn query_async_status(
+        &mut self,
+        bytes_remaining: &mut usize,
+        bytes_transferred: Option<&mut usize>,
+    ) -> bool {
+        let mut transferred = 0;
+        let succeeded = forward!(self.query_async_status(bytes_remaining, Some(&mut transferred)));
+        if let Some(bytes_transferred) = bytes_transferred {
+            *bytes_transferred = transferred;
+        }
+
+        if !succeeded || *bytes_remaining == 0 {
+            if let Some((requested, started)) = self.pending_read.take() {
+                self.record_read(requested, succeeded.then_some(transferred), started);
+            }
+        }
+        succeeded
+    }
+
+    extern "C" fn get_open_mode(&self) -> OpenFileMode {
+        forward_ref!(self.get_open_mode())
+    }
+
+    extern "C" fn delete(&mut self) -> bool {
+        forward!(self.delete())
+    }
+
+    extern "C" fn flush(&mut self) {
+        forward!(self.flush())
+    }
+
+    extern "C" fn populate_file_info(&mut self) -> bool {
+        forward!(self.populate_file_info())
+    }
+
+    extern "C" fn unk2(&mut self) -> bool {
+        forward!(self.unk2())
+    }
+
+    extern "C" fn rename_w(&mut self, path: *const u16) -> bool {
+        forward!(self.rename_w(path))
+    }
+
+    extern "C" fn rename(&mut self, path: *const u8) -> bool {
+        forward!(self.rename(path))
+    }
+
+    extern "C" fn create_directory(&mut self) -> bool {
+        forward!(self.create_directory())
+    }
+}
+
+/// Device that hands out [`TracingFileOperator`]s wrapping the operators of another device.
+#[repr(C)]
+pub struct TracingFileDevice {
+    pub vftable: VPtr<dyn DLFileDeviceVmt, Self>,
+    unk8: bool,
+    ref_count: u32,
+    pub mutex: DLPlainLightMutex,
+    inner: NonNull<DLFileDeviceBase>,
+    log: Arc<FileAccessLog>,
+}
+
+impl TracingFileDevice {
+    pub fn new(inner: NonNull<DLFileDeviceBase>, log: Arc<FileAccessLog>) -> Box<Self> {
+        let mut device = Box::<Self>::new_uninit();
+        let ptr = device.as_mut_ptr();
+        unsafe {
+            (&raw mut (*ptr).vftable).write(Default::default());
+            (&raw mut (*ptr).unk8).write(false);
+            (&raw mut (*ptr).ref_count).write(1);
+            DLPlainLightMutex::initialize_at(&raw mut (*ptr).mutex);
+            (&raw mut (*ptr).inner).write(inner);
+            (&raw mut (*ptr).log).write(log);
+            device.assume_init()
+        }
+    }
+
+    pub fn inner(&self) -> NonNull<DLFileDeviceBase> {
+        self.inner
+    }
+
+    fn record_missing(&self, path: &str, started: Instant) {
+        self.log.record(FileAccess {
+            path: normalize_path(path),
+            kind: FileAccessKind::Open(0),
+            result: DLIOResult::NotFound,
+            started,
+            duration: started.elapsed(),
+        });
+    }
+}
+
+impl DLFileDeviceVmt for TracingFileDevice {
+    extern "C" fn destructor(&mut self) {
+        unsafe { self.inner.as_mut() }.destructor();
+    }
+
+    extern "C" fn get_file_operator(
+        &mut self,
+        path_dlstring: &DLString,
+        path_u16: *const u16,
+        operator_container: &mut DLFileOperatorContainer,
+        allocator: &mut DLAllocatorBase,
+        is_temp_file: bool,
+    ) -> *const DLFileOperatorBase {
+        let started = Instant::now();
+        let inner = unsafe { self.inner.as_mut() };
+        let operator = inner.get_file_operator(
+            path_dlstring,
+            path_u16,
+            operator_container,
+            allocator,
+            is_temp_file,
+        );
+
+        let path = path_dlstring.to_string();
+        let Some(operator) = NonNull::new(operator as *mut DLFileOperatorBase) else {
+            self.record_missing(&path, started);
+            return std::ptr::null();
+        };
+        // Encrypted binders are handed to code that expects the device's own operator type.
+        if inner.is_encrypted() {
+            return operator.as_ptr();
+        }
+
+        let memory = allocator.allocate_aligned(
+            std::mem::size_of::<TracingFileOperator>(),
+            std::mem::align_of::<TracingFileOperator>(),
+        ) as *mut TracingFileOperator;
+        if memory.is_null() {
+            return operator.as_ptr();
+        }
+
+        unsafe { memory.write(TracingFileOperator::new(operator, &path, self.log.clone())) };
+        memory as *const DLFileOperatorBase
+    }
+
+    extern "C" fn file_enumerator(&self) -> *const u8 {
+        unsafe { self.inner.as_ref() }.file_enumerator()
+    }
+
+    extern "C" fn get_drive_type(&self, path: *const u16) -> DLFileDeviceDriveType {
+        unsafe { self.inner.as_ref() }.get_drive_type(path)
+    }
+
+    extern "C" fn is_encrypted(&self) -> bool {
+        unsafe { self.inner.as_ref() }.is_encrypted()
+    }
+}
+
+/// Tracing installed on a [`DLFileDeviceManager`].
+pub struct FileTracing {
+    pub log: Arc<FileAccessLog>,
+    /// Leaked as they may still be referenced by live operators after uninstalling.
+    devices: Vec<NonNull<TracingFileDevice>>,
+}
+
+impl FileTracing {
+    /// Wraps every device in the manager's device list and its mounted binders.
+    pub fn install(manager: &mut DLFileDeviceManager, log: Arc<FileAccessLog>) -> Self {
+        manager.mutex.lock();
+        let mut devices = vec![];
+        for slot in device_slots(manager) {
+            let device = NonNull::from(Box::leak(TracingFileDevice::new(*slot, log.clone())));
+            *slot = device.cast();
+            devices.push(device);
+        }
+        manager.mutex.unlock();
+
+        Self { log, devices }
+    }
+
+    /// Puts the original devices back. Operators that were already handed out keep logging.
+    pub fn uninstall(self, manager: &mut DLFileDeviceManager) {
+        manager.mutex.lock();
+        for slot in device_slots(manager) {
+            let device = self
+                .devices
+                .iter()
+                .find(|device| device.as_ptr().cast() == slot.as_ptr());
+            if let Some(device) = device {
+                *slot = unsafe { device.as_ref() }.inner;
+            }
+        }
+        manager.mutex.unlock();
+    }
+}
+
+fn device_slots(
+    manager: &mut DLFileDeviceManager,
+) -> impl Iterator<Item = &mut NonNull<DLFileDeviceBase>> {
+    let binders = manager
+        .bnd3_files
+        .items_mut()
+        .iter_mut()
+        .chain(manager.bnd4_files.items_mut().iter_mut())
+        .map(|entry| &mut entry.device);
+    manager.devices.items_mut().iter_mut().chain(binders)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Cursor,
+        ptr::NonNull,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use game::{
+        dlio::{
+            AdapterFileOperator, DLFileDeviceBase, DLFileDeviceDriveType, DLFileDeviceVmt,
+            DLFileOperatorBase, DLFileOperatorContainer, DLIOResult, OpenFileMode,
+        },
+        dlkr::{DLAllocatorBase, DLPlainLightMutex, RustAllocator},
+        dltx::DLString,
+    };
+    use vtable_rs::VPtr;
+
+    use super::{
+        FileAccess, FileAccessKind, FileAccessLog, TracingFileDevice, TracingFileOperator,
+    };
+
+    /// Device serving a single file from memory.
+    #[repr(C)]
+    struct CursorFileDevice {
+        vftable: VPtr<dyn DLFileDeviceVmt, Self>,
+        unk8: bool,
+        ref_count: u32,
+        mutex: DLPlainLightMutex,
+        path: &'static str,
+        data: Vec<u8>,
+    }
+
+    impl DLFileDeviceVmt for CursorFileDevice {
+        extern "C" fn destructor(&mut self) {}
+
+        extern "C" fn get_file_operator(
+            &mut self,
+            path_dlstring: &DLString,
+            _path_u16: *const u16,
+            operator_container: &mut DLFileOperatorContainer,
+            allocator: &mut DLAllocatorBase,
+            _is_temp_file: bool,
+        ) -> *const DLFileOperatorBase {
+            if *path_dlstring != self.path {
+                return std::ptr::null();
+            }
+
+            let device = unsafe { &*(self as *const Self as *const DLFileDeviceBase) };
+            let data = Cursor::new(self.data.clone());
+            AdapterFileOperator::new(allocator, path_dlstring, operator_container, device, data)
+                .into_allocated(allocator)
+        }
+
+        extern "C" fn file_enumerator(&self) -> *const u8 {
+            std::ptr::null()
+        }
+
+        extern "C" fn get_drive_type(&self, _path: *const u16) -> DLFileDeviceDriveType {
+            DLFileDeviceDriveType::Default
+        }
+
+        extern "C" fn is_encrypted(&self) -> bool {
+            false
+        }
+    }
+
+    fn access(
+        path: &str,
+        kind: FileAccessKind,
+        result: DLIOResult,
+        started: Instant,
+    ) -> FileAccess {
+        FileAccess {
+            path: path.to_string(),
+            kind,
+            result,
+            started,
+            duration: Duration::from_millis(2),
+        }
+    }
+
+    #[test]
+    fn queries_accesses() {
+        let log = FileAccessLog::new(16);
+        let start = Instant::now();
+        let later = start + Duration::from_millis(100);
+        let read = |read| FileAccessKind::Read {
+            requested: 0x100,
+            read,
+        };
+
+        log.record(access(
+            "data0:/a.bin",
+            read(Some(0x100)),
+            DLIOResult::Success,
+            start,
+        ));
+        log.record(access(
+            "data0:/b.bin",
+            read(Some(0x20)),
+            DLIOResult::Success,
+            start,
+        ));
+        log.record(access(
+            "data0:/a.bin",
+            read(Some(0x80)),
+            DLIOResult::Success,
+            later,
+        ));
+        log.record(access(
+            "data0:/c.bin",
+            read(None),
+            DLIOResult::Invalid,
+            later,
+        ));
+
+        assert_eq!(log.for_path(r"DATA0:\A.bin").len(), 2);
+        assert_eq!(log.between(later, later).len(), 2);
+        assert_eq!(log.failures()[0].path, "data0:/c.bin");
+        assert_eq!(
+            log.bytes_read(),
+            [
+                ("data0:/a.bin".to_string(), 0x180),
+                ("data0:/b.bin".to_string(), 0x20)
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_oldest() {
+        let log = FileAccessLog::new(2);
+        let now = Instant::now();
+        for path in ["a", "b", "c"] {
+            log.record(access(
+                path,
+                FileAccessKind::Close,
+                DLIOResult::Success,
+                now,
+            ));
+        }
+        assert!(log.for_path("a").is_empty());
+        assert_eq!(log.between(now, now).len(), 2);
+    }
+
+    #[test]
+    fn traces_through_vtable() {
+        let mut device = CursorFileDevice {
+            vftable: Default::default(),
+            unk8: false,
+            ref_count: 1,
+            mutex: DLPlainLightMutex::default(),
+            path: "data0:/regulation.bin",
+            data: b"regulation".to_vec(),
+        };
+        let log = Arc::new(FileAccessLog::new(16));
+        let mut tracing = TracingFileDevice::new(NonNull::from(&mut device).cast(), log.clone());
+        let vmt = tracing.vftable;
+
+        let mut allocator = RustAllocator::new(0);
+        let mut container = DLFileOperatorContainer::new(allocator.as_base());
+        let mut open = |tracing: &mut TracingFileDevice, path: &str| {
+            let path = DLString::from_str(allocator.as_base(), path).unwrap();
+            (vmt.get_file_operator)(
+                tracing,
+                &path,
+                std::ptr::null(),
+                &mut container,
+                allocator.as_base(),
+                false,
+            )
+        };
+
+        assert!(open(&mut tracing, "data0:/missing.bin").is_null());
+        assert_eq!(log.failures()[0].result, DLIOResult::NotFound);
+
+        let operator = open(&mut tracing, "data0:/regulation.bin") as *mut TracingFileOperator;
+        let file = unsafe { &mut *operator };
+        let file_vmt = file.base.vftable;
+        assert_eq!(file.base.path.to_string(), "data0:/regulation.bin");
+        assert!((file_vmt.open)(file, OpenFileMode(0x1)));
+
+        let mut output = [0; 16];
+        assert_eq!(unsafe { (file_vmt.read)(file, output.as_mut_ptr(), 4) }, 4);
+        assert!(unsafe { (file_vmt.start_async_read)(file, output.as_mut_ptr(), 16) });
+        let mut remaining = 1;
+        assert!((file_vmt.query_async_status)(file, &mut remaining, None));
+        assert_eq!(remaining, 0);
+        assert!((file_vmt.close)(file));
+
+        let kinds = log
+            .for_path("data0:/regulation.bin")
+            .into_iter()
+            .map(|access| access.kind)
+            .collect::<Vec<_>>();
+        let read = |requested, read| FileAccessKind::Read {
+            requested,
+            read: Some(read),
+        };
+        assert_eq!(
+            kinds,
+            [
+                FileAccessKind::Open(0x1),
+                read(4, 4),
+                read(16, 6),
+                FileAccessKind::Close
+            ]
+        );
+
+        (file_vmt.destructor)(file);
+        allocator.as_base().deallocate(operator as *const u8);
+        assert_eq!(allocator.stats().live_allocations, 0);
+        assert_eq!(Arc::strong_count(&log), 2);
+    }
+}
@@ -3,9 +3,11 @@ pub mod bullet_manager;
 pub mod camera;
 pub mod character_debug_flags;
 pub mod character_type_properties;
+pub mod event_flag;
 pub mod ez_draw;
 pub mod ez_state;
 pub mod fade;
+pub mod file_trace;
 pub mod gaitem;
 pub mod geometry;
 pub mod havok;